@@ -0,0 +1,142 @@
+use crate::crypto::Cipher;
+use crate::export::ExportedEntry;
+use crate::util;
+use anyhow::{Context, Result};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+/// Entries pruned from the history with `[storage] archive` on, in the storage dir
+pub const ARCHIVE_FILE_NAME: &str = "archive.jsonl.gz";
+
+/// One JSON line per entry, sealed and base64-encoded when there is a cipher
+fn encode(items: &[ExportedEntry], cipher: Option<&Cipher>) -> Result<Vec<u8>> {
+    let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+    for item in items {
+        let json = serde_json::to_vec(item)?;
+        match cipher {
+            Some(cipher) => gz.write_all(util::encode_base64(&cipher.seal(&json)?).as_bytes())?,
+            None => gz.write_all(&json)?,
+        }
+        gz.write_all(b"\n")?;
+    }
+    Ok(gz.finish()?)
+}
+
+/// Add `items` to the end of the archive as a gzip member of their own, so
+/// nothing archived before is rewritten
+pub fn append(path: &Path, items: &[ExportedEntry], cipher: Option<&Cipher>) -> Result<()> {
+    if items.is_empty() {
+        return Ok(());
+    }
+    let data = encode(items, cipher)?;
+    // One write per batch so concurrent writers don't interleave members
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .mode(0o600)
+        .open(path)
+        .and_then(|mut file| file.write_all(&data).and_then(|_| file.sync_all()))
+        .with_context(|| format!("Failed to append to archive {:?}", path))
+}
+
+/// Every archived entry, oldest archived first; none if nothing was archived.
+/// Reading stops at a batch cut short by a crash.
+pub fn read(path: &Path, cipher: Option<&Cipher>) -> Result<Vec<ExportedEntry>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read archive {:?}", path)),
+    };
+    let mut items = Vec::new();
+    for line in BufReader::new(MultiGzDecoder::new(file)).lines() {
+        let line = match line {
+            Ok(line) if line.is_empty() => continue,
+            Ok(line) => line,
+            Err(e) => {
+                log::warn!("[archive] {:?} ends early: {}", path, e);
+                break;
+            }
+        };
+        let json = if line.starts_with('{') {
+            line.into_bytes()
+        } else {
+            let cipher = cipher.context("The archive is encrypted; turn encrypt back on")?;
+            cipher.open(&util::decode_base64(&line).map_err(anyhow::Error::msg)?)?
+        };
+        items.push(serde_json::from_slice(&json).context("Malformed archive entry")?);
+    }
+    Ok(items)
+}
+
+/// Replace the archive with `items` (after a restore took some out)
+pub fn write(path: &Path, items: &[ExportedEntry], cipher: Option<&Cipher>) -> Result<()> {
+    if items.is_empty() {
+        return match fs::remove_file(path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("Failed to remove {:?}", path))
+            }
+            _ => Ok(()),
+        };
+    }
+    crate::backend::atomic_write(path, &encode(items, cipher)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::new_entry;
+    use tempfile::TempDir;
+
+    fn item(id: i64, content: &str) -> ExportedEntry {
+        ExportedEntry {
+            entry: new_entry(id, content, String::new(), Default::default()),
+            content: content.to_string(),
+            representation_content: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_archive_appends_and_rewrites() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(ARCHIVE_FILE_NAME);
+        assert!(read(&path, None).unwrap().is_empty());
+
+        append(&path, &[item(1, "first")], None).unwrap();
+        append(&path, &[item(2, "second"), item(3, "third")], None).unwrap();
+        let contents = |items: Vec<ExportedEntry>| -> Vec<String> {
+            items.into_iter().map(|i| i.content).collect()
+        };
+        assert_eq!(contents(read(&path, None).unwrap()), ["first", "second", "third"]);
+
+        // A batch cut short keeps the ones before it
+        let mut data = fs::read(&path).unwrap();
+        data.extend_from_slice(&encode(&[item(4, "lost")], None).unwrap()[..10]);
+        fs::write(&path, data).unwrap();
+        assert_eq!(read(&path, None).unwrap().len(), 3);
+
+        write(&path, &[item(3, "third")], None).unwrap();
+        assert_eq!(contents(read(&path, None).unwrap()), ["third"]);
+        write(&path, &[], None).unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_encrypted_archive_needs_the_key() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(ARCHIVE_FILE_NAME);
+        let cipher = Cipher::load_or_create(&dir.path().join("key")).unwrap();
+
+        append(&path, &[item(1, "hunter2")], Some(&cipher)).unwrap();
+        let mut plain = String::new();
+        let mut gz = MultiGzDecoder::new(File::open(&path).unwrap());
+        std::io::Read::read_to_string(&mut gz, &mut plain).unwrap();
+        assert!(!plain.contains("hunter2"));
+        assert_eq!(read(&path, Some(&cipher)).unwrap()[0].content, "hunter2");
+        assert!(read(&path, None).is_err());
+    }
+}
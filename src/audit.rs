@@ -0,0 +1,125 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+/// Append-only log in the storage dir (`[storage] audit_log`)
+pub const AUDIT_FILE_NAME: &str = "audit.log";
+
+/// What happened to an entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditAction {
+    /// Added to history (recaptures of existing entries aren't logged)
+    Saved,
+    /// Deleted by the user
+    Deleted,
+    /// Evicted by max_entries or a quota
+    Pruned,
+    /// Older than `[storage] max_age`
+    Expired,
+    /// Removed by `clipstack clear`
+    Cleared,
+    /// Content replaced (`clipstack edit`)
+    Edited,
+    /// Taken back out of the trash
+    Restored,
+    /// Deleted from the trash for good, by `trash empty` or `trash_retention`
+    Purged,
+}
+
+impl AuditAction {
+    pub fn name(self) -> &'static str {
+        match self {
+            AuditAction::Saved => "saved",
+            AuditAction::Deleted => "deleted",
+            AuditAction::Pruned => "pruned",
+            AuditAction::Expired => "expired",
+            AuditAction::Cleared => "cleared",
+            AuditAction::Edited => "edited",
+            AuditAction::Restored => "restored",
+            AuditAction::Purged => "purged",
+        }
+    }
+}
+
+/// One line of the log. Ids and content hashes are recorded, never content.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// Milliseconds since the epoch
+    pub time: i64,
+    pub action: AuditAction,
+    pub id: String,
+    /// Hash of the entry's content at the time (missing in older logs)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+}
+
+/// Append records as JSON lines, creating the log readable by the owner only
+pub fn append(path: &Path, records: &[AuditRecord]) -> Result<()> {
+    if records.is_empty() {
+        return Ok(());
+    }
+    let mut data = String::new();
+    for record in records {
+        data.push_str(&serde_json::to_string(record)?);
+        data.push('\n');
+    }
+    // One write per batch so concurrent writers don't interleave lines
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .mode(0o600)
+        .open(path)
+        .and_then(|mut file| file.write_all(data.as_bytes()))
+        .with_context(|| format!("Failed to append to audit log {:?}", path))
+}
+
+/// Every record in the log, oldest first; none if there is no log yet.
+/// Lines that don't parse (a write cut short) are skipped.
+pub fn read(path: &Path) -> Result<Vec<AuditRecord>> {
+    let data = match fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read audit log {:?}", path)),
+    };
+    Ok(data.lines().filter_map(|line| serde_json::from_str(line).ok()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_append_writes_json_lines() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(AUDIT_FILE_NAME);
+        assert!(read(&path).unwrap().is_empty());
+        let record = |action, id: &str| AuditRecord {
+            time: 1,
+            action,
+            id: id.to_string(),
+            hash: Some("blake3:ab".to_string()),
+        };
+        append(&path, &[record(AuditAction::Saved, "1")]).unwrap();
+        append(&path, &[]).unwrap();
+        append(&path, &[record(AuditAction::Expired, "1"), record(AuditAction::Cleared, "2")])
+            .unwrap();
+
+        let data = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = data.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], r#"{"time":1,"action":"expired","id":"1","hash":"blake3:ab"}"#);
+
+        // Logs from before hashes were recorded, and torn lines
+        let old = r#"{"time":2,"action":"deleted","id":"3"}"#;
+        fs::write(&path, format!("{}\n{}\n{{\"time\":3,", data, old)).unwrap();
+        let records = read(&path).unwrap();
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[3].action, AuditAction::Deleted);
+        assert_eq!(records[3].hash, None);
+    }
+}
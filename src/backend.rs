@@ -0,0 +1,842 @@
+use crate::crypto::{self, Cipher};
+use crate::error::Error;
+use crate::kind::{self, EntryKind};
+use crate::storage::{ClipEntry, ClipIndex, EntryMeta, INDEX_VERSION, MAX_PREVIEW_LEN};
+use crate::util::{self, HashMatcher};
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// First bytes of a gzip stream. No UTF-8 text starts with them, so compressed
+/// content is told apart from plain content by looking.
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// Temp files older than this were left by an interrupted write
+const STALE_TEMP_AGE: Duration = Duration::from_secs(60);
+
+/// Pruning limits on unpinned entries
+pub struct Limits<'a> {
+    pub max_entries: usize,
+    /// Per-kind caps, applied before `max_entries`
+    pub quotas: &'a HashMap<EntryKind, usize>,
+    /// Entries last copied longer ago than this are removed first
+    pub max_age: Option<Duration>,
+    /// Budget in bytes for all content, pinned included, applied last
+    pub max_total_size: Option<usize>,
+    /// Leave the content of removed entries stored, for the caller to archive
+    pub keep_content: bool,
+}
+
+impl Limits<'_> {
+    /// Entries with an older timestamp (ms) have expired
+    pub fn expiry_cutoff(&self) -> Option<i64> {
+        self.max_age.map(|age| util::now_millis() - age.as_millis() as i64)
+    }
+
+    /// Whether an unpinned entry was last copied before `max_age` or is past its `expires`
+    pub fn is_expired(&self, entry: &ClipEntry) -> bool {
+        !entry.pinned
+            && (self.expiry_cutoff().is_some_and(|cutoff| entry.timestamp < cutoff)
+                || entry.expires.is_some_and(|at| at <= util::now_millis()))
+    }
+}
+
+/// Outcome of `StorageBackend::record`
+pub struct Recorded {
+    pub entry: ClipEntry,
+    /// The content was already in history and its entry moved to the front
+    pub duplicate: bool,
+    /// Entries removed to keep within the limits
+    pub pruned: Vec<ClipEntry>,
+}
+
+/// Content to save, hashed under the storage's trailing-newline policy
+pub struct Capture<'a> {
+    pub content: &'a str,
+    pub hash: String,
+    /// Matches hashes stored by older versions
+    pub matcher: HashMatcher<'a>,
+    pub meta: EntryMeta,
+    /// Store the content gzip-compressed (`oversize = "compress"`)
+    pub compress: bool,
+    /// When the entry expires (`ClipEntry::expires`); a new capture restarts it
+    pub expires: Option<i64>,
+}
+
+/// Where entries and their content are persisted. `Storage` keeps the policies
+/// (pin limits, tags, names); a backend only stores. The provided methods work
+/// on the whole index, and backends with indexed lookups override them.
+pub trait StorageBackend: Send + Sync {
+    /// The history, newest first. None if nothing was saved yet; an error if
+    /// the index exists but can't be read.
+    fn read_index(&self) -> Result<Option<ClipIndex>>;
+
+    fn write_index(&self, index: &ClipIndex) -> Result<()>;
+
+    fn read_content(&self, id: &str) -> Result<String>;
+
+    fn write_content(&self, id: &str, content: &str) -> Result<()>;
+
+    /// Like `write_content`, gzip-compressed; `read_content` decompresses it
+    fn write_compressed(&self, id: &str, content: &str) -> Result<()>;
+
+    /// Delete an entry's content, returning the bytes freed (0 if there was none)
+    fn remove_content(&self, id: &str) -> Result<u64>;
+
+    /// Delete an entry's content and that of its representations, returning the bytes freed
+    fn remove_entry_content(&self, entry: &ClipEntry) -> Result<u64> {
+        let mut freed = self.remove_content(&entry.id)?;
+        for id in entry.representation_ids() {
+            freed += self.remove_content(&id)?;
+        }
+        Ok(freed)
+    }
+
+    /// Ids of all stored content, whether or not the index lists them
+    fn content_ids(&self) -> Result<Vec<String>>;
+
+    /// Bytes an entry's content takes up as stored (0 if there is none)
+    fn stored_size(&self, id: &str) -> Result<u64>;
+
+    /// Give back space held beyond the content itself (leftover temp files,
+    /// free database pages). Returns the bytes reclaimed.
+    fn compact(&self) -> Result<u64>;
+
+    /// A value that changes whenever the history is written, for a reader
+    /// watching for another process's saves; None if there is nothing to read
+    fn change_stamp(&self) -> Option<u64>;
+
+    /// The file an entry's content is kept in: its own, or the database all
+    /// entries share
+    fn content_file(&self, id: &str) -> PathBuf;
+
+    /// Save a capture at the front of the history. A matching entry moves to the
+    /// front instead, otherwise a new entry is added and the history pruned.
+    fn record(&self, capture: Capture<'_>, limits: &Limits) -> Result<Recorded> {
+        let mut index = self.read_index()?.unwrap_or_default();
+        if let Some(pos) = index
+            .entries
+            .iter()
+            .position(|e| e.hash == capture.hash || capture.matcher.matches(&e.hash))
+        {
+            let mut existing = index.entries.remove(pos);
+            existing.expires = capture.expires;
+            if merge_duplicate(&mut existing, capture.hash, capture.content, capture.meta) {
+                self.store(&existing.id, capture.content, capture.compress)?;
+            }
+            index.entries.insert(0, existing.clone());
+            self.write_index(&index)?;
+            return Ok(Recorded {
+                entry: existing,
+                duplicate: true,
+                pruned: Vec::new(),
+            });
+        }
+
+        let timestamp = next_timestamp(|id| index.entries.iter().any(|e| e.id == id));
+        let mut entry = new_entry(timestamp, capture.content, capture.hash, capture.meta);
+        entry.expires = capture.expires;
+        self.store(&entry.id, capture.content, capture.compress)?;
+        index.entries.insert(0, entry.clone());
+        let pruned = prune_index(&mut index, limits);
+        for old in pruned.iter().filter(|_| !limits.keep_content) {
+            let _ = self.remove_entry_content(old);
+        }
+        self.write_index(&index)?;
+        Ok(Recorded {
+            entry,
+            duplicate: false,
+            pruned,
+        })
+    }
+
+    fn store(&self, id: &str, content: &str, compress: bool) -> Result<()> {
+        if compress {
+            self.write_compressed(id, content)
+        } else {
+            self.write_content(id, content)
+        }
+    }
+
+    fn get_entry(&self, id: &str) -> Result<Option<ClipEntry>> {
+        let index = self.read_index()?.unwrap_or_default();
+        Ok(index.entries.into_iter().find(|e| e.id == id))
+    }
+
+    /// Remove unpinned entries that are expired or beyond `limits`, returning them
+    fn prune(&self, limits: &Limits) -> Result<Vec<ClipEntry>> {
+        let Some(mut index) = self.read_index()? else {
+            return Ok(Vec::new());
+        };
+        let removed = prune_index(&mut index, limits);
+        for old in removed.iter().filter(|_| !limits.keep_content) {
+            let _ = self.remove_entry_content(old);
+        }
+        if !removed.is_empty() {
+            self.write_index(&index)?;
+        }
+        Ok(removed)
+    }
+}
+
+/// First `MAX_PREVIEW_LEN` chars of content, on a single line
+fn preview_of(content: &str) -> String {
+    content
+        .chars()
+        .take(MAX_PREVIEW_LEN)
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect()
+}
+
+/// Set an entry's preview and `sensitive` flag for its content. Content
+/// holding a secret gets a mask naming what was found instead of its text.
+pub fn set_preview(entry: &mut ClipEntry, content: &str) {
+    let secret = kind::detect_secret(content);
+    entry.sensitive = secret.is_some();
+    entry.preview = match secret {
+        Some(secret) => format!("{} {}", kind::MASK, secret.label()),
+        None => preview_of(content),
+    };
+}
+
+/// Build a new entry for content first captured at `timestamp`, which is also its id
+pub fn new_entry(timestamp: i64, content: &str, hash: String, meta: EntryMeta) -> ClipEntry {
+    let mut entry = ClipEntry {
+        id: timestamp.to_string(),
+        timestamp,
+        size: content.len(),
+        preview: String::new(),
+        hash,
+        pinned: false,
+        source_app: meta.source_app,
+        window_title: meta.window_title,
+        tags: Vec::new(),
+        name: None,
+        context: meta.context,
+        seat: meta.seat,
+        kind: EntryKind::detect(content),
+        sensitive: false,
+        annotations: Default::default(),
+        use_count: 0,
+        last_used: None,
+        representations: Vec::new(),
+        expires: None,
+    };
+    set_preview(&mut entry, content);
+    entry
+}
+
+/// Whether `id` has the form of the ids clipstack gives entries, a timestamp in
+/// milliseconds. Ids name content files, so anything else can't be trusted.
+pub fn is_entry_id(id: &str) -> bool {
+    (1..=19).contains(&id.len()) && id.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Ids are millisecond timestamps; bump past any taken by a save in the same ms
+pub fn next_timestamp(taken: impl Fn(&str) -> bool) -> i64 {
+    let mut timestamp = chrono::Utc::now().timestamp_millis();
+    while taken(&timestamp.to_string()) {
+        timestamp += 1;
+    }
+    timestamp
+}
+
+/// Update an existing entry captured again: it picks up the current time, hash
+/// and newer metadata. Returns true if its content must be rewritten, since it was
+/// equal only after normalization and the newest raw bytes are kept for exact paste.
+pub fn merge_duplicate(
+    existing: &mut ClipEntry,
+    hash: String,
+    content: &str,
+    meta: EntryMeta,
+) -> bool {
+    existing.timestamp = util::now_millis();
+    existing.hash = hash;
+    mark_used(existing);
+    if meta.source_app.is_some() {
+        existing.source_app = meta.source_app;
+        existing.window_title = meta.window_title;
+    }
+    if meta.seat.is_some() {
+        existing.seat = meta.seat;
+    }
+    if meta.context.is_some() {
+        existing.context = meta.context;
+    }
+    let rewrite = existing.size != content.len();
+    existing.size = content.len();
+    // Also flags secrets in entries saved before they were detected
+    set_preview(existing, content);
+    rewrite
+}
+
+/// Count a use of an entry: a paste, or capturing its content again
+pub fn mark_used(entry: &mut ClipEntry) {
+    entry.use_count = entry.use_count.saturating_add(1);
+    entry.last_used = Some(util::now_millis());
+}
+
+/// Remove expired entries, then the oldest until every kind is within its
+/// quota and the total is within max_entries. Quotas are applied first, so an
+/// over-quota kind loses its own oldest entries rather than evicting other
+/// kinds; max_entries still bounds the total. Then the oldest go until all
+/// content fits max_total_size. Pinned entries are exempt.
+/// Returns the removed entries.
+pub fn prune_index(index: &mut ClipIndex, limits: &Limits) -> Vec<ClipEntry> {
+    let (mut removed, kept) = index.entries.drain(..).partition(|e| limits.is_expired(e));
+    index.entries = kept;
+    for (&kind, &cap) in limits.quotas {
+        prune_oldest_where(index, cap, |e| !e.pinned && e.kind == kind, &mut removed);
+    }
+    prune_oldest_where(index, limits.max_entries, |e| !e.pinned, &mut removed);
+    if let Some(budget) = limits.max_total_size {
+        prune_to_size(index, budget, &mut removed);
+    }
+    removed
+}
+
+/// Remove the oldest unpinned entries until the content totals at most
+/// `budget` bytes. The newest entry stays even if it alone is over budget,
+/// so a capture isn't dropped as it's saved.
+fn prune_to_size(index: &mut ClipIndex, budget: usize, removed: &mut Vec<ClipEntry>) {
+    let mut total: usize = index.entries.iter().map(|e| e.size).sum();
+    while total > budget {
+        let Some(pos) = index.entries.iter().rposition(|e| !e.pinned).filter(|&pos| pos > 0)
+        else {
+            break;
+        };
+        let entry = index.entries.remove(pos);
+        total -= entry.size;
+        removed.push(entry);
+    }
+}
+
+/// Remove the oldest entries matching `pred` until at most `limit` remain
+fn prune_oldest_where(
+    index: &mut ClipIndex,
+    limit: usize,
+    pred: impl Fn(&ClipEntry) -> bool,
+    removed: &mut Vec<ClipEntry>,
+) {
+    let count = index.entries.iter().filter(|e| pred(e)).count();
+    for _ in limit..count {
+        if let Some(pos) = index.entries.iter().rposition(&pred) {
+            removed.push(index.entries.remove(pos));
+        }
+    }
+}
+
+/// The original layout: `index.json` plus one `<id>.txt` per entry,
+/// optionally encrypted
+pub struct FileBackend {
+    base_dir: PathBuf,
+    cipher: Option<Cipher>,
+    binary_index: bool,
+}
+
+/// MessagePack copy of the index, loaded instead of index.json while it's current
+pub const BINARY_INDEX_FILE: &str = "index.msgpack";
+
+impl FileBackend {
+    pub fn new(base_dir: PathBuf) -> Self {
+        let backend = Self {
+            base_dir,
+            cipher: None,
+            binary_index: false,
+        };
+        // Clean up any orphaned temp files from interrupted operations
+        backend.cleanup_temp_files();
+        backend
+    }
+
+    /// Encrypt everything written from now on
+    pub fn with_cipher(mut self, cipher: Cipher) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    /// Keep a MessagePack copy of the index next to index.json, which parses
+    /// several times faster. index.json stays the source of truth: it's read
+    /// whenever the copy is missing, older or unreadable.
+    pub fn with_binary_index(mut self) -> Self {
+        self.binary_index = true;
+        self
+    }
+
+    /// Rewrite files saved before encryption was turned on. Returns how many.
+    pub fn encrypt_existing(&self) -> Result<usize> {
+        if self.cipher.is_none() {
+            return Ok(0);
+        }
+        let mut paths: Vec<PathBuf> =
+            self.content_ids()?.iter().map(|id| self.content_path(id)).collect();
+        paths.push(self.index_path());
+        paths.push(self.binary_index_path());
+
+        let mut count = 0;
+        for path in paths {
+            match fs::read(&path) {
+                Ok(data) if !crypto::is_sealed(&data) => {
+                    self.write_file(&path, &data)?;
+                    count += 1;
+                }
+                _ => {}
+            }
+        }
+        Ok(count)
+    }
+
+    fn read_file(&self, path: &Path) -> Result<String> {
+        let data = self.read_bytes(path)?;
+        String::from_utf8(data).with_context(|| format!("{:?} is not valid UTF-8", path))
+    }
+
+    fn read_bytes(&self, path: &Path) -> Result<Vec<u8>> {
+        let data = fs::read(path)?;
+        Ok(match &self.cipher {
+            Some(cipher) => cipher.open(&data)?,
+            None if crypto::is_sealed(&data) => {
+                anyhow::bail!("{:?} is encrypted; set encrypt = true under [storage]", path)
+            }
+            None => data,
+        })
+    }
+
+    fn write_file(&self, path: &Path, data: &[u8]) -> Result<()> {
+        match &self.cipher {
+            Some(cipher) => atomic_write(path, &cipher.seal(data)?),
+            None => atomic_write(path, data),
+        }
+    }
+
+    fn index_path(&self) -> PathBuf {
+        self.base_dir.join("index.json")
+    }
+
+    fn binary_index_path(&self) -> PathBuf {
+        self.base_dir.join(BINARY_INDEX_FILE)
+    }
+
+    /// The binary copy, if it was written after index.json (a writer without
+    /// the copy turned on, or a crash between the two writes, leaves it older)
+    /// and is in the current format; migrations only run on index.json
+    fn read_binary_index(&self) -> Option<ClipIndex> {
+        let path = self.binary_index_path();
+        let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+        if modified(&path)? < modified(&self.index_path())? {
+            return None;
+        }
+        let data = self.read_bytes(&path).ok()?;
+        match rmp_serde::from_slice::<ClipIndex>(&data) {
+            Ok(index) if index.version == INDEX_VERSION => Some(index),
+            Ok(_) => None,
+            Err(e) => {
+                log::warn!("[storage] Ignoring unreadable {:?}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    fn content_path(&self, id: &str) -> PathBuf {
+        self.base_dir.join(format!("{}.txt", id))
+    }
+
+    /// Clean up orphaned temp files from interrupted operations
+    fn cleanup_temp_files(&self) {
+        self.remove_temp_files(Duration::ZERO);
+    }
+
+    /// Remove temp files last written at least `min_age` ago, returning the bytes freed
+    fn remove_temp_files(&self, min_age: Duration) -> u64 {
+        let mut freed = 0;
+        if let Ok(entries) = fs::read_dir(&self.base_dir) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let Ok(metadata) = entry.metadata() else {
+                    continue;
+                };
+                let age = metadata.modified().ok().and_then(|t| t.elapsed().ok());
+                if path.extension().is_some_and(|ext| ext == "tmp")
+                    && age.unwrap_or_default() >= min_age
+                {
+                    log::warn!("[cleanup] Removing orphaned temp file: {:?}", path);
+                    if fs::remove_file(&path).is_ok() {
+                        freed += metadata.len();
+                    }
+                }
+            }
+        }
+        freed
+    }
+}
+
+impl StorageBackend for FileBackend {
+    fn read_index(&self) -> Result<Option<ClipIndex>> {
+        if self.binary_index
+            && let Some(index) = self.read_binary_index()
+        {
+            return Ok(Some(index));
+        }
+        let path = self.index_path();
+        if !path.exists() {
+            return Ok(None);
+        }
+        let data = self.read_file(&path).with_context(|| format!("Cannot read index {:?}", path))?;
+        let index = serde_json::from_str(&data)
+            .map_err(|source| Error::StorageCorrupt { what: "Index", source })?;
+        ClipIndex::from_json(index).map(Some)
+    }
+
+    fn write_index(&self, index: &ClipIndex) -> Result<()> {
+        let data = serde_json::to_string_pretty(index)?;
+        self.write_file(&self.index_path(), data.as_bytes())?;
+        // Written second, so it's only ever newer than index.json when it matches
+        if self.binary_index {
+            self.write_file(&self.binary_index_path(), &rmp_serde::to_vec_named(index)?)?;
+        } else {
+            let _ = fs::remove_file(self.binary_index_path());
+        }
+        Ok(())
+    }
+
+    fn read_content(&self, id: &str) -> Result<String> {
+        let path = self.content_path(id);
+        let data = self
+            .read_bytes(&path)
+            .and_then(decompress)
+            .with_context(|| format!("Failed to read content: {:?}", path))?;
+        String::from_utf8(data).with_context(|| format!("{:?} is not valid UTF-8", path))
+    }
+
+    fn write_content(&self, id: &str, content: &str) -> Result<()> {
+        // Atomic write prevents corruption
+        self.write_file(&self.content_path(id), content.as_bytes())
+    }
+
+    fn write_compressed(&self, id: &str, content: &str) -> Result<()> {
+        self.write_file(&self.content_path(id), &compress(content)?)
+    }
+
+    fn remove_content(&self, id: &str) -> Result<u64> {
+        let path = self.content_path(id);
+        let Ok(metadata) = fs::metadata(&path) else {
+            return Ok(0);
+        };
+        fs::remove_file(&path).with_context(|| format!("Failed to remove {:?}", path))?;
+        Ok(metadata.len())
+    }
+
+    fn content_ids(&self) -> Result<Vec<String>> {
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(&self.base_dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "txt")
+                && let Some(id) = path.file_stem().and_then(|s| s.to_str())
+            {
+                ids.push(id.to_string());
+            }
+        }
+        Ok(ids)
+    }
+
+    fn stored_size(&self, id: &str) -> Result<u64> {
+        Ok(fs::metadata(self.content_path(id)).map_or(0, |m| m.len()))
+    }
+
+    fn compact(&self) -> Result<u64> {
+        // A younger one may belong to a save still in progress elsewhere
+        let mut freed = self.remove_temp_files(STALE_TEMP_AGE);
+        let index_size = || fs::metadata(self.index_path()).map_or(0, |m| m.len());
+        let before = index_size();
+        if let Some(index) = self.read_index()? {
+            self.write_index(&index)?;
+        }
+        freed += before.saturating_sub(index_size());
+        Ok(freed)
+    }
+
+    fn change_stamp(&self) -> Option<u64> {
+        // Every write renames a new file over the index, so the inode changes
+        // even where timestamps are too coarse to
+        let metadata = fs::metadata(self.index_path()).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(std::time::UNIX_EPOCH).ok()?;
+        Some(metadata.ino() ^ modified.as_nanos() as u64)
+    }
+
+    fn content_file(&self, id: &str) -> PathBuf {
+        self.content_path(id)
+    }
+}
+
+/// Gzip content for `write_compressed`
+pub fn compress(content: &str) -> Result<Vec<u8>> {
+    let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+    gz.write_all(content.as_bytes())?;
+    Ok(gz.finish()?)
+}
+
+/// Stored content as written: gunzipped if it was compressed, otherwise unchanged
+pub fn decompress(data: Vec<u8>) -> Result<Vec<u8>> {
+    if !data.starts_with(GZIP_MAGIC) {
+        return Ok(data);
+    }
+    let mut content = Vec::new();
+    GzDecoder::new(data.as_slice())
+        .read_to_end(&mut content)
+        .context("Compressed content is damaged")?;
+    Ok(content)
+}
+
+/// Atomically write data to a file using write-then-rename pattern.
+///
+/// This guarantees that file writes are atomic:
+/// 1. Write to temporary file (unique .tmp extension)
+/// 2. fsync() to ensure data is on disk
+/// 3. Atomic rename() to final path
+/// 4. fsync() parent directory for full durability
+///
+/// If interrupted at any point, the original file remains intact.
+pub fn atomic_write(path: &Path, data: &[u8]) -> Result<()> {
+    // Generate unique temp file name using nanosecond timestamp and process ID
+    let unique_id = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let tmp_name = format!("{}.{}.tmp", std::process::id(), unique_id);
+    let tmp_path = path.with_file_name(tmp_name);
+
+    // Step 1: Write to temporary file
+    let mut file = fs::File::create(&tmp_path)
+        .with_context(|| format!("Failed to create temp file: {:?}", tmp_path))?;
+
+    file.write_all(data)
+        .with_context(|| format!("Failed to write temp file: {:?}", tmp_path))?;
+
+    // Step 2: Ensure data is flushed to disk
+    file.sync_all()
+        .with_context(|| format!("Failed to sync temp file: {:?}", tmp_path))?;
+
+    // Step 3: Close file before rename (required on some platforms)
+    drop(file);
+
+    // Step 4: Atomic rename (POSIX guarantees atomicity)
+    fs::rename(&tmp_path, path)
+        .with_context(|| format!("Failed to rename {:?} to {:?}", tmp_path, path))?;
+
+    // Step 5: Sync parent directory for full durability
+    if let Some(parent) = path.parent()
+        && let Ok(dir) = fs::File::open(parent)
+    {
+        let _ = dir.sync_all();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn entry(id: i64, kind: EntryKind, pinned: bool) -> ClipEntry {
+        let mut entry = new_entry(id, "x", String::new(), EntryMeta::default());
+        entry.kind = kind;
+        entry.pinned = pinned;
+        entry
+    }
+
+    #[test]
+    fn test_prune_index_applies_quotas_then_total() {
+        let mut index = ClipIndex {
+            max_entries: 3,
+            entries: vec![
+                entry(5, EntryKind::Url, false),
+                entry(4, EntryKind::Url, false),
+                entry(3, EntryKind::Text, true),
+                entry(2, EntryKind::Url, false),
+                entry(1, EntryKind::Text, false),
+            ],
+            ..Default::default()
+        };
+        let quotas = HashMap::from([(EntryKind::Url, 2)]);
+        let limits = Limits {
+            max_entries: 2,
+            quotas: &quotas,
+            max_age: None,
+            max_total_size: None,
+            keep_content: false,
+        };
+
+        let removed: Vec<String> =
+            prune_index(&mut index, &limits).into_iter().map(|e| e.id).collect();
+        assert_eq!(removed, ["2", "1"]);
+        let kept: Vec<&str> = index.entries.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(kept, ["5", "4", "3"], "Pinned entries don't count");
+    }
+
+    #[test]
+    fn test_prune_index_fits_total_size() {
+        let sized = |id, size, pinned| {
+            let mut e = entry(id, EntryKind::Text, pinned);
+            e.size = size;
+            e
+        };
+        let mut index = ClipIndex {
+            max_entries: 10,
+            entries: vec![
+                sized(4, 600, false),
+                sized(3, 100, true),
+                sized(2, 200, false),
+                sized(1, 300, false),
+            ],
+            ..Default::default()
+        };
+        let quotas = HashMap::new();
+        let limits = Limits {
+            max_entries: 10,
+            quotas: &quotas,
+            max_age: None,
+            max_total_size: Some(800),
+            keep_content: false,
+        };
+
+        let removed: Vec<String> =
+            prune_index(&mut index, &limits).into_iter().map(|e| e.id).collect();
+        assert_eq!(removed, ["1", "2"], "Oldest first until 700 <= 800");
+
+        // The newest entry is kept even when it alone is over budget
+        let limits = Limits {
+            max_total_size: Some(10),
+            ..limits
+        };
+        prune_index(&mut index, &limits);
+        let kept: Vec<&str> = index.entries.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(kept, ["4", "3"]);
+    }
+
+    #[test]
+    fn test_file_backend_content() {
+        let dir = TempDir::new().unwrap();
+        let backend = FileBackend::new(dir.path().to_path_buf());
+        assert!(backend.read_index().unwrap().is_none());
+
+        backend.write_content("1", "hello").unwrap();
+        assert_eq!(backend.read_content("1").unwrap(), "hello");
+        assert_eq!(backend.content_ids().unwrap(), ["1"]);
+        assert_eq!(backend.remove_content("1").unwrap(), 5);
+        assert_eq!(backend.remove_content("1").unwrap(), 0);
+
+        fs::write(dir.path().join("index.json"), "{oops").unwrap();
+        assert!(backend.read_index().is_err());
+    }
+
+    #[test]
+    fn test_file_backend_change_stamp() {
+        let dir = TempDir::new().unwrap();
+        let backend = FileBackend::new(dir.path().to_path_buf());
+        assert_eq!(backend.change_stamp(), None);
+
+        let mut index = ClipIndex::default();
+        backend.write_index(&index).unwrap();
+        let written = backend.change_stamp();
+        assert!(written.is_some());
+        assert_eq!(backend.change_stamp(), written, "Reading changes nothing");
+
+        index.entries.push(entry(1, EntryKind::Text, false));
+        FileBackend::new(dir.path().to_path_buf()).write_index(&index).unwrap();
+        assert_ne!(backend.change_stamp(), written);
+    }
+
+    #[test]
+    fn test_prune_index_expires_old_entries_first() {
+        let now = util::now_millis();
+        let mut index = ClipIndex {
+            max_entries: 10,
+            entries: vec![
+                entry(now, EntryKind::Text, false),
+                entry(now - 7_200_000, EntryKind::Text, true),
+                entry(now - 7_200_000, EntryKind::Text, false),
+            ],
+            ..Default::default()
+        };
+        let quotas = HashMap::new();
+        let limits = Limits {
+            max_entries: 10,
+            quotas: &quotas,
+            max_age: Some(Duration::from_secs(3600)),
+            max_total_size: None,
+            keep_content: false,
+        };
+
+        let removed = prune_index(&mut index, &limits);
+        assert_eq!(removed.len(), 1);
+        assert!(!removed[0].pinned);
+        assert_eq!(index.entries.len(), 2, "Pinned entries don't expire");
+    }
+
+    #[test]
+    fn test_binary_index_used_while_current() {
+        let dir = TempDir::new().unwrap();
+        let backend = FileBackend::new(dir.path().to_path_buf()).with_binary_index();
+        let index_with = |ids: &[i64]| ClipIndex {
+            entries: ids.iter().map(|&id| entry(id, EntryKind::Text, false)).collect(),
+            ..Default::default()
+        };
+        let ids = |index: ClipIndex| -> Vec<String> {
+            index.entries.into_iter().map(|e| e.id).collect()
+        };
+        let json_path = dir.path().join("index.json");
+        let binary_path = dir.path().join(BINARY_INDEX_FILE);
+
+        backend.write_index(&index_with(&[1, 2])).unwrap();
+        assert!(binary_path.exists());
+        assert_eq!(ids(backend.read_index().unwrap().unwrap()), ["1", "2"]);
+
+        // The copy wins while it's at least as new as index.json...
+        fs::write(&json_path, serde_json::to_string(&index_with(&[3])).unwrap()).unwrap();
+        let past = std::time::SystemTime::now() - Duration::from_secs(60);
+        fs::File::options().write(true).open(&json_path).unwrap().set_modified(past).unwrap();
+        assert_eq!(ids(backend.read_index().unwrap().unwrap()), ["1", "2"]);
+
+        // ...and index.json wins once it's newer, or the copy can't be read
+        fs::write(&json_path, serde_json::to_string(&index_with(&[4])).unwrap()).unwrap();
+        assert_eq!(ids(backend.read_index().unwrap().unwrap()), ["4"]);
+        backend.write_index(&index_with(&[5])).unwrap();
+        fs::write(&binary_path, "not msgpack").unwrap();
+        assert_eq!(ids(backend.read_index().unwrap().unwrap()), ["5"]);
+
+        // Without the option the copy is removed on the next write
+        FileBackend::new(dir.path().to_path_buf()).write_index(&index_with(&[6])).unwrap();
+        assert!(!binary_path.exists());
+    }
+
+    #[test]
+    fn test_encrypted_file_backend() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("1.txt"), "written before encryption").unwrap();
+        let cipher = Cipher::load_or_create(&dir.path().join("key")).unwrap();
+        let backend = FileBackend::new(dir.path().to_path_buf()).with_cipher(cipher);
+        assert_eq!(backend.encrypt_existing().unwrap(), 1);
+        assert_eq!(backend.encrypt_existing().unwrap(), 0);
+
+        backend.write_content("2", "secret").unwrap();
+        backend.write_index(&ClipIndex::default()).unwrap();
+        for name in ["1.txt", "2.txt", "index.json"] {
+            assert!(crypto::is_sealed(&fs::read(dir.path().join(name)).unwrap()), "{}", name);
+        }
+        assert_eq!(backend.read_content("1").unwrap(), "written before encryption");
+        assert_eq!(backend.read_content("2").unwrap(), "secret");
+        assert!(backend.read_index().unwrap().is_some());
+
+        let without_key = FileBackend::new(dir.path().to_path_buf());
+        let err = without_key.read_content("2").unwrap_err();
+        assert!(format!("{:#}", err).contains("encrypted"));
+    }
+}
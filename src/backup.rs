@@ -0,0 +1,142 @@
+use crate::crypto::Cipher;
+use crate::export::{self, Export};
+use crate::storage::Storage;
+use anyhow::{Context, Result};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Directory in the storage dir backups go to unless `[backup] dir` says otherwise
+pub const BACKUP_DIR_NAME: &str = "backups";
+
+/// Backups kept unless `--keep` or `[backup] keep` says otherwise
+pub const DEFAULT_KEEP: usize = 7;
+
+const PREFIX: &str = "clipstack-";
+const SUFFIX: &str = ".json.gz";
+
+/// What `create` wrote and rotated away
+pub struct Backup {
+    pub path: PathBuf,
+    pub entries: usize,
+    /// Older backups deleted to keep `keep`
+    pub removed: Vec<PathBuf>,
+}
+
+pub fn default_dir(storage: &Storage) -> PathBuf {
+    storage.base_dir().join(BACKUP_DIR_NAME)
+}
+
+/// Backups in `dir`, oldest first
+pub fn list(dir: &Path) -> Result<Vec<PathBuf>> {
+    let files = match fs::read_dir(dir) {
+        Ok(files) => files,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", dir)),
+    };
+    let mut backups: Vec<PathBuf> = files
+        .flatten()
+        .map(|f| f.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with(PREFIX) && n.ends_with(SUFFIX))
+        })
+        .collect();
+    // Names carry the time, so they sort oldest first
+    backups.sort();
+    Ok(backups)
+}
+
+/// Write the history as a timestamped export to `dir` (encrypted with
+/// `cipher`, if given; `clipstack import` restores it), then delete the
+/// oldest backups beyond `keep`
+pub fn create(
+    storage: &Storage,
+    dir: &Path,
+    keep: usize,
+    cipher: Option<&Cipher>,
+) -> Result<Backup> {
+    anyhow::ensure!(keep > 0, "Keeping no backups would delete the new one");
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+
+    let entries = storage.export_entries()?;
+    let count = entries.len();
+    let mut data = export::to_gzip(&Export::new(entries))?;
+    if let Some(cipher) = cipher {
+        data = cipher.seal(&data)?;
+    }
+
+    let name = format!("{}{}{}", PREFIX, chrono::Local::now().format("%Y%m%d-%H%M%S"), SUFFIX);
+    let path = dir.join(name);
+    // Written aside first so an interrupted backup never rotates out a good one
+    let tmp = path.with_extension("tmp");
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&tmp)
+        .with_context(|| format!("Failed to create {:?}", tmp))?;
+    file.write_all(&data).and_then(|_| file.sync_all())?;
+    fs::rename(&tmp, &path).with_context(|| format!("Failed to write {:?}", path))?;
+
+    let backups = list(dir)?;
+    let excess = backups.len().saturating_sub(keep);
+    let mut removed = Vec::new();
+    for old in backups.into_iter().take(excess) {
+        match fs::remove_file(&old) {
+            Ok(()) => removed.push(old),
+            Err(e) => log::warn!("[backup] Failed to remove {:?}: {}", old, e),
+        }
+    }
+    Ok(Backup {
+        path,
+        entries: count,
+        removed,
+    })
+}
+
+/// Whether the newest backup in `dir` is older than `interval`, or there is none
+pub fn due(dir: &Path, interval: Duration) -> bool {
+    let newest = list(dir).ok().and_then(|backups| backups.last().cloned());
+    let age = newest.and_then(|path| fs::metadata(path).ok()?.modified().ok()?.elapsed().ok());
+    age.is_none_or(|age| age >= interval)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_backup_rotates_and_restores() {
+        let dir = TempDir::new().unwrap();
+        let storage = Storage::with_defaults(dir.path().join("history")).unwrap();
+        let entry = storage.save_entry("keep me").unwrap();
+        storage.set_pinned(&entry.id, true).unwrap();
+        let backups = dir.path().join("backups");
+        assert!(due(&backups, Duration::from_secs(3600)));
+
+        // Older backups, as left by earlier runs
+        fs::create_dir_all(&backups).unwrap();
+        for name in ["clipstack-20240101-000000.json.gz", "clipstack-20240102-000000.json.gz"] {
+            fs::write(backups.join(name), "old").unwrap();
+        }
+        fs::write(backups.join("notes.txt"), "not a backup").unwrap();
+
+        let backup = create(&storage, &backups, 2, None).unwrap();
+        assert_eq!(backup.entries, 1);
+        assert_eq!(backup.removed, [backups.join("clipstack-20240101-000000.json.gz")]);
+        assert_eq!(list(&backups).unwrap().len(), 2);
+        assert!(backups.join("notes.txt").exists());
+        assert!(!due(&backups, Duration::from_secs(3600)));
+
+        let restored = export::read(&backup.path, None).unwrap();
+        assert_eq!(restored.entries[0].content, "keep me");
+        assert!(restored.entries[0].entry.pinned);
+        assert!(create(&storage, &backups, 0, None).is_err());
+    }
+}
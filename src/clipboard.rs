@@ -1,6 +1,10 @@
+use crate::error::Error;
 use anyhow::{Context, Result};
+use std::fs;
 use std::io::Write;
+use std::path::PathBuf;
 use std::process::{Command, Stdio};
+use std::sync::Arc;
 
 const CLIPBOARD_TROUBLESHOOT: &str = "\
 Troubleshooting:
@@ -8,59 +12,137 @@ Troubleshooting:
   • Are you in a Wayland session? (echo $WAYLAND_DISPLAY)
   • Is your compositor running?";
 
-pub struct Clipboard;
+fn unavailable(program: &'static str, source: std::io::Error) -> Error {
+    Error::ClipboardUnavailable {
+        program,
+        hint: CLIPBOARD_TROUBLESHOOT,
+        source,
+    }
+}
 
-impl Clipboard {
-    /// Copy content to the system clipboard using wl-copy
-    pub fn copy(content: &str) -> Result<()> {
-        let mut child = Command::new("wl-copy")
-            .stdin(Stdio::piped())
-            .stdout(Stdio::null())
-            // Note: We use inherit() for stderr because wl-copy forks to background,
-            // and piped stderr would cause wait_with_output() to hang waiting for the
-            // forked child to close the pipe (which never happens).
-            .stderr(Stdio::inherit())
-            .spawn()
-            .with_context(|| format!("Failed to run wl-copy.\n{}", CLIPBOARD_TROUBLESHOOT))?;
+/// MIME type password managers (KeePassXC, KDE apps) offer alongside secrets
+const PASSWORD_MANAGER_HINT: &str = "x-kde-passwordManagerHint";
 
-        // Write content and close stdin to signal EOF to wl-copy
-        {
-            let mut stdin = child.stdin.take()
-                .context("Failed to get wl-copy stdin")?;
-            stdin.write_all(content.as_bytes())
-                .context("Failed to write to wl-copy stdin")?;
-            // stdin is dropped here, closing the pipe and sending EOF
+/// Which clipboard implementation to use (`--backend`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Backend {
+    /// The Wayland clipboard via wl-clipboard
+    #[default]
+    Wayland,
+    /// A fake clipboard kept in $XDG_RUNTIME_DIR/clipstack-mock, for tests and headless use
+    Mock,
+}
+
+impl Backend {
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Wayland => "wayland",
+            Backend::Mock => "mock",
         }
+    }
 
-        // Wait for wl-copy parent process to exit (it forks to background)
-        let status = child.wait()?;
-        if !status.success() {
-            anyhow::bail!("wl-copy failed with status: {}", status);
+    pub fn open(self) -> Arc<dyn ClipboardBackend> {
+        match self {
+            Backend::Wayland => Arc::new(WlClipboard),
+            Backend::Mock => Arc::new(MockClipboard::shared(MockClipboard::default_dir())),
         }
+    }
+}
 
-        Ok(())
+/// Access to the system clipboard and PRIMARY selection. `seat` None means
+/// the default seat.
+pub trait ClipboardBackend: Send + Sync {
+    /// Set the clipboard or PRIMARY selection of a seat
+    fn copy_to(&self, seat: Option<&str>, primary: bool, content: &str) -> Result<()>;
+
+    /// Copy content to the default seat's clipboard
+    fn copy(&self, content: &str) -> Result<()> {
+        self.copy_to(None, false, content)
     }
 
-    /// Paste content from the system clipboard using wl-paste
-    pub fn paste() -> Result<String> {
-        Self::paste_selection(false)
+    /// Read the clipboard or PRIMARY selection; empty if nothing is selected
+    fn paste_from(&self, seat: Option<&str>, primary: bool) -> Result<String>;
+
+    /// MIME types offered by the clipboard or PRIMARY selection
+    fn list_types(&self, seat: Option<&str>, primary: bool) -> Result<Vec<String>>;
+
+    /// Read the clipboard or PRIMARY selection as one of the types it offers
+    fn paste_type(&self, seat: Option<&str>, primary: bool, mime: &str) -> Result<Vec<u8>>;
+
+    /// Offer `data` as type `mime` on the default seat's clipboard
+    fn copy_type(&self, mime: &str, data: &[u8]) -> Result<()>;
+
+    /// Empty a seat's clipboard
+    fn clear(&self, seat: Option<&str>) -> Result<()>;
+
+    /// Read the default seat's clipboard
+    fn paste(&self) -> Result<String> {
+        self.paste_from(None, false)
     }
 
-    /// Paste content from PRIMARY selection (mouse selection)
-    pub fn paste_primary() -> Result<String> {
-        Self::paste_selection(true)
+    /// Backend name, for passing `--backend` on to a spawned daemon
+    fn backend(&self) -> Backend;
+}
+
+/// Whether the offered MIME types mark the content as a password manager secret
+pub fn has_password_manager_hint(types: &[String]) -> bool {
+    types.iter().any(|t| t == PASSWORD_MANAGER_HINT)
+}
+
+/// The Wayland clipboard, through the wl-copy and wl-paste commands
+pub struct WlClipboard;
+
+impl ClipboardBackend for WlClipboard {
+    /// Copy content to the clipboard or PRIMARY selection using wl-copy
+    fn copy_to(&self, seat: Option<&str>, primary: bool, content: &str) -> Result<()> {
+        let mut cmd = Command::new("wl-copy");
+        if let Some(seat) = seat {
+            cmd.args(["--seat", seat]);
+        }
+        if primary {
+            cmd.arg("--primary");
+        }
+        Self::run_copy(cmd, content.as_bytes())
+    }
+
+    fn copy_type(&self, mime: &str, data: &[u8]) -> Result<()> {
+        let mut cmd = Command::new("wl-copy");
+        cmd.args(["--type", mime]);
+        Self::run_copy(cmd, data)
+    }
+
+    /// Clear the system clipboard of a seat (None: the default seat)
+    fn clear(&self, seat: Option<&str>) -> Result<()> {
+        let mut cmd = Command::new("wl-copy");
+        if let Some(seat) = seat {
+            cmd.args(["--seat", seat]);
+        }
+        let status = cmd
+            .arg("--clear")
+            .stdout(Stdio::null())
+            .stderr(Stdio::inherit())
+            .status()
+            .map_err(|source| unavailable("wl-copy", source))?;
+        if !status.success() {
+            anyhow::bail!("wl-copy --clear failed with status: {}", status);
+        }
+        Ok(())
     }
 
-    fn paste_selection(primary: bool) -> Result<String> {
+    /// Paste the clipboard or PRIMARY selection of a seat using wl-paste
+    fn paste_from(&self, seat: Option<&str>, primary: bool) -> Result<String> {
         let mut cmd = Command::new("wl-paste");
         cmd.arg("--no-newline");
+        if let Some(seat) = seat {
+            cmd.args(["--seat", seat]);
+        }
         if primary {
             cmd.arg("--primary");
         }
 
         let output = cmd
             .output()
-            .with_context(|| format!("Failed to run wl-paste.\n{}", CLIPBOARD_TROUBLESHOOT))?;
+            .map_err(|source| unavailable("wl-paste", source))?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
@@ -74,9 +156,92 @@ impl Clipboard {
         String::from_utf8(output.stdout).context("Clipboard content is not valid UTF-8")
     }
 
+    /// List the MIME types offered by the clipboard or PRIMARY selection
+    fn list_types(&self, seat: Option<&str>, primary: bool) -> Result<Vec<String>> {
+        let mut cmd = Command::new("wl-paste");
+        cmd.arg("--list-types");
+        if let Some(seat) = seat {
+            cmd.args(["--seat", seat]);
+        }
+        if primary {
+            cmd.arg("--primary");
+        }
+
+        let output = cmd
+            .output()
+            .map_err(|source| unavailable("wl-paste", source))?;
+
+        if !output.status.success() {
+            return Ok(Vec::new());
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect())
+    }
+
+    /// Read one offered type using wl-paste --type
+    fn paste_type(&self, seat: Option<&str>, primary: bool, mime: &str) -> Result<Vec<u8>> {
+        let mut cmd = Command::new("wl-paste");
+        cmd.args(["--type", mime]);
+        if let Some(seat) = seat {
+            cmd.args(["--seat", seat]);
+        }
+        if primary {
+            cmd.arg("--primary");
+        }
+
+        let output = cmd
+            .output()
+            .map_err(|source| unavailable("wl-paste", source))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            anyhow::bail!("wl-paste --type {} failed: {}", mime, stderr.trim());
+        }
+        Ok(output.stdout)
+    }
+
+    fn backend(&self) -> Backend {
+        Backend::Wayland
+    }
+}
+
+impl WlClipboard {
+    /// Feed `data` to a wl-copy command and wait for it to take over the selection
+    fn run_copy(mut cmd: Command, data: &[u8]) -> Result<()> {
+        let mut child = cmd
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            // Note: We use inherit() for stderr because wl-copy forks to background,
+            // and piped stderr would cause wait_with_output() to hang waiting for the
+            // forked child to close the pipe (which never happens).
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|source| unavailable("wl-copy", source))?;
+
+        // Write content and close stdin to signal EOF to wl-copy
+        {
+            let mut stdin = child.stdin.take()
+                .context("Failed to get wl-copy stdin")?;
+            stdin.write_all(data)
+                .context("Failed to write to wl-copy stdin")?;
+            // stdin is dropped here, closing the pipe and sending EOF
+        }
+
+        // Wait for wl-copy parent process to exit (it forks to background)
+        let status = child.wait()?;
+        if !status.success() {
+            anyhow::bail!("wl-copy failed with status: {}", status);
+        }
+
+        Ok(())
+    }
+
     /// Watch clipboard for changes using polling
     #[allow(dead_code)]
-    pub fn watch<F>(mut on_change: F) -> Result<()>
+    pub fn watch<F>(&self, mut on_change: F) -> Result<()>
     where
         F: FnMut(String) -> Result<()>,
     {
@@ -87,7 +252,7 @@ impl Clipboard {
         let mut last_hash: Option<Vec<u8>> = None;
 
         loop {
-            match Self::paste() {
+            match self.paste() {
                 Ok(content) if !content.is_empty() => {
                     let hash = util::compute_hash(&content);
                     if last_hash.as_ref() != Some(&hash) {
@@ -103,10 +268,253 @@ impl Clipboard {
     }
 }
 
+/// Fake clipboard for tests and headless environments. Selections live in
+/// memory, or as files in a directory so separate clipstack processes (CLI
+/// and daemon) share them: `<seat>.clipboard` and `<seat>.primary`, with
+/// `default` for the default seat. Other offered types go in
+/// `<seat>.<selection>@<type>`, with the type's `/` written as `%2F`.
+pub struct MockClipboard {
+    store: MockStore,
+}
+
+enum MockStore {
+    #[cfg(test)]
+    Memory(std::sync::Mutex<std::collections::HashMap<String, Vec<u8>>>),
+    Dir(PathBuf),
+}
+
+/// What the mock reports offering for its plain text
+const MOCK_TEXT_TYPE: &str = "text/plain;charset=utf-8";
+
+impl MockClipboard {
+    /// Selections kept in this process only
+    #[cfg(test)]
+    pub fn in_memory() -> Self {
+        Self {
+            store: MockStore::Memory(Default::default()),
+        }
+    }
+
+    /// Selections kept as files in `dir`
+    pub fn shared(dir: PathBuf) -> Self {
+        Self {
+            store: MockStore::Dir(dir),
+        }
+    }
+
+    /// $CLIPSTACK_MOCK_DIR, or clipstack-mock in the runtime dir
+    pub fn default_dir() -> PathBuf {
+        std::env::var_os("CLIPSTACK_MOCK_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| {
+                dirs::runtime_dir()
+                    .unwrap_or_else(std::env::temp_dir)
+                    .join("clipstack-mock")
+            })
+    }
+
+    fn key(seat: Option<&str>, primary: bool) -> String {
+        let selection = if primary { "primary" } else { "clipboard" };
+        format!("{}.{}", seat.unwrap_or("default"), selection)
+    }
+
+    fn type_key(seat: Option<&str>, primary: bool, mime: &str) -> String {
+        format!("{}@{}", Self::key(seat, primary), mime.replace('/', "%2F"))
+    }
+
+    /// Replace a selection with plain text, dropping the other types it offered
+    fn set(&self, seat: Option<&str>, primary: bool, content: &str) -> Result<()> {
+        self.drop_types(seat, primary)?;
+        self.write(Self::key(seat, primary), content.as_bytes())
+    }
+
+    fn get(&self, seat: Option<&str>, primary: bool) -> Result<String> {
+        let data = self.read(&Self::key(seat, primary))?.unwrap_or_default();
+        String::from_utf8(data).context("Mock clipboard content is not valid UTF-8")
+    }
+
+    /// Also offer `data` as `mime` on a selection, as apps copying rich
+    /// content do next to its plain text
+    pub fn offer_type(
+        &self,
+        seat: Option<&str>,
+        primary: bool,
+        mime: &str,
+        data: &[u8],
+    ) -> Result<()> {
+        self.write(Self::type_key(seat, primary, mime), data)
+    }
+
+    /// Types other than plain text a selection offers
+    fn other_types(&self, seat: Option<&str>, primary: bool) -> Result<Vec<String>> {
+        let prefix = format!("{}@", Self::key(seat, primary));
+        let keys: Vec<String> = match &self.store {
+            #[cfg(test)]
+            MockStore::Memory(map) => map.lock().unwrap().keys().cloned().collect(),
+            MockStore::Dir(dir) => match fs::read_dir(dir) {
+                Ok(files) => files
+                    .flatten()
+                    .filter_map(|f| f.file_name().into_string().ok())
+                    .collect(),
+                Err(_) => Vec::new(),
+            },
+        };
+        let mut types: Vec<String> = keys
+            .iter()
+            .filter_map(|k| k.strip_prefix(&prefix))
+            .map(|mime| mime.replace("%2F", "/"))
+            .collect();
+        types.sort();
+        Ok(types)
+    }
+
+    fn drop_types(&self, seat: Option<&str>, primary: bool) -> Result<()> {
+        for mime in self.other_types(seat, primary)? {
+            let key = Self::type_key(seat, primary, &mime);
+            match &self.store {
+                #[cfg(test)]
+                MockStore::Memory(map) => {
+                    map.lock().unwrap().remove(&key);
+                }
+                MockStore::Dir(dir) => {
+                    fs::remove_file(dir.join(key)).context("Failed to write mock clipboard")?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write(&self, key: String, data: &[u8]) -> Result<()> {
+        match &self.store {
+            #[cfg(test)]
+            MockStore::Memory(map) => {
+                map.lock().unwrap().insert(key, data.to_vec());
+            }
+            MockStore::Dir(dir) => {
+                fs::create_dir_all(dir)
+                    .with_context(|| format!("Failed to create mock clipboard dir: {:?}", dir))?;
+                fs::write(dir.join(key), data).context("Failed to write mock clipboard")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        match &self.store {
+            #[cfg(test)]
+            MockStore::Memory(map) => Ok(map.lock().unwrap().get(key).cloned()),
+            MockStore::Dir(dir) => match fs::read(dir.join(key)) {
+                Ok(data) => Ok(Some(data)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e).context("Failed to read mock clipboard"),
+            },
+        }
+    }
+}
+
+impl ClipboardBackend for MockClipboard {
+    fn copy_to(&self, seat: Option<&str>, primary: bool, content: &str) -> Result<()> {
+        self.set(seat, primary, content)
+    }
+
+    fn paste_from(&self, seat: Option<&str>, primary: bool) -> Result<String> {
+        self.get(seat, primary)
+    }
+
+    fn list_types(&self, seat: Option<&str>, primary: bool) -> Result<Vec<String>> {
+        let mut types = Vec::new();
+        if !self.get(seat, primary)?.is_empty() {
+            types.push(MOCK_TEXT_TYPE.to_string());
+        }
+        types.extend(self.other_types(seat, primary)?);
+        Ok(types)
+    }
+
+    fn paste_type(&self, seat: Option<&str>, primary: bool, mime: &str) -> Result<Vec<u8>> {
+        if mime == MOCK_TEXT_TYPE {
+            return Ok(self.get(seat, primary)?.into_bytes());
+        }
+        self.read(&Self::type_key(seat, primary, mime))?
+            .with_context(|| format!("The clipboard doesn't offer {}", mime))
+    }
+
+    /// Like wl-copy --type, the new selection offers only this type
+    fn copy_type(&self, mime: &str, data: &[u8]) -> Result<()> {
+        self.set(None, false, "")?;
+        self.offer_type(None, false, mime, data)
+    }
+
+    fn clear(&self, seat: Option<&str>) -> Result<()> {
+        self.set(seat, false, "")
+    }
+
+    fn backend(&self) -> Backend {
+        Backend::Mock
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_password_manager_hint_detection() {
+        let plain = vec!["text/plain;charset=utf-8".to_string(), "TEXT".to_string()];
+        assert!(!has_password_manager_hint(&plain));
+
+        let secret = vec![
+            "text/plain".to_string(),
+            "x-kde-passwordManagerHint".to_string(),
+        ];
+        assert!(has_password_manager_hint(&secret));
+    }
+
+    #[test]
+    fn test_mock_clipboard_in_memory() {
+        let mock = MockClipboard::in_memory();
+        assert_eq!(mock.paste().unwrap(), "");
+        assert!(mock.list_types(None, false).unwrap().is_empty());
+
+        mock.copy("hello").unwrap();
+        mock.copy_to(Some("seat1"), true, "selected").unwrap();
+        assert_eq!(mock.paste().unwrap(), "hello");
+        assert_eq!(mock.paste_from(Some("seat1"), true).unwrap(), "selected");
+        assert_eq!(mock.paste_from(Some("seat1"), false).unwrap(), "");
+        assert_eq!(mock.list_types(None, false).unwrap().len(), 1);
+
+        mock.clear(None).unwrap();
+        assert_eq!(mock.paste().unwrap(), "");
+    }
+
+    #[test]
+    fn test_mock_clipboard_offers_other_types() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mock = MockClipboard::shared(dir.path().to_path_buf());
+        mock.copy("bold").unwrap();
+        mock.offer_type(None, false, "text/html", b"<b>bold</b>").unwrap();
+        assert_eq!(mock.list_types(None, false).unwrap(), [MOCK_TEXT_TYPE, "text/html"]);
+        assert_eq!(mock.paste_type(None, false, "text/html").unwrap(), b"<b>bold</b>");
+        assert!(mock.paste_type(None, false, "image/png").is_err());
+
+        mock.copy_type("image/png", &[0x89, b'P', b'N', b'G']).unwrap();
+        assert_eq!(mock.list_types(None, false).unwrap(), ["image/png"]);
+        assert_eq!(mock.paste().unwrap(), "");
+
+        // A plain copy replaces the whole offer
+        mock.copy("plain").unwrap();
+        assert_eq!(mock.list_types(None, false).unwrap(), [MOCK_TEXT_TYPE]);
+    }
+
+    #[test]
+    fn test_mock_clipboard_shared_between_instances() {
+        let dir = tempfile::TempDir::new().unwrap();
+        MockClipboard::shared(dir.path().to_path_buf()).copy("from cli").unwrap();
+
+        let other = MockClipboard::shared(dir.path().to_path_buf());
+        assert_eq!(other.paste().unwrap(), "from cli");
+        assert!(dir.path().join("default.clipboard").exists());
+    }
+
     // Note: These tests require wl-clipboard to be installed and a Wayland session
     // They are integration tests that actually interact with the system clipboard
 
@@ -114,9 +522,9 @@ mod tests {
     #[ignore] // Run with: cargo test -- --ignored
     fn test_copy_and_paste() {
         let content = "test clipboard content";
-        Clipboard::copy(content).unwrap();
+        WlClipboard.copy(content).unwrap();
 
-        let pasted = Clipboard::paste().unwrap();
+        let pasted = WlClipboard.paste().unwrap();
         assert_eq!(pasted, content);
     }
 
@@ -124,9 +532,9 @@ mod tests {
     #[ignore]
     fn test_large_content() {
         let content = "x".repeat(500_000); // 500KB
-        Clipboard::copy(&content).unwrap();
+        WlClipboard.copy(&content).unwrap();
 
-        let pasted = Clipboard::paste().unwrap();
+        let pasted = WlClipboard.paste().unwrap();
         assert_eq!(pasted.len(), 500_000);
     }
 
@@ -134,9 +542,9 @@ mod tests {
     #[ignore]
     fn test_unicode_content() {
         let content = "Hello 世界 🎉 émojis";
-        Clipboard::copy(content).unwrap();
+        WlClipboard.copy(content).unwrap();
 
-        let pasted = Clipboard::paste().unwrap();
+        let pasted = WlClipboard.paste().unwrap();
         assert_eq!(pasted, content);
     }
 }
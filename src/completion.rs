@@ -0,0 +1,192 @@
+use crate::storage::Storage;
+use anyhow::Result;
+use clap_complete::Shell;
+use std::io::Write;
+
+/// Entries offered when completing an id
+const MAX_ID_CANDIDATES: usize = 50;
+
+/// Characters of preview shown next to each candidate
+const HINT_LEN: usize = 60;
+
+/// What `clipstack complete` lists
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Candidates {
+    /// Recent entry ids, newest first
+    Ids,
+    /// Snippet names, sorted
+    Snippets,
+    /// Names from `clipstack snippet`, sorted
+    Saved,
+}
+
+/// Completion candidates as `value<TAB>hint` lines, the hint being a preview
+pub fn candidates(storage: &Storage, kind: Candidates) -> Result<Vec<String>> {
+    let index = storage.load_index()?;
+    let line = |value: &str, preview: &str| {
+        let hint: String = preview.chars().take(HINT_LEN).collect();
+        format!("{}\t{}", value, hint.trim())
+    };
+    Ok(match kind {
+        Candidates::Ids => index
+            .entries
+            .iter()
+            .take(MAX_ID_CANDIDATES)
+            .map(|e| line(&e.id, &e.preview))
+            .collect(),
+        Candidates::Snippets => {
+            let mut snippets: Vec<_> = index
+                .entries
+                .iter()
+                .filter(|e| e.pinned)
+                .filter_map(|e| Some((e.name.as_deref()?, e.preview.as_str())))
+                .collect();
+            snippets.sort();
+            snippets.into_iter().map(|(name, preview)| line(name, preview)).collect()
+        }
+        Candidates::Saved => storage
+            .snippets()
+            .list()?
+            .iter()
+            .map(|s| line(&s.name, s.first_line()))
+            .collect(),
+    })
+}
+
+/// Write clap's completion script for `shell`, extended for bash, zsh and fish so
+/// entry ids and snippet names complete from history via `clipstack complete`
+pub fn generate(shell: Shell, cmd: &mut clap::Command, out: &mut dyn Write) -> Result<()> {
+    let name = cmd.get_name().to_string();
+    let mut script = Vec::new();
+    clap_complete::generate(shell, cmd, name, &mut script);
+    let mut script = String::from_utf8(script)?;
+
+    match shell {
+        Shell::Bash => script.push_str(BASH_HOOK),
+        Shell::Fish => script.push_str(FISH_HOOK),
+        Shell::Zsh => {
+            // Replace the registration at the end so it goes through the hook
+            if let Some(pos) = script.find(ZSH_REGISTRATION) {
+                script.truncate(pos);
+            }
+            script.push_str(ZSH_HOOK);
+        }
+        _ => {}
+    }
+    out.write_all(script.as_bytes())?;
+    Ok(())
+}
+
+const ZSH_REGISTRATION: &str = "if [ \"$funcstack[1]\" = \"_clipstack\" ]; then";
+
+const BASH_HOOK: &str = r#"
+# Entry ids and snippet names come from history
+_clipstack_dynamic() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}"
+    local sub="" kind="" w
+    for w in "${COMP_WORDS[@]:1:COMP_CWORD-1}"; do
+        if [[ $w != -* ]]; then sub=$w; break; fi
+    done
+    case "$sub" in
+        get|pin|unpin|split|join) kind=ids ;;
+        paste-snippet) kind=snippets ;;
+        snippet) [[ $prev == @(copy|show|remove) ]] && kind=saved ;;
+    esac
+    if [[ -n $kind && $cur != -* && $prev != -* ]]; then
+        COMPREPLY=($(compgen -W "$(clipstack complete $kind 2>/dev/null | cut -f1)" -- "$cur"))
+        return 0
+    fi
+    _clipstack "$@"
+}
+
+if [[ "${BASH_VERSINFO[0]}" -eq 4 && "${BASH_VERSINFO[1]}" -ge 4 || "${BASH_VERSINFO[0]}" -gt 4 ]]; then
+    complete -F _clipstack_dynamic -o nosort -o bashdefault -o default clipstack
+else
+    complete -F _clipstack_dynamic -o bashdefault -o default clipstack
+fi
+"#;
+
+const ZSH_HOOK: &str = r#"# Entry ids and snippet names come from history
+_clipstack_dynamic() {
+    local sub=${${words[2,CURRENT-1]:#-*}[1]} kind
+    case $sub in
+        get|pin|unpin|split|join) kind=ids ;;
+        paste-snippet) kind=snippets ;;
+        snippet) [[ $words[CURRENT-1] == (copy|show|remove) ]] && kind=saved ;;
+    esac
+    if [[ -n $kind && $PREFIX != -* && $words[CURRENT-1] != -* ]]; then
+        local -a candidates
+        candidates=(${${(f)"$(clipstack complete $kind 2>/dev/null)"}//$'\t'/:})
+        _describe -t $kind "clipstack $kind" candidates
+        return
+    fi
+    _clipstack "$@"
+}
+
+compdef _clipstack_dynamic clipstack
+if [ "$funcstack[1]" = "_clipstack" ]; then
+    _clipstack_dynamic "$@"
+fi
+"#;
+
+const FISH_HOOK: &str = r#"
+# Entry ids and snippet names come from history
+complete -c clipstack -n "__fish_seen_subcommand_from get pin unpin split join" -f -k \
+    -a "(clipstack complete ids 2>/dev/null)"
+complete -c clipstack -n "__fish_seen_subcommand_from paste-snippet" -f -k \
+    -a "(clipstack complete snippets 2>/dev/null)"
+complete -c clipstack -f -k \
+    -n "__fish_seen_subcommand_from snippet; and __fish_seen_subcommand_from copy show remove" \
+    -a "(clipstack complete saved 2>/dev/null)"
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+    use tempfile::TempDir;
+
+    #[derive(clap::Parser)]
+    #[command(name = "clipstack")]
+    struct TestCli {
+        id: Option<String>,
+    }
+
+    fn script(shell: Shell) -> String {
+        let mut out = Vec::new();
+        generate(shell, &mut TestCli::command(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_candidates() {
+        let dir = TempDir::new().unwrap();
+        let storage = Storage::new(dir.path().to_path_buf(), 100).unwrap();
+        let first = storage.save_entry("first\nline").unwrap();
+        let second = storage.save_entry("second").unwrap();
+        storage.set_name(&first.id, "sig").unwrap();
+
+        let ids = candidates(&storage, Candidates::Ids).unwrap();
+        assert_eq!(ids, [format!("{}\tsecond", second.id), format!("{}\tfirst line", first.id)]);
+        let snippets = candidates(&storage, Candidates::Snippets).unwrap();
+        assert_eq!(snippets, ["sig\tfirst line"]);
+
+        storage.snippets().add("license", "\nMIT License\n", false).unwrap();
+        let saved = candidates(&storage, Candidates::Saved).unwrap();
+        assert_eq!(saved, ["license\tMIT License"]);
+    }
+
+    #[test]
+    fn test_scripts_register_hook() {
+        assert!(script(Shell::Bash).ends_with(BASH_HOOK));
+        assert!(script(Shell::Fish).contains("clipstack complete ids"));
+
+        // The static zsh registration is replaced, not run before the hook
+        let zsh = script(Shell::Zsh);
+        assert!(zsh.ends_with(ZSH_HOOK));
+        assert_eq!(zsh.matches(ZSH_REGISTRATION).count(), 1);
+        assert!(!zsh.contains("compdef _clipstack clipstack"));
+
+        assert!(!script(Shell::Elvish).contains("_clipstack_dynamic"));
+    }
+}
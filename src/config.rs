@@ -0,0 +1,975 @@
+use anyhow::{Context, Result};
+use crate::backup;
+use crate::crypto::Cipher;
+use crate::kind::EntryKind;
+use crate::query::SortKey;
+use crate::schedule::Schedule;
+use crate::storage::DEFAULT_TRASH_RETENTION;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Built-in short labels for well-known app ids (matched case-insensitively)
+const BUILTIN_APP_LABELS: &[(&str, &str)] = &[
+    ("firefox", "ff"),
+    ("org.mozilla.firefox", "ff"),
+    ("librewolf", "ff"),
+    ("chromium", "chr"),
+    ("google-chrome", "chr"),
+    ("brave-browser", "brv"),
+    ("code", "code"),
+    ("code-oss", "code"),
+    ("codium", "code"),
+    ("alacritty", "term"),
+    ("kitty", "term"),
+    ("foot", "term"),
+    ("footclient", "term"),
+    ("org.wezfurlong.wezterm", "term"),
+    ("com.mitchellh.ghostty", "term"),
+    ("org.gnome.terminal", "term"),
+    ("org.keepassxc.keepassxc", "kpx"),
+    ("bitwarden", "bw"),
+    ("slack", "slk"),
+    ("discord", "dsc"),
+    ("org.telegram.desktop", "tg"),
+    ("thunderbird", "tb"),
+    ("obsidian", "obs"),
+];
+
+/// Width of the source column in the picker list
+pub const APP_LABEL_WIDTH: usize = 4;
+
+/// Longest history retention allowed in compliance mode
+pub const COMPLIANCE_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// User configuration loaded from `~/.config/clipstack/config.toml`.
+///
+/// Every field has a default so a missing or partial file is valid.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// History size (CLI `--max-entries` and CLIPSTACK_MAX_ENTRIES take precedence)
+    pub max_entries: Option<usize>,
+    /// Pinned entries allowed (CLI `--max-pinned` and CLIPSTACK_MAX_PINNED take precedence)
+    pub max_pinned: Option<usize>,
+    /// Content size budget, e.g. "50MB" (CLI `--max-total-size` takes precedence)
+    #[serde(with = "opt_size_str", skip_serializing_if = "Option::is_none")]
+    pub max_total_size: Option<usize>,
+    /// Hide previews that look like passwords or tokens (picker, `list`, daemon log)
+    pub mask_secrets: bool,
+    /// Custom app id -> label mappings for the picker source column
+    pub app_labels: HashMap<String, String>,
+    /// Content filters applied by the daemon before saving
+    pub filters: FilterConfig,
+    /// Desktop notifications for new captures
+    pub notifications: NotificationConfig,
+    /// How captured content is normalized
+    pub capture: CaptureConfig,
+    /// Daemon tuning
+    pub daemon: DaemonConfig,
+    /// Per-kind caps on regular entries (e.g. `url = 20`), within max_entries
+    pub quotas: HashMap<EntryKind, usize>,
+    /// Picker appearance
+    pub picker: PickerConfig,
+    /// Picker colors
+    pub theme: ThemeConfig,
+    /// Where history is kept
+    pub storage: StorageConfig,
+    /// Confirmation before copying credentials while a browser or chat app is focused
+    pub paste_guard: PasteGuardConfig,
+    /// Preset for corporate data-handling policies
+    pub compliance: ComplianceConfig,
+    /// Scheduled backups made by the daemon
+    pub backup: BackupConfig,
+    /// Named profiles (`--profile`): settings overriding the ones above, e.g.
+    /// `[profiles.work.filters]`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, toml::Table>,
+}
+
+/// `[compliance]` section. When enabled, loading the config turns on
+/// encryption, the audit log and secret masking, caps `max_age` and
+/// `trash_retention` at 24h, turns off backups, and `serve` refuses to run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ComplianceConfig {
+    pub enabled: bool,
+}
+
+/// `[backup]` section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupConfig {
+    /// Back up this often while the daemon runs, e.g. "1d" (default: never)
+    #[serde(with = "opt_duration_str", skip_serializing_if = "Option::is_none")]
+    pub interval: Option<Duration>,
+    /// Where backups go (default: `backups/` in the storage dir)
+    pub dir: Option<PathBuf>,
+    /// How many backups to keep; older ones are deleted
+    pub keep: usize,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            interval: None,
+            dir: None,
+            keep: backup::DEFAULT_KEEP,
+        }
+    }
+}
+
+/// `[paste_guard]` section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PasteGuardConfig {
+    /// Off by default
+    pub enabled: bool,
+    /// App ids that need confirmation, matched case-insensitively as substrings
+    pub apps: Vec<String>,
+}
+
+impl Default for PasteGuardConfig {
+    fn default() -> Self {
+        let apps = [
+            "firefox", "chrom", "brave", "slack", "discord", "telegram", "signal", "element",
+            "teams", "whatsapp", "thunderbird",
+        ];
+        Self {
+            enabled: false,
+            apps: apps.iter().map(|app| app.to_string()).collect(),
+        }
+    }
+}
+
+impl PasteGuardConfig {
+    pub fn guards(&self, app_id: &str) -> bool {
+        let app_id = app_id.to_lowercase();
+        self.apps.iter().any(|app| app_id.contains(&app.to_lowercase()))
+    }
+}
+
+/// `[storage]` section
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    pub backend: StorageKind,
+    /// Remove unpinned entries last copied longer ago than this, e.g. "24h"
+    #[serde(with = "opt_duration_str", skip_serializing_if = "Option::is_none")]
+    pub max_age: Option<Duration>,
+    /// Encrypt entries and the index at rest (files backend only)
+    pub encrypt: bool,
+    /// Encryption key (default: ~/.config/clipstack/storage.key, created on first use)
+    pub key_file: Option<PathBuf>,
+    /// Append saves and removals (ids only) to `audit.log` in the storage dir
+    pub audit_log: bool,
+    /// Also keep the index as MessagePack, which loads faster (files backend only)
+    pub binary_index: bool,
+    /// How long deleted entries stay in the trash, e.g. "3d" (default 7 days, "0s" turns it off)
+    #[serde(with = "opt_duration_str", skip_serializing_if = "Option::is_none")]
+    pub trash_retention: Option<Duration>,
+    /// Move entries pruned by max_entries, quotas or the size budget to `archive.jsonl.gz`
+    /// in the storage dir instead of deleting them
+    pub archive: bool,
+    /// How long one-time codes (6-8 digits) are kept, e.g. "5m"; unset or "0s" keeps them
+    #[serde(with = "opt_duration_str", skip_serializing_if = "Option::is_none")]
+    pub otp_ttl: Option<Duration>,
+    /// Largest copy stored as is, e.g. "5MB"; `oversize` says what happens to larger ones
+    #[serde(with = "opt_size_str", skip_serializing_if = "Option::is_none")]
+    pub max_entry_size: Option<usize>,
+    pub oversize: Oversize,
+}
+
+impl StorageConfig {
+    pub fn key_path(&self) -> PathBuf {
+        self.key_file.clone().unwrap_or_else(Cipher::default_key_path)
+    }
+
+    /// The storage key when `encrypt` is on
+    pub fn cipher(&self) -> Result<Option<Cipher>> {
+        self.encrypt.then(|| Cipher::load_or_create(&self.key_path())).transpose()
+    }
+}
+
+/// History storage layout
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageKind {
+    /// `index.json` plus one file per entry
+    #[default]
+    Files,
+    /// A single `history.db`, imported from the files the first time it's used
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+/// What happens to a copy larger than `[storage] max_entry_size`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Oversize {
+    /// Not saved at all
+    #[default]
+    Skip,
+    /// Cut to `max_entry_size`, ending in a `[truncated]` marker
+    Truncate,
+    /// Saved whole, gzip-compressed on disk
+    Compress,
+}
+
+impl Oversize {
+    pub fn name(self) -> &'static str {
+        match self {
+            Oversize::Skip => "skip",
+            Oversize::Truncate => "truncate",
+            Oversize::Compress => "compress",
+        }
+    }
+}
+
+/// `[picker]` section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PickerConfig {
+    /// Time column: "relative" (5m ago) or a strftime format such as "%H:%M"
+    pub time_format: String,
+    /// Bottom line of the list view. Placeholders: {mode} {keys} {position}
+    /// {count} {total} {size} {age} {app} {pins}
+    pub status_line: String,
+    /// Record session counts and open-to-paste time for `stats --usage`
+    pub usage_stats: bool,
+    /// How image entries are drawn in the preview
+    pub image_preview: ImagePreview,
+    /// Order of the list, pinned entries first; any `list --sort` key
+    pub sort: SortKey,
+    /// Ask y/n before `d` deletes an entry or a tag
+    pub confirm_delete: bool,
+    /// Searches remembered across sessions for recall (0 remembers none)
+    pub search_history: usize,
+    /// Letter case in searches; Alt+C switches it while the picker is open
+    pub search_case: SearchCase,
+    /// Enter also types the entry into the focused window (`pick --type`)
+    pub type_on_enter: bool,
+    /// What types it: wtype or ydotool, or whichever is installed
+    pub type_tool: TypeTool,
+}
+
+/// Program that types picked entries (`[picker] type_tool`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TypeTool {
+    /// wtype when installed, else ydotool
+    #[default]
+    Auto,
+    /// Wayland virtual keyboard; not supported by GNOME
+    Wtype,
+    /// Kernel uinput, through the ydotoold service; works anywhere
+    Ydotool,
+}
+
+impl TypeTool {
+    pub fn program(self) -> &'static str {
+        match self {
+            TypeTool::Auto | TypeTool::Wtype => "wtype",
+            TypeTool::Ydotool => "ydotool",
+        }
+    }
+}
+
+/// How picker searches treat letter case (`[picker] search_case`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchCase {
+    /// Ignore case unless the query has an uppercase letter
+    #[default]
+    Smart,
+    Ignore,
+    Respect,
+}
+
+impl SearchCase {
+    /// The next one Alt+C switches to
+    pub fn next(self) -> Self {
+        match self {
+            SearchCase::Smart => SearchCase::Ignore,
+            SearchCase::Ignore => SearchCase::Respect,
+            SearchCase::Respect => SearchCase::Smart,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SearchCase::Smart => "smart",
+            SearchCase::Ignore => "ignore",
+            SearchCase::Respect => "respect",
+        }
+    }
+}
+
+/// Terminal graphics for image previews (`[picker] image_preview`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImagePreview {
+    /// Whichever protocol the terminal is known to support, if any
+    #[default]
+    Auto,
+    Kitty,
+    Sixel,
+    /// Only a line with the format, dimensions and size
+    Off,
+}
+
+impl Default for PickerConfig {
+    fn default() -> Self {
+        Self {
+            time_format: "relative".to_string(),
+            status_line: "[{mode}] {pins}  {keys}".to_string(),
+            usage_stats: false,
+            image_preview: ImagePreview::Auto,
+            sort: SortKey::Frecency,
+            confirm_delete: false,
+            search_history: 50,
+            search_case: SearchCase::Smart,
+            type_on_enter: false,
+            type_tool: TypeTool::Auto,
+        }
+    }
+}
+
+impl PickerConfig {
+    /// Whether the time column shows ages instead of formatted times
+    pub fn relative_time(&self) -> bool {
+        self.time_format == "relative"
+    }
+}
+
+/// `[theme]` section: a built-in theme and colors overriding it. Colors are
+/// names ("cyan", "lightblue"), 256-color indexes or "#rrggbb".
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThemeConfig {
+    /// "dark", "light" or "solarized"
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accent: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub focus: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub highlight_bg: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub highlight_fg: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pin: Option<String>,
+    /// Search match highlight
+    #[serde(rename = "match", skip_serializing_if = "Option::is_none")]
+    pub matched: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dim: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mark: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub success: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+}
+
+impl Default for ThemeConfig {
+    fn default() -> Self {
+        Self {
+            name: "dark".to_string(),
+            border: None,
+            accent: None,
+            focus: None,
+            highlight_bg: None,
+            highlight_fg: None,
+            pin: None,
+            matched: None,
+            dim: None,
+            mark: None,
+            snippet: None,
+            kind: None,
+            success: None,
+            warning: None,
+        }
+    }
+}
+
+/// `[filters]` section
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilterConfig {
+    /// Regex patterns; content matching any of them is never saved
+    pub ignore: Vec<String>,
+    /// App ids (case-insensitive) whose copies are never saved
+    pub ignore_apps: Vec<String>,
+    /// Content shorter than this many bytes is never saved
+    pub min_size: Option<usize>,
+    /// Content longer than this many bytes is never saved
+    pub max_size: Option<usize>,
+    /// Allowlist: when this or `allow_apps` is set, only content matching one of
+    /// these regexes (or copied from an allowed app) is saved
+    pub allow: Vec<String>,
+    /// App ids (case-insensitive) whose copies pass the allowlist
+    pub allow_apps: Vec<String>,
+}
+
+/// `[daemon]` section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DaemonConfig {
+    /// How often the clipboard is polled, e.g. "250ms"
+    #[serde(with = "duration_str")]
+    pub poll_interval: Duration,
+    /// PRIMARY selections are saved only once unchanged for this long ("0s" disables)
+    #[serde(with = "duration_str")]
+    pub primary_debounce: Duration,
+    /// Clear the system clipboard this long after the last copy (history is kept)
+    #[serde(with = "opt_duration_str", skip_serializing_if = "Option::is_none")]
+    pub clear_after: Option<Duration>,
+    /// Keep the clipboard and PRIMARY selection in sync
+    pub mirror: Mirror,
+    /// Identical content captured again within this window (e.g. selected,
+    /// then copied) is not saved a second time ("0s" disables)
+    #[serde(with = "duration_str")]
+    pub duplicate_window: Duration,
+    /// Local-time windows to record in, e.g. ["mon-fri 09:00-18:00"] (empty: always)
+    pub schedule: Schedule,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_millis(250),
+            primary_debounce: Duration::from_secs(1),
+            clear_after: None,
+            mirror: Mirror::Off,
+            duplicate_window: Duration::from_millis(500),
+            schedule: Schedule::default(),
+        }
+    }
+}
+
+/// Which way new selections are copied between the clipboard and PRIMARY
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Mirror {
+    #[default]
+    Off,
+    /// Mouse selections become pasteable with Ctrl+V
+    ToClipboard,
+    /// Copies become pasteable with middle click
+    ToPrimary,
+    Both,
+}
+
+impl Mirror {
+    pub fn to_clipboard(self) -> bool {
+        matches!(self, Mirror::ToClipboard | Mirror::Both)
+    }
+
+    pub fn to_primary(self) -> bool {
+        matches!(self, Mirror::ToPrimary | Mirror::Both)
+    }
+}
+
+/// `[capture]` section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CaptureConfig {
+    pub trailing_newline: TrailingNewline,
+    /// Other MIME types saved with a copy when it's offered in them too, so
+    /// the picker can restore e.g. formatted text (empty: plain text only)
+    pub formats: Vec<String>,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            trailing_newline: TrailingNewline::default(),
+            formats: vec!["text/html".to_string(), "image/png".to_string()],
+        }
+    }
+}
+
+/// Whether a trailing newline makes two copies different entries
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrailingNewline {
+    /// `foo` and `foo\n` are distinct entries
+    #[default]
+    Preserve,
+    /// Trailing newlines are ignored when deduplicating; the newest raw copy is kept
+    Strip,
+}
+
+impl TrailingNewline {
+    /// The text used to compare entries under this policy
+    pub fn normalize(self, content: &str) -> &str {
+        match self {
+            TrailingNewline::Preserve => content,
+            TrailingNewline::Strip => content.trim_end_matches(['\n', '\r']),
+        }
+    }
+}
+
+/// `[notifications]` section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationConfig {
+    /// Master switch (off by default)
+    pub enabled: bool,
+    /// Notify for regular clipboard copies
+    pub clipboard: bool,
+    /// Notify for PRIMARY (mouse selection) captures, which are frequent
+    pub primary: bool,
+    /// Minimum time between notifications, e.g. "3s"; captures in between stay silent
+    #[serde(with = "duration_str")]
+    pub min_interval: Duration,
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            clipboard: true,
+            primary: false,
+            min_interval: Duration::from_secs(3),
+        }
+    }
+}
+
+/// Durations are written like the CLI's `--for` values ("30s", "10m")
+mod duration_str {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(d: &Duration, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&crate::util::format_duration(*d))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Duration, D::Error> {
+        let s = String::deserialize(d)?;
+        crate::util::parse_duration(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Like `duration_str`, for optional settings
+mod opt_duration_str {
+    use serde::{Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(d: &Option<Duration>, s: S) -> Result<S::Ok, S::Error> {
+        match d {
+            Some(d) => super::duration_str::serialize(d, s),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Duration>, D::Error> {
+        super::duration_str::deserialize(d).map(Some)
+    }
+}
+
+/// Merge `overrides` into `base`, recursing into tables present in both
+fn merge_tables(base: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(value)) => {
+                merge_tables(base, value)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Sizes are written like the CLI's `--max-total-size` values ("50MB")
+mod opt_size_str {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(size: &Option<usize>, s: S) -> Result<S::Ok, S::Error> {
+        match size {
+            Some(size) => s.serialize_str(&crate::util::format_size(*size)),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<usize>, D::Error> {
+        let s = String::deserialize(d)?;
+        crate::util::parse_size(&s).map(Some).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Config {
+    /// Get the default path to the config file
+    pub fn default_path() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("clipstack")
+            .join("config.toml")
+    }
+
+    /// Load config from a specific path, falling back to defaults if absent,
+    /// as seen by the profile this process runs as
+    pub fn load_from(path: &Path) -> Result<Self> {
+        Self::load_profile(path, crate::profile::current())
+    }
+
+    /// Load config with a profile's `[profiles.<name>]` settings merged over the rest
+    pub fn load_profile(path: &Path, profile: Option<&str>) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config: {:?}", path))?;
+        let parsed = match profile {
+            Some(name) => Self::parse_profile(&data, name),
+            None => toml::from_str(&data).map_err(Into::into),
+        };
+        let mut config = parsed.with_context(|| format!("Invalid config: {:?}", path))?;
+        config.apply_compliance().with_context(|| format!("Invalid config: {:?}", path))?;
+
+        let time_format = &config.picker.time_format;
+        if !config.picker.relative_time()
+            && chrono::format::StrftimeItems::new(time_format)
+                .any(|item| item == chrono::format::Item::Error)
+        {
+            anyhow::bail!("Invalid config: {:?}: bad picker.time_format '{}'", path, time_format);
+        }
+        crate::theme::Theme::load(&config.theme)
+            .with_context(|| format!("Invalid config: {:?}", path))?;
+        Ok(config)
+    }
+
+    fn parse_profile(data: &str, name: &str) -> Result<Self> {
+        let mut table: toml::Table = toml::from_str(data)?;
+        let overrides = table.get("profiles").and_then(|p| p.get(name)?.as_table()).cloned();
+        if let Some(overrides) = overrides {
+            merge_tables(&mut table, overrides);
+        }
+        Ok(toml::Value::Table(table).try_into()?)
+    }
+
+    /// Override settings that `[compliance]` enforces
+    fn apply_compliance(&mut self) -> Result<()> {
+        if !self.compliance.enabled {
+            return Ok(());
+        }
+        anyhow::ensure!(
+            self.storage.backend == StorageKind::Files,
+            "compliance mode needs backend = \"files\" under [storage] (for encryption)"
+        );
+        self.storage.encrypt = true;
+        self.storage.audit_log = true;
+        self.mask_secrets = true;
+        let max_age = self.storage.max_age.unwrap_or(COMPLIANCE_MAX_AGE);
+        self.storage.max_age = Some(max_age.min(COMPLIANCE_MAX_AGE));
+        let trash = self.storage.trash_retention.unwrap_or(DEFAULT_TRASH_RETENTION);
+        self.storage.trash_retention = Some(trash.min(COMPLIANCE_MAX_AGE));
+        self.backup.interval = None;
+        self.storage.archive = false;
+        Ok(())
+    }
+
+    /// Short label for an app id: custom table first, then built-ins,
+    /// otherwise the last dotted segment truncated to the column width
+    pub fn app_label(&self, app_id: &str) -> String {
+        let key = app_id.to_lowercase();
+
+        if let Some(label) = self
+            .app_labels
+            .iter()
+            .find(|(k, _)| k.to_lowercase() == key)
+            .map(|(_, v)| v)
+        {
+            return label.chars().take(APP_LABEL_WIDTH).collect();
+        }
+
+        if let Some((_, label)) = BUILTIN_APP_LABELS.iter().find(|(k, _)| *k == key) {
+            return label.to_string();
+        }
+
+        key.rsplit('.')
+            .next()
+            .unwrap_or(&key)
+            .chars()
+            .take(APP_LABEL_WIDTH)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_missing_config_uses_defaults() {
+        let dir = TempDir::new().unwrap();
+        let config = Config::load_from(&dir.path().join("config.toml")).unwrap();
+        assert!(config.app_labels.is_empty());
+    }
+
+    #[test]
+    fn test_parse_app_labels() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "[app_labels]\n\"org.example.Notes\" = \"note\"\n").unwrap();
+
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.app_label("org.example.notes"), "note");
+    }
+
+    #[test]
+    fn test_invalid_config_is_error() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "app_labels = 42").unwrap();
+
+        assert!(Config::load_from(&path).is_err());
+    }
+
+    #[test]
+    fn test_parse_filters() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "[filters]\nignore = ['^-----BEGIN .* PRIVATE KEY', 'AKIA[0-9A-Z]{16}']\n")
+            .unwrap();
+
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.filters.ignore.len(), 2);
+        assert!(config.filters.ignore_apps.is_empty());
+        assert_eq!(config.filters.max_size, None);
+        assert!(config.filters.allow.is_empty());
+
+        fs::write(&path, "[filters]\nallow = ['^https://']\nallow_apps = ['kitty']\n").unwrap();
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.filters.allow, ["^https://"]);
+        assert_eq!(config.filters.allow_apps, ["kitty"]);
+    }
+
+    #[test]
+    fn test_parse_quotas() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "[quotas]\nurl = 20\npath = 5\n").unwrap();
+
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.quotas.get(&EntryKind::Url), Some(&20));
+        assert_eq!(config.quotas.get(&EntryKind::Path), Some(&5));
+
+        fs::write(&path, "[quotas]\nimages = 20\n").unwrap();
+        assert!(Config::load_from(&path).is_err(), "Unknown kinds are rejected");
+    }
+
+    #[test]
+    fn test_parse_size_filters() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "[filters]\nmin_size = 2\nmax_size = 10_485_760\n").unwrap();
+
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.filters.min_size, Some(2));
+        assert_eq!(config.filters.max_size, Some(10 * 1024 * 1024));
+
+        fs::write(&path, "[storage]\nmax_entry_size = \"5MB\"\noversize = \"truncate\"\n").unwrap();
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.storage.max_entry_size, Some(5 * 1024 * 1024));
+        assert_eq!(config.storage.oversize, Oversize::Truncate);
+        fs::write(&path, "[storage]\noversize = \"drop\"\n").unwrap();
+        assert!(Config::load_from(&path).is_err());
+    }
+
+    #[test]
+    fn test_profile_settings_override() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        let data = "max_entries = 50\n[filters]\nignore = ['a']\nmin_size = 2\n\
+                    [profiles.work]\nmax_entries = 500\n[profiles.work.filters]\nignore = ['b']\n";
+        fs::write(&path, data).unwrap();
+
+        let base = Config::load_profile(&path, None).unwrap();
+        assert_eq!(base.max_entries, Some(50));
+        assert_eq!(base.filters.ignore, ["a"]);
+        assert_eq!(base.profiles.keys().collect::<Vec<_>>(), ["work"]);
+
+        let work = Config::load_profile(&path, Some("work")).unwrap();
+        assert_eq!(work.max_entries, Some(500));
+        assert_eq!(work.filters.ignore, ["b"]);
+        assert_eq!(work.filters.min_size, Some(2), "Unset settings are inherited");
+        let other = Config::load_profile(&path, Some("home")).unwrap();
+        assert_eq!(other.max_entries, Some(50));
+
+        fs::write(&path, "[profiles.work]\nmax_entries = 'lots'\n").unwrap();
+        assert!(Config::load_profile(&path, None).is_ok());
+        assert!(Config::load_profile(&path, Some("work")).is_err());
+    }
+
+    #[test]
+    fn test_parse_picker() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "[picker]\ntime_format = '%H:%M'\n").unwrap();
+
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.picker.time_format, "%H:%M");
+        assert!(!config.picker.relative_time());
+        assert_eq!(config.picker.status_line, "[{mode}] {pins}  {keys}");
+        assert_eq!(config.picker.image_preview, ImagePreview::Auto);
+        assert_eq!(config.picker.sort, SortKey::Frecency);
+        assert_eq!(config.picker.type_tool, TypeTool::Auto);
+        assert!(Config::default().picker.relative_time());
+
+        fs::write(&path, "[picker]\nimage_preview = 'sixel'\nsort = 'time'\n").unwrap();
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.picker.image_preview, ImagePreview::Sixel);
+        assert_eq!(config.picker.sort, SortKey::Time);
+
+        fs::write(&path, "[picker]\ntype_on_enter = true\ntype_tool = 'ydotool'\n").unwrap();
+        let config = Config::load_from(&path).unwrap();
+        assert!(config.picker.type_on_enter);
+        assert_eq!(config.picker.type_tool.program(), "ydotool");
+
+        fs::write(&path, "[picker]\ntime_format = '%H:%Q'\n").unwrap();
+        let err = Config::load_from(&path).unwrap_err();
+        assert!(err.to_string().contains("time_format"));
+    }
+
+    #[test]
+    fn test_parse_theme() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "[theme]\nname = 'light'\nmatch = '#ff8800'\n").unwrap();
+
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.theme.name, "light");
+        assert_eq!(config.theme.matched.as_deref(), Some("#ff8800"));
+        assert_eq!(Config::default().theme.name, "dark");
+
+        fs::write(&path, "[theme]\nname = 'neon'\n").unwrap();
+        let err = Config::load_from(&path).unwrap_err();
+        assert!(format!("{:#}", err).contains("unknown theme 'neon'"));
+    }
+
+    #[test]
+    fn test_parse_notifications() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "[notifications]\nenabled = true\nmin_interval = \"10s\"\n").unwrap();
+
+        let config = Config::load_from(&path).unwrap();
+        assert!(config.notifications.enabled);
+        assert!(config.notifications.clipboard, "Unset keys keep their defaults");
+        assert!(!config.notifications.primary);
+        assert_eq!(config.notifications.min_interval, Duration::from_secs(10));
+
+        fs::write(&path, "[notifications]\nmin_interval = \"soon\"\n").unwrap();
+        assert!(Config::load_from(&path).is_err());
+    }
+
+    #[test]
+    fn test_parse_trailing_newline_policy() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        assert_eq!(
+            Config::default().capture.trailing_newline,
+            TrailingNewline::Preserve
+        );
+
+        fs::write(&path, "[capture]\ntrailing_newline = \"strip\"\n").unwrap();
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.capture.trailing_newline, TrailingNewline::Strip);
+        assert_eq!(TrailingNewline::Strip.normalize("foo\r\n\n"), "foo");
+        assert_eq!(TrailingNewline::Preserve.normalize("foo\n"), "foo\n");
+    }
+
+    #[test]
+    fn test_parse_daemon_settings() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        let toml = "max_entries = 500\n\n[daemon]\npoll_interval = \"500ms\"\n\
+                    primary_debounce = \"0s\"\nclear_after = \"5m\"\nmirror = \"to-primary\"\n";
+        fs::write(&path, toml).unwrap();
+
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.max_entries, Some(500));
+        assert_eq!(config.max_pinned, None);
+        assert_eq!(config.daemon.poll_interval, Duration::from_millis(500));
+        assert_eq!(config.daemon.clear_after, Some(Duration::from_secs(300)));
+        assert_eq!(config.daemon.primary_debounce, Duration::ZERO);
+        assert_eq!(config.daemon.mirror, Mirror::ToPrimary);
+        assert_eq!(config.daemon.duplicate_window, Duration::from_millis(500));
+        assert!(!config.daemon.mirror.to_clipboard());
+        assert_eq!(Config::default().daemon.poll_interval, Duration::from_millis(250));
+        assert_eq!(Config::default().daemon.clear_after, None);
+        assert!(config.daemon.schedule.is_empty());
+
+        fs::write(&path, "[daemon]\nschedule = [\"mon-fri 09:00-18:00\"]\n").unwrap();
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.daemon.schedule.describe(), "mon-fri 09:00-18:00");
+        fs::write(&path, "[daemon]\nschedule = [\"weekdays 9-5\"]\n").unwrap();
+        assert!(Config::load_from(&path).is_err());
+
+        assert_eq!(config.backup.keep, backup::DEFAULT_KEEP);
+        fs::write(&path, "[backup]\ninterval = \"12h\"\nkeep = 3\n").unwrap();
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.backup.interval, Some(Duration::from_secs(12 * 3600)));
+        assert_eq!(config.backup.keep, 3);
+    }
+
+    #[test]
+    fn test_compliance_enforces_settings() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        let toml = "[storage]\nmax_age = \"7d\"\narchive = true\n[backup]\ninterval = \"1d\"\n\
+                    [compliance]\nenabled = true\n";
+        fs::write(&path, toml).unwrap();
+        let config = Config::load_from(&path).unwrap();
+        assert!(config.storage.encrypt && config.storage.audit_log && config.mask_secrets);
+        assert_eq!(config.backup.interval, None);
+        assert!(!config.storage.archive);
+        assert_eq!(config.storage.max_age, Some(COMPLIANCE_MAX_AGE));
+
+        fs::write(&path, "[storage]\nmax_age = \"1h\"\n[compliance]\nenabled = true\n").unwrap();
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.storage.max_age, Some(Duration::from_secs(3600)), "Shorter is kept");
+
+        fs::write(&path, "[storage]\nbackend = \"sqlite\"\n[compliance]\nenabled = true\n")
+            .unwrap();
+        assert!(Config::load_from(&path).is_err());
+
+        fs::write(&path, "[storage]\nmax_age = \"12h\"\n").unwrap();
+        let config = Config::load_from(&path).unwrap();
+        assert!(!config.storage.encrypt && !config.mask_secrets);
+    }
+
+    #[test]
+    fn test_builtin_app_labels() {
+        let config = Config::default();
+        assert_eq!(config.app_label("firefox"), "ff");
+        assert_eq!(config.app_label("Alacritty"), "term");
+        assert_eq!(config.app_label("org.keepassxc.KeePassXC"), "kpx");
+    }
+
+    #[test]
+    fn test_custom_label_overrides_builtin() {
+        let mut config = Config::default();
+        config.app_labels.insert("firefox".to_string(), "web".to_string());
+        assert_eq!(config.app_label("firefox"), "web");
+    }
+
+    #[test]
+    fn test_unknown_app_label_truncated() {
+        let config = Config::default();
+        assert_eq!(config.app_label("org.example.Spreadsheet"), "spre");
+        assert_eq!(config.app_label("gimp"), "gimp");
+    }
+}
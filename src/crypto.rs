@@ -0,0 +1,153 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{anyhow, Context, Result};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+/// Default key file in the config dir, kept apart from the history it protects
+pub const KEY_FILE_NAME: &str = "storage.key";
+
+/// Starts every encrypted file; files without it are plain text from before
+/// encryption was turned on
+const MAGIC: &[u8] = b"clipstack-enc1\n";
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+/// Starts hashes made by `Cipher::keyed_hash`
+pub const KEYED_PREFIX: &str = "keyed:";
+
+/// AES-256-GCM encryption of history files (`[storage] encrypt`)
+#[derive(Clone)]
+pub struct Cipher {
+    aead: Aes256Gcm,
+    /// Derived from the key, for `keyed_hash`
+    hash_key: [u8; KEY_LEN],
+}
+
+impl Cipher {
+    pub fn default_key_path() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("clipstack")
+            .join(KEY_FILE_NAME)
+    }
+
+    /// Load the key at `path`, generating one readable only by the owner on first use
+    pub fn load_or_create(path: &Path) -> Result<Self> {
+        match fs::read(path) {
+            Ok(key) => Self::from_key(&key).with_context(|| format!("Invalid key file {:?}", path)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Self::create(path),
+            Err(e) => Err(e).with_context(|| format!("Failed to read key file {:?}", path)),
+        }
+    }
+
+    /// Load the key at `path`, which must exist
+    pub fn load(path: &Path) -> Result<Self> {
+        let key = fs::read(path).with_context(|| format!("Failed to read key file {:?}", path))?;
+        Self::from_key(&key).with_context(|| format!("Invalid key file {:?}", path))
+    }
+
+    fn create(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let key = Aes256Gcm::generate_key(OsRng);
+        match OpenOptions::new().write(true).create_new(true).mode(0o600).open(path) {
+            Ok(mut file) => {
+                file.write_all(&key)
+                    .and_then(|_| file.sync_all())
+                    .with_context(|| format!("Failed to write key file {:?}", path))?;
+                log::info!("[storage] Created encryption key {:?}", path);
+                Self::from_key(&key)
+            }
+            // Another process created it first: use theirs
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => Self::load_or_create(path),
+            Err(e) => Err(e).with_context(|| format!("Failed to create key file {:?}", path)),
+        }
+    }
+
+    fn from_key(key: &[u8]) -> Result<Self> {
+        anyhow::ensure!(key.len() == KEY_LEN, "expected {} bytes, found {}", KEY_LEN, key.len());
+        Ok(Self {
+            aead: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)),
+            hash_key: blake3::derive_key("clipstack keyed hash v1", key),
+        })
+    }
+
+    /// A hash of `data` that only the key's holder can compute, for files
+    /// left unencrypted: guesses at short secrets can't be checked against it
+    pub fn keyed_hash(&self, data: &str) -> String {
+        let hash = blake3::keyed_hash(&self.hash_key, data.as_bytes());
+        format!("{}{}", KEYED_PREFIX, hash.to_hex())
+    }
+
+    /// Encrypt under a fresh nonce: magic, nonce, then ciphertext with its tag
+    pub fn seal(&self, plain: &[u8]) -> Result<Vec<u8>> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let sealed = self.aead.encrypt(&nonce, plain).map_err(|_| anyhow!("Encryption failed"))?;
+        Ok([MAGIC, nonce.as_slice(), &sealed].concat())
+    }
+
+    /// Decrypt `seal` output; anything else is returned as is
+    pub fn open(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let Some(body) = data.strip_prefix(MAGIC) else {
+            return Ok(data.to_vec());
+        };
+        anyhow::ensure!(body.len() >= NONCE_LEN, "Encrypted data is truncated");
+        let (nonce, sealed) = body.split_at(NONCE_LEN);
+        self.aead
+            .decrypt(Nonce::from_slice(nonce), sealed)
+            .map_err(|_| anyhow!("Decryption failed: wrong key or damaged file"))
+    }
+}
+
+/// Whether `data` was written by `Cipher::seal`
+pub fn is_sealed(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_seal_round_trip_and_plain_passthrough() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("keys").join(KEY_FILE_NAME);
+        let cipher = Cipher::load_or_create(&path).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+
+        let sealed = cipher.seal(b"hunter2").unwrap();
+        assert!(is_sealed(&sealed));
+        assert!(!sealed.windows(7).any(|w| w == b"hunter2"));
+        assert_ne!(sealed, cipher.seal(b"hunter2").unwrap(), "Fresh nonce per write");
+
+        // The same key file opens it again; plain data passes through
+        let reloaded = Cipher::load_or_create(&path).unwrap();
+        assert_eq!(reloaded.open(&sealed).unwrap(), b"hunter2");
+        assert_eq!(reloaded.open(b"plain").unwrap(), b"plain");
+
+        let mut damaged = sealed.clone();
+        *damaged.last_mut().unwrap() ^= 1;
+        assert!(reloaded.open(&damaged).is_err());
+    }
+
+    #[test]
+    fn test_wrong_key_fails() {
+        let dir = TempDir::new().unwrap();
+        let a = Cipher::load_or_create(&dir.path().join("a.key")).unwrap();
+        let b = Cipher::load_or_create(&dir.path().join("b.key")).unwrap();
+        assert!(b.open(&a.seal(b"secret").unwrap()).is_err());
+
+        assert_ne!(a.keyed_hash("123456"), b.keyed_hash("123456"));
+        assert_eq!(a.keyed_hash("123456"), a.clone().keyed_hash("123456"));
+
+        fs::write(dir.path().join("short.key"), b"too short").unwrap();
+        assert!(Cipher::load_or_create(&dir.path().join("short.key")).is_err());
+    }
+}
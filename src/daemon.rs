@@ -1,52 +1,318 @@
-use crate::clipboard::Clipboard;
-use crate::storage::Storage;
+use crate::backup;
+use crate::clipboard::{self, ClipboardBackend, WlClipboard};
+use crate::config::{BackupConfig, CaptureConfig, Config, DaemonConfig, Oversize, StorageConfig};
+use crate::crypto::Cipher;
+use crate::error::Error;
+use crate::filters::Filters;
+use crate::focus;
+use crate::kind;
+use crate::metrics::{self, Metrics};
+use crate::notify::Notifier;
+use crate::profile;
+use crate::storage::{EntryMeta, SkipReason, Storage, DEFAULT_MAX_ENTRIES, DEFAULT_MAX_PINNED};
+use crate::systemd;
 use crate::util;
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use fs2::FileExt;
-use std::fs::File;
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Consecutive failed saves before the daemon enters degraded mode
+const DEGRADED_AFTER: u32 = 3;
+/// First write backoff once degraded; doubles with each further failure
+const BACKOFF_MIN: Duration = Duration::from_secs(5);
+const BACKOFF_MAX: Duration = Duration::from_secs(300);
+/// Set in the environment of a daemon started by `clipstack daemon --detach`
+pub const DETACHED_ENV: &str = "CLIPSTACK_DETACHED";
+
+/// Marker in the storage dir for content clipstack set itself: length and hash
+const OWN_COPY_FILE_NAME: &str = "own-copy";
+
+/// How often metrics are published for `clipstack stats --daemon` without a request
+const METRICS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often entries past `[storage] max_age` or `otp_ttl` are removed while nothing is copied
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Daemon state as seen through the lock file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DaemonState {
+    NotRunning,
+    /// Lock is held; pid is None for lock files written by older versions
+    Running(Option<u32>),
+    /// Lock is held but the recorded pid is no longer a clipstack process
+    Stale(u32),
+}
+
+/// Clears the system clipboard once it has gone untouched for `after`
+#[derive(Debug, Default)]
+struct AutoClear {
+    after: Option<Duration>,
+    last_copy: Option<Instant>,
+    /// We emptied the clipboard and haven't seen it empty yet
+    cleared: bool,
+}
+
+impl AutoClear {
+    /// The clipboard content changed (captured or not)
+    fn copied(&mut self, now: Instant) {
+        self.last_copy = Some(now);
+        self.cleared = false;
+    }
+
+    fn due(&self, now: Instant) -> bool {
+        match (self.after, self.last_copy) {
+            (Some(after), Some(last)) => now.duration_since(last) >= after,
+            _ => false,
+        }
+    }
+
+    /// Our own clear must not restart the timer; only a new copy does
+    fn did_clear(&mut self) {
+        self.last_copy = None;
+        self.cleared = true;
+    }
+
+    /// Seeing the clipboard empty after our clear confirms it took effect.
+    /// Returns true once, so the caller can forget the last hash and capture
+    /// the same content again if the user re-copies it.
+    fn confirm_empty(&mut self) -> bool {
+        std::mem::take(&mut self.cleared)
+    }
+}
+
+/// Holds back a selection until it has stopped changing, so dragging a mouse
+/// selection saves only the final text instead of every intermediate one
+#[derive(Debug)]
+struct Debounce {
+    interval: Duration,
+    pending: Option<(Vec<u8>, Instant)>, // Hash of the last seen content, and since when
+}
+
+impl Debounce {
+    fn new(interval: Duration) -> Self {
+        Self { interval, pending: None }
+    }
+
+    /// Whether `content` has been unchanged for the interval
+    fn settled(&mut self, content: &str, now: Instant) -> bool {
+        if self.interval.is_zero() || content.is_empty() {
+            return true;
+        }
+        let hash = util::compute_hash(content);
+        match &self.pending {
+            Some((pending, since)) if *pending == hash => {
+                now.duration_since(*since) >= self.interval
+            }
+            _ => {
+                self.pending = Some((hash, now));
+                false
+            }
+        }
+    }
+}
+
+/// Why the daemon stopped saving, published next to the lock file for `clipstack status`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Degraded {
+    /// Unix ms of the failure that started the degraded period
+    pub since: i64,
+    /// Consecutive failed saves
+    pub failures: u32,
+    pub last_error: String,
+    /// Unix ms of the next save attempt
+    pub retry_at: i64,
+}
+
+/// Tracks consecutive save failures and backs off writes once they pile up
+#[derive(Debug, Default)]
+struct SaveHealth {
+    failures: u32,
+    degraded: Option<Degraded>,
+    retry_at: Option<Instant>,
+}
+
+impl SaveHealth {
+    /// Whether a save may be attempted (not inside a backoff period)
+    fn can_write(&self, now: Instant) -> bool {
+        self.retry_at.is_none_or(|at| now >= at)
+    }
+
+    /// Record a failed save. Returns true if the daemon is now degraded.
+    fn failed(&mut self, error: &str, now: Instant, now_ms: i64) -> bool {
+        self.failures += 1;
+        if self.failures < DEGRADED_AFTER {
+            return false;
+        }
+        let doublings = self.failures - DEGRADED_AFTER;
+        let backoff = BACKOFF_MIN
+            .saturating_mul(1u32.checked_shl(doublings).unwrap_or(u32::MAX))
+            .min(BACKOFF_MAX);
+        self.retry_at = Some(now + backoff);
+        self.degraded = Some(Degraded {
+            since: self.degraded.as_ref().map_or(now_ms, |d| d.since),
+            failures: self.failures,
+            last_error: error.to_string(),
+            retry_at: now_ms + backoff.as_millis() as i64,
+        });
+        true
+    }
+
+    /// Record a successful save, returning the degraded period it ended
+    fn succeeded(&mut self) -> Option<Degraded> {
+        self.failures = 0;
+        self.retry_at = None;
+        self.degraded.take()
+    }
+}
+
+/// Which seats the daemon watches
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SeatSelection {
+    /// The compositor's default seat (wl-paste without --seat)
+    Default,
+    Named(Vec<String>),
+    /// Every seat the compositor reports, re-checked on config reload
+    All,
+}
+
+/// Per-seat capture state
+#[derive(Debug)]
+struct SeatWatch {
+    seat: Option<String>, // None: the default seat
+    last_clipboard_hash: Option<Vec<u8>>,
+    last_primary_hash: Option<Vec<u8>>,
+    primary_debounce: Debounce,
+    auto_clear: AutoClear,
+}
+
+impl SeatWatch {
+    fn new(seat: Option<String>, config: &DaemonConfig) -> Self {
+        let mut watch = Self {
+            seat,
+            last_clipboard_hash: None,
+            last_primary_hash: None,
+            primary_debounce: Debounce::new(config.primary_debounce),
+            auto_clear: AutoClear::default(),
+        };
+        watch.apply(config);
+        watch
+    }
+
+    fn apply(&mut self, config: &DaemonConfig) {
+        self.primary_debounce.interval = config.primary_debounce;
+        self.auto_clear.after = config.clear_after;
+    }
+}
+
+/// Log prefix for a selection, e.g. `clipboard` or `seat1/primary`
+fn source_label(seat: Option<&str>, source: &str) -> String {
+    match seat {
+        Some(seat) => format!("{}/{}", seat, source),
+        None => source.to_string(),
+    }
+}
 
 pub struct Daemon {
     storage: Storage,
     running: Arc<AtomicBool>,
     poll_interval: Duration,
+    config_path: PathBuf,
+    config_mtime: Option<SystemTime>, // Last seen config mtime, for hot reload
+    filters: Filters,
+    notifier: Notifier,
+    reload_requested: Arc<AtomicBool>, // Set by SIGHUP
+    fixed_max_entries: bool,           // max_entries came from CLI/env, ignore config
+    fixed_max_total_size: bool,        // max_total_size came from CLI, ignore config
+    fixed_max_pinned: bool,            // max_pinned came from CLI/env, ignore config
+    daemon_config: DaemonConfig, // Applied to seats found later
+    seat_selection: SeatSelection,
+    seats: Vec<SeatWatch>,
+    health: SaveHealth,
+    last_saved: Option<(Vec<u8>, Instant)>, // For coalescing within duplicate_window
+    health_path: PathBuf, // Degraded state for `clipstack status`, next to the lock
+    metrics: Metrics,
+    metrics_path: PathBuf, // Published counters for `clipstack stats --daemon`
+    metrics_published: Option<Instant>,
+    own_copy_path: PathBuf, // Marker for content clipstack set itself
+    dump_requested: Arc<AtomicBool>, // Set by SIGUSR1
+    expiry_checked: Option<Instant>,
+    backup_config: BackupConfig,
+    backup_checked: Option<Instant>,
+    cipher: Option<Cipher>, // Seals backups when the storage is encrypted
+    mask_secrets: bool, // Hide credential-looking previews in the log and notifications
+    formats: Vec<String>, // Other MIME types saved with a copy (`[capture] formats`)
+    pid_file: Option<PathBuf>,
+    clipboard: Arc<dyn ClipboardBackend>,
     _lock_file: File, // Keep lock file open to maintain lock
 }
 
 impl Daemon {
-    /// Get the default path to the daemon lock file
+    /// Get the default path to the daemon lock file; each profile has its own
     pub fn lock_file_path() -> PathBuf {
-        dirs::runtime_dir()
-            .unwrap_or_else(|| PathBuf::from("/tmp"))
-            .join("clipstack.lock")
+        let name = match profile::current() {
+            Some(profile) => format!("clipstack-{}.lock", profile),
+            None => "clipstack.lock".to_string(),
+        };
+        dirs::runtime_dir().unwrap_or_else(|| PathBuf::from("/tmp")).join(name)
+    }
+
+    /// Default pid file of a detached daemon
+    pub fn pid_file_path() -> PathBuf {
+        Self::lock_file_path().with_extension("pid")
     }
 
     /// Check if daemon is currently running by testing the lock file
     pub fn is_running() -> bool {
-        let lock_path = Self::lock_file_path();
-        if let Ok(file) = File::open(&lock_path) {
-            // Try to acquire exclusive lock - if fails, daemon is running
-            file.try_lock_exclusive().is_err()
-        } else {
-            false
-        }
+        matches!(Self::state(), DaemonState::Running(_))
+    }
+
+    /// Inspect the global lock file
+    pub fn state() -> DaemonState {
+        state_at(&Self::lock_file_path())
     }
 
-    pub fn new(storage_dir: Option<PathBuf>, max_entries: usize) -> Result<Self> {
-        Self::new_with_lock(storage_dir, max_entries, false)
+    /// Counters last published by the running daemon
+    pub fn metrics() -> Option<Metrics> {
+        metrics::read(&metrics_path(&Self::lock_file_path()))
+    }
+
+    /// Degraded state published by the running daemon, if it has stopped saving
+    pub fn degraded() -> Option<Degraded> {
+        read_degraded(&health_path(&Self::lock_file_path()))
+    }
+
+    pub fn new(
+        storage_dir: Option<PathBuf>,
+        max_entries: usize,
+        storage_config: &StorageConfig,
+    ) -> Result<Self> {
+        Self::open(storage_dir, max_entries, storage_config, false)
     }
 
     /// Create daemon with option to use local lock file (for tests)
+    #[cfg(test)]
     pub fn new_with_lock(
         storage_dir: Option<PathBuf>,
         max_entries: usize,
         use_local_lock: bool,
+    ) -> Result<Self> {
+        Self::open(storage_dir, max_entries, &StorageConfig::default(), use_local_lock)
+    }
+
+    fn open(
+        storage_dir: Option<PathBuf>,
+        max_entries: usize,
+        storage_config: &StorageConfig,
+        use_local_lock: bool,
     ) -> Result<Self> {
         let base_dir = storage_dir.unwrap_or_else(Storage::default_dir);
-        let storage = Storage::new(base_dir.clone(), max_entries)?;
+        let storage = Storage::open(base_dir.clone(), max_entries, storage_config)?;
 
         // Use storage-local lock file only when explicitly requested (for tests),
         // otherwise use global lock file path
@@ -57,20 +323,267 @@ impl Daemon {
         };
 
         // Acquire exclusive lock - fails if another daemon is running
-        let lock_file = File::create(&lock_path)
-            .with_context(|| format!("Failed to create lock file: {:?}", lock_path))?;
-        lock_file
-            .try_lock_exclusive()
-            .context("Daemon already running (lock file is held)")?;
+        let lock_file = acquire_lock(&lock_path)?;
 
         Ok(Self {
             storage,
             running: Arc::new(AtomicBool::new(false)),
             poll_interval: Duration::from_millis(250),
+            config_path: Config::default_path(),
+            config_mtime: None,
+            filters: Filters::default(),
+            notifier: Notifier::new(Default::default()),
+            reload_requested: Arc::new(AtomicBool::new(false)),
+            fixed_max_entries: false,
+            fixed_max_total_size: false,
+            fixed_max_pinned: false,
+            daemon_config: DaemonConfig::default(),
+            seat_selection: SeatSelection::Default,
+            seats: vec![SeatWatch::new(None, &DaemonConfig::default())],
+            health: SaveHealth::default(),
+            last_saved: None,
+            health_path: health_path(&lock_path),
+            metrics: Metrics::new(std::process::id(), util::now_millis()),
+            metrics_path: metrics_path(&lock_path),
+            metrics_published: None,
+            own_copy_path: base_dir.join(OWN_COPY_FILE_NAME),
+            dump_requested: Arc::new(AtomicBool::new(false)),
+            expiry_checked: None,
+            backup_config: BackupConfig::default(),
+            backup_checked: None,
+            cipher: storage_config.cipher()?,
+            mask_secrets: false,
+            formats: CaptureConfig::default().formats,
+            pid_file: None,
+            clipboard: Arc::new(WlClipboard),
             _lock_file: lock_file,
         })
     }
 
+    /// Read config from a custom path instead of the default location
+    pub fn with_config_path(mut self, path: PathBuf) -> Self {
+        self.config_path = path;
+        self
+    }
+
+    /// Write the daemon's pid to this file while it runs
+    pub fn with_pid_file(mut self, path: Option<PathBuf>) -> Self {
+        self.pid_file = path;
+        self
+    }
+
+    /// Read and clear the clipboard through `backend` instead of wl-clipboard
+    pub fn with_clipboard(mut self, backend: Arc<dyn ClipboardBackend>) -> Self {
+        self.clipboard = backend;
+        self
+    }
+
+    /// Keep the max_entries given on the command line instead of the config's
+    pub fn with_fixed_max_entries(mut self, fixed: bool) -> Self {
+        self.fixed_max_entries = fixed;
+        self
+    }
+
+    /// Keep this content budget (from the command line) instead of the config's
+    pub fn with_fixed_max_total_size(mut self, budget: Option<usize>) -> Result<Self> {
+        if budget.is_some() {
+            self.storage.set_max_total_size(budget)?;
+            self.fixed_max_total_size = true;
+        }
+        Ok(self)
+    }
+
+    /// Keep this pin limit (from the command line or env) instead of the config's
+    pub fn with_fixed_max_pinned(mut self, max_pinned: Option<usize>) -> Result<Self> {
+        if let Some(max) = max_pinned {
+            self.storage.set_max_pinned(max)?;
+            self.fixed_max_pinned = true;
+        }
+        Ok(self)
+    }
+
+    /// Watch the given seats instead of the default one
+    pub fn with_seats(mut self, selection: SeatSelection) -> Self {
+        self.seat_selection = selection;
+        self.refresh_seats();
+        self
+    }
+
+    /// Sync the watched seats with the selection, keeping state for seats
+    /// that are still present
+    fn refresh_seats(&mut self) {
+        let names: Vec<Option<String>> = match &self.seat_selection {
+            SeatSelection::Default => vec![None],
+            SeatSelection::Named(names) => names.iter().cloned().map(Some).collect(),
+            SeatSelection::All => {
+                let found = focus::seats();
+                if found.is_empty() {
+                    log::info!("[seat] Compositor doesn't list seats; watching the default seat");
+                    vec![None]
+                } else {
+                    found.into_iter().map(Some).collect()
+                }
+            }
+        };
+
+        let mut old = std::mem::take(&mut self.seats);
+        for name in names {
+            let watch = match old.iter().position(|w| w.seat == name) {
+                Some(pos) => old.remove(pos),
+                None => {
+                    if let Some(seat) = &name {
+                        log::info!("[seat] Watching seat {}", seat);
+                    }
+                    SeatWatch::new(name, &self.daemon_config)
+                }
+            };
+            self.seats.push(watch);
+        }
+        for gone in old.iter().filter_map(|w| w.seat.as_deref()) {
+            log::info!("[seat] Seat {} is gone", gone);
+        }
+    }
+
+    /// Flag that makes the loop re-read the config (set on SIGHUP)
+    pub fn reload_handle(&self) -> Arc<AtomicBool> {
+        self.reload_requested.clone()
+    }
+
+    /// Flag that makes the loop log and publish its metrics (set on SIGUSR1)
+    pub fn dump_handle(&self) -> Arc<AtomicBool> {
+        self.dump_requested.clone()
+    }
+
+    /// Publish the metrics every METRICS_INTERVAL, or right away (and to the
+    /// log) when a dump was requested
+    fn publish_metrics_if_due(&mut self) {
+        let dump = self.dump_requested.swap(false, Ordering::SeqCst);
+        let due = self.metrics_published.is_none_or(|at| at.elapsed() >= METRICS_INTERVAL);
+        if !dump && !due {
+            return;
+        }
+        self.metrics.updated = util::now_millis();
+        if dump {
+            log::info!("[metrics] {}", self.metrics.summary());
+        }
+        if let Err(e) = metrics::write(&self.metrics_path, &self.metrics) {
+            log::warn!("[metrics] Failed to publish: {:#}", e);
+        }
+        self.metrics_published = Some(Instant::now());
+    }
+
+    /// Remove expired entries every EXPIRY_INTERVAL, so retention holds even
+    /// when nothing new is saved
+    fn expire_if_due(&mut self) {
+        if !self.storage.expires_entries()
+            || self.expiry_checked.is_some_and(|at| at.elapsed() < EXPIRY_INTERVAL)
+        {
+            return;
+        }
+        self.expiry_checked = Some(Instant::now());
+        match self.storage.prune() {
+            Ok(removed) if !removed.is_empty() => {
+                log::info!("[storage] Removed {} expired entries", removed.len());
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("[storage] Failed to remove expired entries: {:#}", e),
+        }
+    }
+
+    /// Back up the history when the newest backup is older than `[backup]
+    /// interval`, checking every EXPIRY_INTERVAL
+    fn backup_if_due(&mut self) {
+        let Some(interval) = self.backup_config.interval else {
+            return;
+        };
+        if self.backup_checked.is_some_and(|at| at.elapsed() < EXPIRY_INTERVAL) {
+            return;
+        }
+        self.backup_checked = Some(Instant::now());
+        let dir = match &self.backup_config.dir {
+            Some(dir) => dir.clone(),
+            None => backup::default_dir(&self.storage),
+        };
+        if !backup::due(&dir, interval) {
+            return;
+        }
+        let keep = self.backup_config.keep;
+        match backup::create(&self.storage, &dir, keep, self.cipher.as_ref()) {
+            Ok(backup) => log::info!(
+                "[backup] Saved {} entries to {:?}, removed {} older backups",
+                backup.entries,
+                backup.path,
+                backup.removed.len()
+            ),
+            Err(e) => log::warn!("[backup] Failed: {:#}", e),
+        }
+    }
+
+    /// Reload filters, notification, capture and daemon settings if the config
+    /// file changed since the last check or a reload was requested (SIGHUP).
+    /// An invalid config keeps the previous settings so a typo doesn't
+    /// silently disable the filters. The lock is held throughout.
+    fn reload_config_if_changed(&mut self) {
+        let requested = self.reload_requested.swap(false, Ordering::SeqCst);
+        let mtime = std::fs::metadata(&self.config_path)
+            .and_then(|m| m.modified())
+            .ok();
+        if !requested && mtime == self.config_mtime {
+            return;
+        }
+        self.config_mtime = mtime;
+        if requested {
+            log::info!("[config] Reload requested");
+        }
+
+        let loaded = Config::load_from(&self.config_path)
+            .and_then(|c| Filters::from_config(&c).map(|f| (c, f)));
+        match loaded {
+            Ok((config, filters)) => {
+                log::info!(
+                    "[config] Loaded {} ignore filters from {:?}",
+                    filters.patterns().count(),
+                    self.config_path
+                );
+                self.filters = filters;
+                self.notifier.set_config(config.notifications);
+                self.mask_secrets = config.mask_secrets;
+                self.storage.set_trailing_newline(config.capture.trailing_newline);
+                self.formats = config.capture.formats;
+                self.poll_interval = config.daemon.poll_interval;
+                for watch in &mut self.seats {
+                    watch.apply(&config.daemon);
+                }
+                self.daemon_config = config.daemon.clone();
+                self.backup_config = config.backup;
+                if self.seat_selection == SeatSelection::All {
+                    self.refresh_seats();
+                }
+                if let Err(e) = self.storage.set_quotas(config.quotas) {
+                    log::error!("[config] Failed to apply quotas: {:#}", e);
+                }
+                if !self.fixed_max_entries {
+                    let max = config.max_entries.unwrap_or(DEFAULT_MAX_ENTRIES);
+                    if let Err(e) = self.storage.set_max_entries(max) {
+                        log::error!("[config] Failed to apply max_entries: {:#}", e);
+                    }
+                }
+                if !self.fixed_max_total_size
+                    && let Err(e) = self.storage.set_max_total_size(config.max_total_size)
+                {
+                    log::error!("[config] Failed to apply max_total_size: {:#}", e);
+                }
+                let max_pinned = config.max_pinned.unwrap_or(DEFAULT_MAX_PINNED);
+                if !self.fixed_max_pinned
+                    && let Err(e) = self.storage.set_max_pinned(max_pinned)
+                {
+                    log::error!("[config] Failed to apply max_pinned: {:#}", e);
+                }
+            }
+            Err(e) => log::warn!("[config] Keeping previous settings: {:#}", e),
+        }
+    }
+
     #[allow(dead_code)]
     pub fn with_poll_interval(mut self, interval: Duration) -> Self {
         self.poll_interval = interval;
@@ -78,54 +591,283 @@ impl Daemon {
     }
 
     /// Run the daemon, monitoring clipboard and saving changes
-    pub fn run(&self) -> Result<()> {
+    pub fn run(&mut self) -> Result<()> {
         self.running.store(true, Ordering::SeqCst);
+        // Left behind if a previous daemon died while degraded
+        let _ = fs::remove_file(&self.health_path);
+        self.metrics = Metrics::new(std::process::id(), util::now_millis());
+        if let Some(path) = &self.pid_file
+            && let Err(e) = fs::write(path, format!("{}\n", std::process::id()))
+        {
+            log::warn!("Failed to write pid file {:?}: {}", path, e);
+        }
 
-        let mut last_clipboard_hash: Option<Vec<u8>> = None;
-        let mut last_primary_hash: Option<Vec<u8>> = None;
+        let mut was_paused = false;
 
-        eprintln!("clipstack daemon started, monitoring clipboard + primary selection...");
+        log::info!("clipstack daemon started, monitoring clipboard + primary selection...");
 
-        while self.running.load(Ordering::SeqCst) {
-            // Check regular clipboard
-            self.check_and_save(Clipboard::paste(), &mut last_clipboard_hash, "clipboard");
+        // Under systemd (Type=notify) signal readiness and keep the watchdog fed
+        systemd::notify("READY=1");
+        let watchdog = systemd::watchdog_interval();
+        let mut last_ping = Instant::now();
 
-            // Check PRIMARY selection (mouse selection, used by terminals)
-            self.check_and_save(Clipboard::paste_primary(), &mut last_primary_hash, "primary");
+        while self.running.load(Ordering::SeqCst) {
+            if let Some(interval) = watchdog
+                && last_ping.elapsed() >= interval
+            {
+                systemd::notify("WATCHDOG=1");
+                last_ping = Instant::now();
+            }
 
+            was_paused = self.poll(was_paused);
             std::thread::sleep(self.poll_interval);
         }
 
-        eprintln!("clipstack daemon stopped");
+        let _ = fs::remove_file(&self.health_path);
+        let _ = fs::remove_file(&self.metrics_path);
+        let _ = fs::remove_file(&self.own_copy_path);
+        if let Some(path) = &self.pid_file {
+            let _ = fs::remove_file(path);
+        }
+        log::info!("[metrics] {}", self.metrics.summary());
+        systemd::notify("STOPPING=1");
+        log::info!("clipstack daemon stopped");
         Ok(())
     }
 
-    fn check_and_save(
+    /// One pass over every watched seat. Takes and returns the pause state of
+    /// the previous pass so pausing and resuming are logged once.
+    fn poll(&mut self, was_paused: bool) -> bool {
+        // Cheap mtime check makes filter edits take effect without a restart
+        self.reload_config_if_changed();
+
+        // Pause state lives in the storage dir so `clipstack pause` reaches us
+        let now = chrono::Local::now().naive_local();
+        let off_schedule = !self.daemon_config.schedule.active_at(now);
+        let paused = self.storage.is_paused() || off_schedule;
+        if paused != was_paused {
+            let state = match (paused, off_schedule) {
+                (false, _) => "resumed",
+                (true, true) => "paused (outside schedule)",
+                (true, false) => "paused",
+            };
+            log::info!("clipstack capture {}", state);
+        }
+
+        let started = Instant::now();
+        let mut seats = std::mem::take(&mut self.seats);
+        for watch in &mut seats {
+            self.poll_seat(watch, paused);
+        }
+        self.seats = seats;
+        self.metrics.record_poll(started.elapsed());
+
+        self.publish_metrics_if_due();
+        self.expire_if_due();
+        self.backup_if_due();
+        paused
+    }
+
+    /// Check one seat's clipboard and PRIMARY selection
+    fn poll_seat(&mut self, watch: &mut SeatWatch, paused: bool) {
+        let seat = watch.seat.as_deref();
+
+        // Check regular clipboard
+        let clipboard = self.clipboard.paste_from(seat, false);
+        if matches!(&clipboard, Ok(c) if c.is_empty()) && watch.auto_clear.confirm_empty() {
+            watch.last_clipboard_hash = None;
+        }
+        let previous = watch.last_clipboard_hash.clone();
+        let mirror =
+            self.daemon_config.mirror.to_primary().then(|| clipboard.as_ref().ok().cloned());
+        self.check_and_save(clipboard, &mut watch.last_clipboard_hash, seat, "clipboard", paused);
+        if watch.last_clipboard_hash != previous {
+            watch.auto_clear.copied(Instant::now());
+            if let Some(Some(content)) = &mirror
+                && watch.last_clipboard_hash.is_some()
+            {
+                self.mirror(seat, content, true, &mut watch.last_primary_hash);
+            }
+        }
+        self.clear_clipboard_if_idle(seat, &mut watch.auto_clear);
+
+        // Check PRIMARY selection (mouse selection, used by terminals) once
+        // it has settled; a drag changes it on every poll
+        let primary = self.clipboard.paste_from(seat, true);
+        let settled = match &primary {
+            Ok(content) => watch.primary_debounce.settled(content, Instant::now()),
+            Err(_) => true,
+        };
+        if settled {
+            let previous = watch.last_primary_hash.clone();
+            let mirror =
+                self.daemon_config.mirror.to_clipboard().then(|| primary.as_ref().ok().cloned());
+            self.check_and_save(primary, &mut watch.last_primary_hash, seat, "primary", paused);
+            if let Some(Some(content)) = &mirror
+                && watch.last_primary_hash != previous
+                && watch.last_primary_hash.is_some()
+                && self.mirror(seat, content, false, &mut watch.last_clipboard_hash)
+            {
+                watch.auto_clear.copied(Instant::now());
+            }
+        }
+    }
+
+    /// Copy a new selection to the other one (`mirror` setting). The target's
+    /// hash is updated so the copy is neither saved again nor mirrored back.
+    /// Password manager secrets stay where they were copied.
+    fn mirror(
         &self,
+        seat: Option<&str>,
+        content: &str,
+        to_primary: bool,
+        target_hash: &mut Option<Vec<u8>>,
+    ) -> bool {
+        let (from, to) = if to_primary {
+            ("clipboard", "primary")
+        } else {
+            ("primary", "clipboard")
+        };
+        let label = source_label(seat, from);
+        let types = self.clipboard.list_types(seat, !to_primary).unwrap_or_default();
+        if clipboard::has_password_manager_hint(&types) {
+            log::debug!("[{}] Not mirroring password manager content", label);
+            return false;
+        }
+        match self.clipboard.copy_to(seat, to_primary, content) {
+            Ok(()) => {
+                *target_hash = Some(util::compute_hash(content));
+                log::debug!("[{}] Mirrored to {}", label, to);
+                true
+            }
+            Err(e) => {
+                log::warn!("[{}] Failed to mirror to {}: {:#}", label, to, e);
+                false
+            }
+        }
+    }
+
+    fn check_and_save(
+        &mut self,
         result: Result<String>,
         last_hash: &mut Option<Vec<u8>>,
+        seat: Option<&str>,
         source: &str,
+        paused: bool,
     ) {
+        let label = source_label(seat, source);
         match result {
             Ok(content) if !content.is_empty() => {
                 let hash = util::compute_hash(&content);
 
                 if last_hash.as_ref() != Some(&hash) {
-                    *last_hash = Some(hash);
+                    // Track the hash even while paused so resuming doesn't
+                    // capture whatever was copied during the pause
+                    *last_hash = Some(hash.clone());
+                    if self.is_own_copy(&content) {
+                        log::debug!("[{}] Set by clipstack itself, already recorded", label);
+                        return;
+                    }
+                    if paused {
+                        return;
+                    }
+                    if !self.health.can_write(Instant::now()) {
+                        // Backing off after repeated failures; retry this content later
+                        *last_hash = None;
+                        return;
+                    }
+                    if let Some((saved, at)) = &self.last_saved
+                        && *saved == hash
+                        && at.elapsed() < self.daemon_config.duplicate_window
+                    {
+                        // e.g. selected (PRIMARY) and then copied: already at the front
+                        log::debug!("[{}] Coalesced with the previous capture", label);
+                        self.metrics.duplicates += 1;
+                        return;
+                    }
+
+                    if self.filters.too_small(content.len()) {
+                        let detail = format!("{} bytes is below min_size", content.len());
+                        self.skip(&label, SkipReason::TooSmall, detail);
+                        return;
+                    }
+                    if self.filters.too_large(content.len()) {
+                        let detail =
+                            format!("{} exceeds max_size", util::format_size(content.len()));
+                        self.skip(&label, SkipReason::TooLarge, detail);
+                        return;
+                    }
+                    if self.storage.oversize(content.len()) == Some(Oversize::Skip) {
+                        let detail =
+                            format!("{} exceeds max_entry_size", util::format_size(content.len()));
+                        self.skip(&label, SkipReason::TooLarge, detail);
+                        return;
+                    }
+
+                    // Password managers flag secrets with a MIME hint; never persist those
+                    let types =
+                        self.clipboard.list_types(seat, source == "primary").unwrap_or_default();
+                    if clipboard::has_password_manager_hint(&types) {
+                        let detail = "password manager content".to_string();
+                        self.skip(&label, SkipReason::PasswordManager, detail);
+                        return;
+                    }
 
-                    match self.storage.save_entry(&content) {
-                        Ok(entry) => {
+                    if let Some(pattern) = self.filters.ignore_match(&content) {
+                        let detail = format!("matches ignore filter '{}'", pattern);
+                        self.skip(&label, SkipReason::IgnorePattern, detail);
+                        return;
+                    }
+
+                    let window = focus::focused_window();
+                    let source_app = window.as_ref().map(|w| w.app_id.clone());
+                    if let Some(app) = &source_app
+                        && self.filters.ignores_app(app)
+                    {
+                        let detail = format!("copied from ignored app '{}'", app);
+                        self.skip(&label, SkipReason::IgnoredApp, detail);
+                        return;
+                    }
+                    if !self.filters.allows(&content, source_app.as_deref()) {
+                        let detail = "not on the allowlist".to_string();
+                        self.skip(&label, SkipReason::NotAllowed, detail);
+                        return;
+                    }
+
+                    let meta = EntryMeta {
+                        source_app,
+                        window_title: window.and_then(|w| w.title),
+                        seat: seat.map(str::to_string),
+                        context: None,
+                    };
+                    match self.storage.save_entry_checked(&content, meta) {
+                        Ok((entry, duplicate)) => {
+                            let formats =
+                                self.read_formats(&label, seat, source == "primary", &types);
+                            if let Err(e) = self.storage.set_representations(&entry.id, formats) {
+                                log::warn!("[{}] Failed to save other formats: {:#}", label, e);
+                            }
+                            self.save_succeeded();
+                            self.last_saved = Some((hash, Instant::now()));
+                            if duplicate {
+                                self.metrics.duplicates += 1;
+                            } else {
+                                self.metrics.saved += 1;
+                            }
                             // Use chars().take() for safe Unicode truncation
-                            let preview: String = entry.preview.chars().take(40).collect();
-                            eprintln!(
+                            let shown = kind::masked(&entry.preview, self.mask_secrets);
+                            let preview: String = shown.chars().take(40).collect();
+                            log::info!(
                                 "[{}] Saved: {} bytes, preview: {}...",
-                                source,
+                                label,
                                 entry.size,
                                 preview
                             );
+                            self.notifier.entry_saved(source, entry.size, shown);
                         }
                         Err(e) => {
-                            eprintln!("[{}] Error saving entry: {}", source, e);
+                            *last_hash = None;
+                            self.save_failed(&label, &e);
                         }
                     }
                 }
@@ -135,6 +877,126 @@ impl Daemon {
         }
     }
 
+    /// Empty the system clipboard once `clear_after` has passed since the last copy.
+    /// The cleared clipboard reads as empty, which is never captured, so this
+    /// can't feed back into the history.
+    fn clear_clipboard_if_idle(&self, seat: Option<&str>, auto_clear: &mut AutoClear) {
+        if !auto_clear.due(Instant::now()) {
+            return;
+        }
+        let label = source_label(seat, "clipboard");
+        match self.clipboard.clear(seat) {
+            Ok(()) => {
+                log::info!(
+                    "[{}] Cleared after {} without a new copy",
+                    label,
+                    util::format_duration(auto_clear.after.unwrap_or_default())
+                );
+                auto_clear.did_clear();
+            }
+            Err(e) => {
+                log::warn!("[{}] Failed to clear: {:#}", label, e);
+                // Retry after another full interval rather than every poll
+                auto_clear.copied(Instant::now());
+            }
+        }
+    }
+
+    /// Track a failed save. After repeated failures the daemon is degraded: it
+    /// tries to rebuild the index, backs off further writes and publishes the
+    /// condition for `clipstack status`.
+    fn save_failed(&mut self, source: &str, error: &anyhow::Error) {
+        self.metrics.errors += 1;
+        let message = format!("{:#}", error);
+        log::error!("[{}] Error saving entry: {}", source, message);
+        if !self.health.failed(&message, Instant::now(), util::now_millis()) {
+            return;
+        }
+        let Some(degraded) = self.health.degraded.clone() else {
+            return;
+        };
+
+        let backoff = Duration::from_millis((degraded.retry_at - util::now_millis()).max(0) as u64);
+        log::warn!(
+            "[storage] {} consecutive save failures, degraded; retrying in {}",
+            degraded.failures,
+            util::format_duration(backoff)
+        );
+        match self.storage.attempt_recovery() {
+            Ok(summary) => log::info!(
+                "[storage] Recovery rebuilt the index with {} entries",
+                summary.remaining
+            ),
+            Err(e) => log::error!("[storage] Recovery failed: {:#}", e),
+        }
+        if let Err(e) = write_degraded(&self.health_path, &degraded) {
+            log::warn!("[storage] Failed to publish degraded state: {:#}", e);
+        }
+    }
+
+    fn save_succeeded(&mut self) {
+        if let Some(degraded) = self.health.succeeded() {
+            log::info!(
+                "[storage] Saving again after {} failed attempts",
+                degraded.failures
+            );
+            let _ = fs::remove_file(&self.health_path);
+        }
+    }
+
+    /// Log a skipped capture and count it for `clipstack stats`
+    /// Whether `content` was just put on the clipboard by clipstack itself
+    /// (see [`mark_own_copy`]); a match consumes the marker
+    fn is_own_copy(&self, content: &str) -> bool {
+        let Ok(marker) = fs::read_to_string(&self.own_copy_path) else {
+            return false;
+        };
+        let Some((len, hash)) = marker.split_once('\n') else {
+            return false;
+        };
+        // Length first: most captures are ruled out without hashing again
+        if len.parse() != Ok(content.len()) || hash != util::compute_hash_string(content) {
+            return false;
+        }
+        let _ = fs::remove_file(&self.own_copy_path);
+        true
+    }
+
+    /// Read the other formats (`[capture] formats`) among the `offered` types,
+    /// leaving out any too large to keep
+    fn read_formats(
+        &self,
+        label: &str,
+        seat: Option<&str>,
+        primary: bool,
+        offered: &[String],
+    ) -> Vec<(String, Vec<u8>)> {
+        let mut formats = Vec::new();
+        for mime in self.formats.iter().filter(|m| offered.contains(m)) {
+            match self.clipboard.paste_type(seat, primary, mime) {
+                Ok(data) if data.is_empty() => {}
+                Ok(data)
+                    if self.filters.too_large(data.len())
+                        || self.storage.oversize(data.len()).is_some() =>
+                {
+                    let size = util::format_size(data.len());
+                    log::debug!("[{}] Not saving {} {}: too large", label, size, mime);
+                }
+                Ok(data) => formats.push((mime.clone(), data)),
+                Err(e) => log::debug!("[{}] Failed to read {}: {:#}", label, mime, e),
+            }
+        }
+        formats
+    }
+
+    fn skip(&mut self, source: &str, reason: SkipReason, detail: String) {
+        self.metrics.skipped += 1;
+        log::info!("[{}] Skipped: {}", source, detail);
+        if let Err(e) = self.storage.record_skip(reason) {
+            log::warn!("[{}] Error recording skip: {}", source, e);
+        }
+    }
+
     /// Stop the daemon
     #[allow(dead_code)]
     pub fn stop(&self) {
@@ -147,6 +1009,212 @@ impl Daemon {
     }
 }
 
+/// Start the daemon in the background (`daemon --detach`): this command line
+/// runs again without --detach in a new session, so it has no controlling
+/// terminal, with stdout and stderr appended to the log. `pid_file` None
+/// passes the default pid file. Returns the daemon's pid once it holds the
+/// lock, so startup errors such as an invalid config are reported here.
+pub fn detach(pid_file: Option<&Path>, log_path: &Path) -> Result<u32> {
+    use std::os::unix::process::CommandExt;
+    use std::process::{Command, Stdio};
+
+    if let DaemonState::Running(pid) = Daemon::state() {
+        return Err(Error::DaemonLocked { pid }.into());
+    }
+
+    let exe = std::env::current_exe().context("Cannot locate the clipstack binary")?;
+    let mut args: Vec<std::ffi::OsString> =
+        std::env::args_os().skip(1).filter(|a| a != "--detach").collect();
+    if pid_file.is_none() {
+        let mut arg = std::ffi::OsString::from("--pid-file=");
+        arg.push(Daemon::pid_file_path());
+        args.push(arg);
+    }
+
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path)
+        .with_context(|| format!("Failed to open log file: {:?}", log_path))?;
+    let mut cmd = Command::new(&exe);
+    cmd.args(&args)
+        .env(DETACHED_ENV, "1")
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+    // SAFETY: setsid is async-signal-safe and touches no memory of the parent
+    unsafe {
+        cmd.pre_exec(|| {
+            if libc::setsid() == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let mut child = cmd.spawn().with_context(|| format!("Failed to start {:?}", exe))?;
+
+    let deadline = Instant::now() + Duration::from_secs(3);
+    loop {
+        if let Some(status) = child.try_wait()? {
+            bail!("Daemon exited during startup ({}); see {:?}", status, log_path);
+        }
+        if Daemon::state() == DaemonState::Running(Some(child.id())) || Instant::now() >= deadline {
+            return Ok(child.id());
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Whether a pid belongs to a live clipstack process (guards against pid reuse)
+pub fn process_alive(pid: u32) -> bool {
+    fs::read_to_string(format!("/proc/{}/comm", pid))
+        .map(|comm| comm.trim().starts_with("clipstack"))
+        .unwrap_or(false)
+}
+
+/// Command line of a running process, for relaunching it with the same arguments
+pub fn process_cmdline(pid: u32) -> Option<Vec<String>> {
+    let raw = fs::read(format!("/proc/{}/cmdline", pid)).ok()?;
+    let args: Vec<String> = raw
+        .split(|&b| b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect();
+    (!args.is_empty()).then_some(args)
+}
+
+/// Send a signal (by name, e.g. "HUP") to a running daemon
+pub fn signal_process(pid: u32, signal: &str) -> Result<()> {
+    let status = std::process::Command::new("kill")
+        .arg(format!("-{}", signal))
+        .arg(pid.to_string())
+        .status()
+        .context("Failed to run kill")?;
+    if !status.success() {
+        bail!("Failed to signal daemon (pid {})", pid);
+    }
+    Ok(())
+}
+
+/// Ask a running daemon to exit (SIGTERM) and wait until its lock is released
+pub fn stop_process(pid: u32, timeout: Duration) -> Result<()> {
+    signal_process(pid, "TERM")?;
+
+    let deadline = Instant::now() + timeout;
+    while process_alive(pid) {
+        if Instant::now() >= deadline {
+            bail!("Daemon (pid {}) did not stop within {}s", pid, timeout.as_secs());
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    Ok(())
+}
+
+fn read_pid(file: &mut File) -> Option<u32> {
+    let mut data = String::new();
+    file.rewind().ok()?;
+    file.read_to_string(&mut data).ok()?;
+    data.trim().parse().ok()
+}
+
+/// Degraded state file kept next to the lock (`clipstack.health`)
+fn health_path(lock_path: &Path) -> PathBuf {
+    lock_path.with_extension("health")
+}
+
+fn metrics_path(lock_path: &Path) -> PathBuf {
+    lock_path.with_extension("metrics")
+}
+
+fn write_own_copy_marker(path: &Path, content: &str) {
+    let marker = format!("{}\n{}", content.len(), util::compute_hash_string(content));
+    if let Err(e) = fs::write(path, marker) {
+        log::debug!("Failed to write {:?}: {}", path, e);
+    }
+}
+
+/// Tell a running daemon that clipstack is about to set the clipboard to
+/// `content` and records it in history itself, so the daemon skips the capture
+/// instead of reading it back. Call before copying.
+pub fn mark_own_copy(storage: &Storage, content: &str) {
+    write_own_copy_marker(&storage.base_dir().join(OWN_COPY_FILE_NAME), content);
+}
+
+/// Save content clipstack is about to copy, as the daemon would have (moving an
+/// existing entry to the front and counting a use of it), and mark it with
+/// [`mark_own_copy`]. `sources` are the entries the content was made from; those
+/// it wasn't saved as (an expanded template, a transform, a join) count a use too.
+pub fn record_own_copy(storage: &Storage, content: &str, sources: &[String]) -> Result<()> {
+    let mut saved = None;
+    if !content.is_empty() && !storage.is_paused() {
+        saved = Some(storage.save_entry(content)?.id);
+    }
+    for id in sources.iter().filter(|&id| saved.as_ref() != Some(id)) {
+        if let Err(e) = storage.record_use(id) {
+            log::warn!("Failed to count a use of {}: {}", id, e);
+        }
+    }
+    mark_own_copy(storage, content);
+    Ok(())
+}
+
+fn read_degraded(path: &Path) -> Option<Degraded> {
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+fn write_degraded(path: &Path, degraded: &Degraded) -> Result<()> {
+    let json = serde_json::to_string(degraded)?;
+    fs::write(path, json).with_context(|| format!("Failed to write {:?}", path))
+}
+
+fn state_at(lock_path: &Path) -> DaemonState {
+    let Ok(mut file) = File::open(lock_path) else {
+        return DaemonState::NotRunning;
+    };
+    // If we can take the lock, nobody holds it (fs2 releases it on drop)
+    if file.try_lock_exclusive().is_ok() {
+        return DaemonState::NotRunning;
+    }
+    match read_pid(&mut file) {
+        Some(pid) if !process_alive(pid) => DaemonState::Stale(pid),
+        pid => DaemonState::Running(pid),
+    }
+}
+
+/// Take the daemon lock and record our pid in it. A lock whose recorded pid is
+/// gone is treated as stale: the file is replaced instead of refusing to start.
+fn acquire_lock(lock_path: &Path) -> Result<File> {
+    let open = || {
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false) // Don't wipe a running daemon's pid before locking
+            .open(lock_path)
+            .with_context(|| format!("Failed to create lock file: {:?}", lock_path))
+    };
+
+    let mut file = open()?;
+    if file.try_lock_exclusive().is_err() {
+        match read_pid(&mut file) {
+            Some(pid) if !process_alive(pid) => {
+                log::warn!("Removing stale lock left by pid {}", pid);
+                fs::remove_file(lock_path)
+                    .with_context(|| format!("Failed to remove stale lock: {:?}", lock_path))?;
+                file = open()?;
+                file.try_lock_exclusive()
+                    .map_err(|_| Error::DaemonLocked { pid: None })?;
+            }
+            pid => return Err(Error::DaemonLocked { pid }.into()),
+        }
+    }
+
+    file.set_len(0)?;
+    file.rewind()?;
+    writeln!(file, "{}", std::process::id())?;
+    Ok(file)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -160,6 +1228,474 @@ mod tests {
         assert!(!daemon.running.load(Ordering::SeqCst));
     }
 
+    #[test]
+    fn test_lock_records_pid_and_blocks_second_daemon() {
+        let dir = TempDir::new().unwrap();
+        let lock_path = dir.path().join("clipstack.lock");
+
+        let _lock = acquire_lock(&lock_path).unwrap();
+        let pid = std::process::id();
+        assert_eq!(fs::read_to_string(&lock_path).unwrap().trim(), pid.to_string());
+        assert_eq!(state_at(&lock_path), DaemonState::Running(Some(pid)));
+
+        let err = acquire_lock(&lock_path).unwrap_err();
+        assert!(err.to_string().contains(&pid.to_string()));
+    }
+
+    #[test]
+    fn test_stale_lock_is_replaced() {
+        let dir = TempDir::new().unwrap();
+        let lock_path = dir.path().join("clipstack.lock");
+
+        // Lock held by a handle whose recorded pid is not a live process
+        let stale = File::create(&lock_path).unwrap();
+        stale.try_lock_exclusive().unwrap();
+        fs::write(&lock_path, "999999999\n").unwrap();
+        assert_eq!(state_at(&lock_path), DaemonState::Stale(999_999_999));
+
+        let _lock = acquire_lock(&lock_path).unwrap();
+        assert_eq!(state_at(&lock_path), DaemonState::Running(Some(std::process::id())));
+    }
+
+    #[test]
+    fn test_unlocked_lock_file_is_not_running() {
+        let dir = TempDir::new().unwrap();
+        let lock_path = dir.path().join("clipstack.lock");
+        assert_eq!(state_at(&lock_path), DaemonState::NotRunning);
+
+        fs::write(&lock_path, "12345\n").unwrap();
+        assert_eq!(state_at(&lock_path), DaemonState::NotRunning);
+    }
+
+    #[test]
+    fn test_daemon_reloads_filters_on_config_change() {
+        let dir = TempDir::new().unwrap();
+        let config_path = dir.path().join("config.toml");
+        let mut daemon = Daemon::new_with_lock(Some(dir.path().to_path_buf()), 100, true)
+            .unwrap()
+            .with_config_path(config_path.clone());
+
+        daemon.reload_config_if_changed();
+        assert!(daemon.filters.ignore_match("secret-123").is_none());
+
+        std::fs::write(&config_path, "[filters]\nignore = ['^secret-']\n").unwrap();
+        daemon.reload_config_if_changed();
+        assert!(daemon.filters.ignore_match("secret-123").is_some());
+
+        // Invalid pattern keeps the previous filters
+        std::fs::write(&config_path, "[filters]\nignore = ['(']\n").unwrap();
+        filetime_bump(&config_path);
+        daemon.reload_config_if_changed();
+        assert!(daemon.filters.ignore_match("secret-123").is_some());
+    }
+
+    #[test]
+    fn test_daemon_reload_request_applies_daemon_settings() {
+        let dir = TempDir::new().unwrap();
+        let config_path = dir.path().join("config.toml");
+        std::fs::write(&config_path, "max_entries = 5
+
+[daemon]
+poll_interval = \"1s\"
+").unwrap();
+        let mut daemon = Daemon::new_with_lock(Some(dir.path().to_path_buf()), 100, true)
+            .unwrap()
+            .with_config_path(config_path.clone());
+
+        daemon.reload_config_if_changed();
+        assert_eq!(daemon.poll_interval, Duration::from_secs(1));
+        assert_eq!(daemon.storage.max_entries(), 5);
+
+        // Unchanged mtime: only an explicit request (SIGHUP) triggers a reload
+        daemon.poll_interval = Duration::from_millis(10);
+        daemon.reload_config_if_changed();
+        assert_eq!(daemon.poll_interval, Duration::from_millis(10));
+
+        daemon.reload_requested.store(true, Ordering::SeqCst);
+        daemon.reload_config_if_changed();
+        assert_eq!(daemon.poll_interval, Duration::from_secs(1));
+        assert!(!daemon.reload_requested.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_daemon_fixed_max_entries_ignores_config() {
+        let dir = TempDir::new().unwrap();
+        let config_path = dir.path().join("config.toml");
+        std::fs::write(&config_path, "max_entries = 5
+").unwrap();
+        let mut daemon = Daemon::new_with_lock(Some(dir.path().to_path_buf()), 100, true)
+            .unwrap()
+            .with_config_path(config_path)
+            .with_fixed_max_entries(true);
+
+        daemon.reload_config_if_changed();
+        assert_eq!(daemon.storage.max_entries(), 100);
+    }
+
+    #[test]
+    fn test_auto_clear_timing() {
+        let start = Instant::now();
+        let mut clear = AutoClear {
+            after: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        assert!(!clear.due(start), "Nothing copied yet");
+
+        clear.copied(start);
+        assert!(!clear.due(start + Duration::from_secs(59)));
+        assert!(clear.due(start + Duration::from_secs(60)));
+
+        // Clearing doesn't count as a copy, so it fires only once
+        clear.did_clear();
+        assert!(!clear.due(start + Duration::from_secs(600)));
+        assert!(clear.confirm_empty());
+        assert!(!clear.confirm_empty());
+
+        // Disabled
+        clear.after = None;
+        clear.copied(start);
+        assert!(!clear.due(start + Duration::from_secs(600)));
+    }
+
+    #[test]
+    fn test_debounce_waits_for_stable_selection() {
+        let start = Instant::now();
+        let mut debounce = Debounce::new(Duration::from_secs(1));
+        let at = |ms| start + Duration::from_millis(ms);
+
+        // Growing drag selection: each change restarts the wait
+        assert!(!debounce.settled("he", at(0)));
+        assert!(!debounce.settled("hell", at(250)));
+        assert!(!debounce.settled("hello", at(500)));
+        assert!(!debounce.settled("hello", at(1000)));
+        assert!(debounce.settled("hello", at(1500)));
+        assert!(debounce.settled("hello", at(1750)));
+
+        assert!(!debounce.settled("hello world", at(2000)));
+        assert!(debounce.settled("", at(2000)), "Empty selections pass through");
+    }
+
+    #[test]
+    fn test_zero_debounce_passes_through() {
+        let mut debounce = Debounce::new(Duration::ZERO);
+        assert!(debounce.settled("a", Instant::now()));
+        assert!(debounce.settled("b", Instant::now()));
+    }
+
+    #[test]
+    fn test_seat_selection_keeps_existing_state() {
+        let dir = TempDir::new().unwrap();
+        let daemon = Daemon::new_with_lock(Some(dir.path().to_path_buf()), 100, true).unwrap();
+        assert_eq!(daemon.seats.len(), 1);
+        assert_eq!(daemon.seats[0].seat, None, "Default seat by default");
+
+        let names = |d: &Daemon| d.seats.iter().map(|w| w.seat.clone()).collect::<Vec<_>>();
+        let mut daemon = daemon.with_seats(SeatSelection::Named(vec![
+            "seat0".to_string(),
+            "seat1".to_string(),
+        ]));
+        assert_eq!(names(&daemon), [Some("seat0".to_string()), Some("seat1".to_string())]);
+
+        daemon.seats[1].last_clipboard_hash = Some(vec![1, 2, 3]);
+        let daemon = daemon.with_seats(SeatSelection::Named(vec!["seat1".to_string()]));
+        assert_eq!(names(&daemon), [Some("seat1".to_string())]);
+        assert_eq!(daemon.seats[0].last_clipboard_hash, Some(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn test_source_label() {
+        assert_eq!(source_label(None, "clipboard"), "clipboard");
+        assert_eq!(source_label(Some("seat1"), "primary"), "seat1/primary");
+    }
+
+    #[test]
+    fn test_save_health_backoff() {
+        let start = Instant::now();
+        let mut health = SaveHealth::default();
+
+        assert!(!health.failed("disk full", start, 0));
+        assert!(!health.failed("disk full", start, 0));
+        assert!(health.can_write(start), "Isolated failures don't back off");
+
+        assert!(health.failed("disk full", start, 1000));
+        assert!(!health.can_write(start));
+        assert!(health.can_write(start + BACKOFF_MIN));
+
+        // Each further failure doubles the wait, up to the cap
+        assert!(health.failed("disk full", start, 2000));
+        assert!(!health.can_write(start + BACKOFF_MIN));
+        assert!(health.can_write(start + BACKOFF_MIN * 2));
+        for _ in 0..20 {
+            health.failed("disk full", start, 3000);
+        }
+        assert!(health.can_write(start + BACKOFF_MAX));
+
+        let degraded = health.degraded.clone().unwrap();
+        assert_eq!(degraded.since, 1000, "Degraded period starts at the first backoff");
+        assert_eq!(degraded.failures, 24);
+
+        assert_eq!(health.succeeded(), Some(degraded));
+        assert!(health.can_write(start));
+        assert_eq!(health.succeeded(), None);
+    }
+
+    #[test]
+    fn test_degraded_state_is_published_and_cleared() {
+        let dir = TempDir::new().unwrap();
+        let mut daemon = Daemon::new_with_lock(Some(dir.path().to_path_buf()), 100, true).unwrap();
+        let health_file = dir.path().join("clipstack.health");
+        let error = anyhow::anyhow!("No space left on device");
+
+        for _ in 0..DEGRADED_AFTER {
+            daemon.save_failed("clipboard", &error);
+        }
+        let degraded = read_degraded(&health_file).expect("Degraded state should be written");
+        assert_eq!(degraded.failures, DEGRADED_AFTER);
+        assert_eq!(degraded.last_error, "No space left on device");
+
+        daemon.save_succeeded();
+        assert!(!health_file.exists(), "Recovery clears the degraded state");
+    }
+
+    /// Ensure the mtime differs from the previous write on coarse filesystems
+    fn filetime_bump(path: &std::path::Path) {
+        let file = File::options().append(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(1)).unwrap();
+    }
+
+    fn mock_daemon(dir: &TempDir, config: &str) -> (Daemon, Arc<clipboard::MockClipboard>) {
+        let config_path = dir.path().join("config.toml");
+        std::fs::write(&config_path, config).unwrap();
+        let mock = Arc::new(clipboard::MockClipboard::in_memory());
+        let daemon = Daemon::new_with_lock(Some(dir.path().join("history")), 100, true)
+            .unwrap()
+            .with_config_path(config_path)
+            .with_clipboard(mock.clone());
+        (daemon, mock)
+    }
+
+    fn history(daemon: &Daemon) -> Vec<String> {
+        let index = daemon.storage.load_index().unwrap();
+        index.entries.iter().map(|e| daemon.storage.load_content(&e.id).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_mock_pipeline_captures_clipboard_and_primary() {
+        let dir = TempDir::new().unwrap();
+        let (mut daemon, mock) = mock_daemon(&dir, "[daemon]\nprimary_debounce = \"0s\"\n");
+
+        daemon.poll(false);
+        assert!(history(&daemon).is_empty(), "Empty clipboard is never captured");
+
+        mock.copy("first").unwrap();
+        daemon.poll(false);
+        daemon.poll(false);
+        mock.copy_to(None, true, "selected").unwrap();
+        daemon.poll(false);
+
+        assert_eq!(history(&daemon), ["selected", "first"]);
+    }
+
+    #[test]
+    fn test_mock_pipeline_skips_while_paused() {
+        let dir = TempDir::new().unwrap();
+        let (mut daemon, mock) = mock_daemon(&dir, "");
+
+        daemon.storage.pause(None).unwrap();
+        mock.copy("secret").unwrap();
+        assert!(daemon.poll(false));
+        assert!(history(&daemon).is_empty());
+
+        // Resuming doesn't capture what was copied during the pause
+        daemon.storage.resume().unwrap();
+        assert!(!daemon.poll(true));
+        assert!(history(&daemon).is_empty());
+
+        mock.copy("public").unwrap();
+        daemon.poll(false);
+        assert_eq!(history(&daemon), ["public"]);
+    }
+
+    #[test]
+    fn test_own_copies_are_not_captured_again() {
+        let dir = TempDir::new().unwrap();
+        let (mut daemon, mock) = mock_daemon(&dir, "");
+        daemon.storage.save_entry("from picker").unwrap();
+        daemon.storage.save_entry("newer").unwrap();
+
+        // A stale marker for other content changes nothing
+        write_own_copy_marker(&daemon.own_copy_path, "something else");
+        mock.copy("first").unwrap();
+        daemon.poll(false);
+        assert_eq!(history(&daemon), ["first", "newer", "from picker"]);
+
+        write_own_copy_marker(&daemon.own_copy_path, "from picker");
+        mock.copy("from picker").unwrap();
+        daemon.poll(false);
+        assert_eq!(history(&daemon), ["first", "newer", "from picker"]);
+        assert!(!daemon.own_copy_path.exists(), "Marker is consumed");
+        assert_eq!(daemon.metrics.duplicates, 0);
+
+        // The same content copied again by someone else is captured as usual
+        mock.copy("other").unwrap();
+        daemon.poll(false);
+        mock.copy("from picker").unwrap();
+        daemon.poll(false);
+        assert_eq!(history(&daemon)[0], "from picker");
+    }
+
+    #[test]
+    fn test_own_copy_counts_uses_of_its_sources() {
+        let dir = TempDir::new().unwrap();
+        let storage = Storage::with_defaults(dir.path().to_path_buf()).unwrap();
+        let template = storage.save_entry("Hi {date}").unwrap();
+        let uses = |id: &str| storage.get_entry(id).unwrap().use_count;
+        let sources = [template.id.clone()];
+
+        // Pasted as is: the copy is the entry itself, counted once
+        record_own_copy(&storage, "Hi {date}", &sources).unwrap();
+        assert_eq!(uses(&template.id), 1);
+
+        // Expanded: both the new entry and its template were used
+        record_own_copy(&storage, "Hi 2024-05-01", &sources).unwrap();
+        assert_eq!(uses(&template.id), 2);
+        let expanded = storage.load_index().unwrap().entries[0].clone();
+        assert_eq!(expanded.use_count, 0);
+    }
+
+    #[test]
+    fn test_other_formats_are_saved_with_the_entry() {
+        let dir = TempDir::new().unwrap();
+        let (mut daemon, mock) = mock_daemon(&dir, "[capture]\nformats = ['text/html']\n");
+
+        mock.copy("bold").unwrap();
+        mock.offer_type(None, false, "text/html", b"<b>bold</b>").unwrap();
+        mock.offer_type(None, false, "image/png", b"\x89PNG").unwrap();
+        daemon.poll(false);
+        let entry = daemon.storage.load_index().unwrap().entries[0].clone();
+        let mimes: Vec<&str> = entry.representations.iter().map(|r| r.mime.as_str()).collect();
+        assert_eq!(mimes, ["text/html"], "Only the configured formats are kept");
+        let html = daemon.storage.load_representation(&entry, "text/html").unwrap();
+        assert_eq!(html, b"<b>bold</b>");
+
+        // Copied again as plain text only, the entry keeps just that
+        mock.copy("other").unwrap();
+        daemon.poll(false);
+        mock.copy("bold").unwrap();
+        daemon.poll(false);
+        let entry = daemon.storage.load_index().unwrap().entries[0].clone();
+        assert!(entry.representations.is_empty());
+    }
+
+    #[test]
+    fn test_allowlist_skips_everything_else() {
+        let dir = TempDir::new().unwrap();
+        let (mut daemon, mock) = mock_daemon(&dir, "[filters]\nallow = ['^https://']\n");
+
+        for content in ["https://example.com", "hunter2", "https://docs.rs"] {
+            mock.copy(content).unwrap();
+            daemon.poll(false);
+        }
+        assert_eq!(history(&daemon), ["https://docs.rs", "https://example.com"]);
+        assert_eq!(daemon.storage.skip_counts().not_allowed, 1);
+    }
+
+    #[test]
+    fn test_nothing_recorded_outside_schedule() {
+        use chrono::Datelike;
+        let dir = TempDir::new().unwrap();
+        // Every day but today and tomorrow, so passing midnight can't flip it
+        let tomorrow = chrono::Local::now().weekday().succ();
+        let days: Vec<String> = std::iter::successors(Some(tomorrow.succ()), |d| Some(d.succ()))
+            .take(5)
+            .map(|d| d.to_string().to_lowercase())
+            .collect();
+        let config = format!("[daemon]\nschedule = ['{} 00:00-24:00']\n", days.join(","));
+        let (mut daemon, mock) = mock_daemon(&dir, &config);
+
+        mock.copy("after hours").unwrap();
+        daemon.poll(false);
+        assert!(history(&daemon).is_empty());
+    }
+
+    #[test]
+    fn test_metrics_count_captures_and_publish_on_dump() {
+        let dir = TempDir::new().unwrap();
+        let (mut daemon, mock) = mock_daemon(&dir, "[filters]\nmin_size = 2\n");
+
+        for content in ["one", "two", "one", "x"] {
+            mock.copy(content).unwrap();
+            daemon.poll(false);
+        }
+        assert_eq!(daemon.metrics.polls, 4);
+        assert_eq!(daemon.metrics.saved, 2);
+        assert_eq!(daemon.metrics.duplicates, 1);
+        assert_eq!(daemon.metrics.skipped, 1);
+
+        daemon.dump_handle().store(true, Ordering::SeqCst);
+        daemon.poll(false);
+        let published = metrics::read(&daemon.metrics_path).unwrap();
+        assert_eq!(published.polls, 5);
+        assert!(!daemon.dump_requested.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_duplicate_window_coalesces_select_then_copy() {
+        let dir = TempDir::new().unwrap();
+        let config = "[daemon]\nprimary_debounce = \"0s\"\nduplicate_window = \"1h\"\n";
+        let (mut daemon, mock) = mock_daemon(&dir, config);
+
+        mock.copy_to(None, true, "selected").unwrap();
+        daemon.poll(false);
+        mock.copy("selected").unwrap();
+        daemon.poll(false);
+        assert_eq!(daemon.metrics.saved, 1);
+        assert_eq!(daemon.metrics.duplicates, 1);
+
+        // Outside the window the copy is saved again, moving the entry to the front
+        daemon.daemon_config.duplicate_window = Duration::ZERO;
+        mock.copy_to(None, true, "other").unwrap();
+        daemon.poll(false);
+        mock.copy_to(None, true, "selected").unwrap();
+        daemon.poll(false);
+        assert_eq!(history(&daemon), ["selected", "other"]);
+        assert_eq!(daemon.metrics.duplicates, 2);
+    }
+
+    #[test]
+    fn test_mirror_both_ways_without_echo() {
+        let dir = TempDir::new().unwrap();
+        let config = "[daemon]\nprimary_debounce = \"0s\"\nmirror = \"both\"\n";
+        let (mut daemon, mock) = mock_daemon(&dir, config);
+
+        mock.copy("copied").unwrap();
+        daemon.poll(false);
+        assert_eq!(mock.paste_from(None, true).unwrap(), "copied");
+
+        mock.copy_to(None, true, "selected").unwrap();
+        daemon.poll(false);
+        daemon.poll(false);
+        assert_eq!(mock.paste().unwrap(), "selected");
+
+        // Each selection is saved once, not again when it arrives mirrored
+        assert_eq!(history(&daemon), ["selected", "copied"]);
+    }
+
+    #[test]
+    fn test_mirror_follows_direction() {
+        let dir = TempDir::new().unwrap();
+        let config = "[daemon]\nprimary_debounce = \"0s\"\nmirror = \"to-clipboard\"\n";
+        let (mut daemon, mock) = mock_daemon(&dir, config);
+
+        mock.copy("copied").unwrap();
+        daemon.poll(false);
+        assert_eq!(mock.paste_from(None, true).unwrap(), "");
+
+        mock.copy_to(None, true, "selected").unwrap();
+        daemon.poll(false);
+        assert_eq!(mock.paste().unwrap(), "selected");
+    }
+
     #[test]
     fn test_daemon_stop_handle() {
         let dir = TempDir::new().unwrap();
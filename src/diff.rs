@@ -0,0 +1,239 @@
+use unicode_width::UnicodeWidthChar;
+
+/// Largest table of line pairs compared in full; past it only the lines
+/// around the common start and end are matched
+const MAX_CELLS: usize = 4_000_000;
+
+/// Unchanged lines shown around each change; longer runs are folded
+pub const CONTEXT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Same,
+    Removed,
+    Added,
+}
+
+/// The lines of `old` and `new` in order, each kept, removed or added, with as
+/// few changes as possible. In a block of changes the removals come first.
+pub fn lines<'a>(old: &'a str, new: &'a str) -> Vec<(Change, &'a str)> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (ma, mb) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let mut out: Vec<(Change, &str)> = a[..prefix].iter().map(|l| (Change::Same, *l)).collect();
+    let (mut i, mut j) = (0, 0);
+    if ma.len().saturating_mul(mb.len()) <= MAX_CELLS {
+        // common[i * w + j]: longest common subsequence of ma[i..] and mb[j..]
+        let w = mb.len() + 1;
+        let mut common = vec![0u32; (ma.len() + 1) * w];
+        for i in (0..ma.len()).rev() {
+            for j in (0..mb.len()).rev() {
+                common[i * w + j] = if ma[i] == mb[j] {
+                    common[(i + 1) * w + j + 1] + 1
+                } else {
+                    common[(i + 1) * w + j].max(common[i * w + j + 1])
+                };
+            }
+        }
+        while i < ma.len() && j < mb.len() {
+            if ma[i] == mb[j] {
+                out.push((Change::Same, ma[i]));
+                (i, j) = (i + 1, j + 1);
+            } else if common[(i + 1) * w + j] >= common[i * w + j + 1] {
+                out.push((Change::Removed, ma[i]));
+                i += 1;
+            } else {
+                out.push((Change::Added, mb[j]));
+                j += 1;
+            }
+        }
+    }
+    out.extend(ma[i..].iter().map(|l| (Change::Removed, *l)));
+    out.extend(mb[j..].iter().map(|l| (Change::Added, *l)));
+    out.extend(a[a.len() - suffix..].iter().map(|l| (Change::Same, *l)));
+    out
+}
+
+/// Lines added and removed
+pub fn stats(diff: &[(Change, &str)]) -> (usize, usize) {
+    let count = |change| diff.iter().filter(|(c, _)| *c == change).count();
+    (count(Change::Added), count(Change::Removed))
+}
+
+/// One line per diff line, prefixed ' ', '-' or '+'
+pub fn unified(diff: &[(Change, &str)]) -> Vec<String> {
+    let rows = diff
+        .iter()
+        .map(|&(change, line)| {
+            let mark = match change {
+                Change::Same => ' ',
+                Change::Removed => '-',
+                Change::Added => '+',
+            };
+            (change != Change::Same, format!("{}{}", mark, untab(line)))
+        })
+        .collect();
+    fold(rows)
+}
+
+/// The old text on the left and the new on the right, `width` columns in
+/// all. Removed and added lines of one block share rows; each row starts with
+/// ' ', '-', '+' or '~' (both sides changed).
+pub fn side_by_side(diff: &[(Change, &str)], width: usize) -> Vec<String> {
+    let side = width.saturating_sub(2) / 2;
+    let mut rows = Vec::new();
+    let mut pos = 0;
+    while pos < diff.len() {
+        if diff[pos].0 == Change::Same {
+            let line = untab(diff[pos].1);
+            rows.push((false, format!(" {}│{}", pad(&line, side), line)));
+            pos += 1;
+            continue;
+        }
+        let block = diff[pos..].iter().take_while(|(c, _)| *c != Change::Same);
+        let removed: Vec<&str> =
+            block.clone().filter(|(c, _)| *c == Change::Removed).map(|(_, l)| *l).collect();
+        let added: Vec<&str> =
+            block.filter(|(c, _)| *c == Change::Added).map(|(_, l)| *l).collect();
+        for row in 0..removed.len().max(added.len()) {
+            let (left, right) = (removed.get(row), added.get(row));
+            let mark = match (left, right) {
+                (Some(_), Some(_)) => '~',
+                (Some(_), None) => '-',
+                _ => '+',
+            };
+            let left = left.map(|l| untab(l)).unwrap_or_default();
+            let right = right.map(|l| untab(l)).unwrap_or_default();
+            rows.push((true, format!("{}{}│{}", mark, pad(&left, side), right)));
+        }
+        pos += removed.len() + added.len();
+    }
+    fold(rows)
+}
+
+/// Keep changed rows and `CONTEXT` rows around them, replacing longer runs of
+/// unchanged ones with a line counting them. Nothing is folded without changes.
+fn fold(rows: Vec<(bool, String)>) -> Vec<String> {
+    if !rows.iter().any(|(changed, _)| *changed) {
+        return rows.into_iter().map(|(_, row)| row).collect();
+    }
+    // Distance to the nearest change before and after each row
+    let mut near = vec![usize::MAX; rows.len()];
+    let mut last = None;
+    for (i, (changed, _)) in rows.iter().enumerate() {
+        if *changed {
+            last = Some(i);
+        }
+        near[i] = last.map_or(usize::MAX, |l| i - l);
+    }
+    last = None;
+    for (i, (changed, _)) in rows.iter().enumerate().rev() {
+        if *changed {
+            last = Some(i);
+        }
+        near[i] = near[i].min(last.map_or(usize::MAX, |l| l - i));
+    }
+
+    let folded = |skipped: usize| match skipped {
+        1 => "⋯ 1 unchanged line".to_string(),
+        n => format!("⋯ {} unchanged lines", n),
+    };
+    let mut out = Vec::new();
+    let mut skipped = 0;
+    for ((_, row), distance) in rows.into_iter().zip(near) {
+        if distance <= CONTEXT {
+            if skipped > 0 {
+                out.push(folded(skipped));
+                skipped = 0;
+            }
+            out.push(row);
+        } else {
+            skipped += 1;
+        }
+    }
+    if skipped > 0 {
+        out.push(folded(skipped));
+    }
+    out
+}
+
+fn untab(line: &str) -> String {
+    line.replace('\t', "    ")
+}
+
+/// `text` cut or padded to exactly `width` display columns
+fn pad(text: &str, width: usize) -> String {
+    let mut out = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let columns = c.width().unwrap_or(0);
+        if used + columns > width {
+            break;
+        }
+        out.push(c);
+        used += columns;
+    }
+    out.extend(std::iter::repeat_n(' ', width - used));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lines_finds_fewest_changes() {
+        let diff = lines("a\nb\nc\nd", "a\nc\nd\ne");
+        assert_eq!(
+            diff,
+            [
+                (Change::Same, "a"),
+                (Change::Removed, "b"),
+                (Change::Same, "c"),
+                (Change::Same, "d"),
+                (Change::Added, "e"),
+            ]
+        );
+        assert_eq!(stats(&diff), (1, 1));
+        assert_eq!(unified(&diff), [" a", "-b", " c", " d", "+e"]);
+        assert!(lines("same", "same").iter().all(|(c, _)| *c == Change::Same));
+        assert_eq!(lines("", "new"), [(Change::Added, "new")]);
+    }
+
+    #[test]
+    fn test_side_by_side_pairs_changed_lines() {
+        let diff = lines("port = 80\nhost = a", "port = 8080\nhost = a\nextra");
+        let rows = side_by_side(&diff, 24);
+        assert_eq!(
+            rows,
+            [
+                "~port = 80  │port = 8080",
+                " host = a   │host = a",
+                "+           │extra",
+            ]
+        );
+        // Wide characters count two columns
+        assert_eq!(pad("日本語", 5), "日本 ");
+    }
+
+    #[test]
+    fn test_long_unchanged_runs_fold() {
+        let old: Vec<String> = (0..20).map(|i| i.to_string()).collect();
+        let mut new = old.clone();
+        new[10] = "ten".to_string();
+        let rows = unified(&lines(&old.join("\n"), &new.join("\n")));
+        assert_eq!(rows[0], "⋯ 7 unchanged lines");
+        assert_eq!(rows[1..4], [" 7", " 8", " 9"]);
+        assert_eq!(rows[4..6], ["-10", "+ten"]);
+        assert_eq!(rows.last().unwrap(), "⋯ 6 unchanged lines");
+        assert_eq!(rows.len(), 1 + 3 + 2 + 3 + 1);
+    }
+}
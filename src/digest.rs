@@ -0,0 +1,212 @@
+use crate::kind::{self, EntryKind};
+use crate::storage::ClipEntry;
+use crate::util;
+use chrono::{Local, NaiveDate, TimeZone, Timelike};
+use serde::Serialize;
+use std::cmp::Reverse;
+
+/// Entries at least this large are listed as notable
+pub const LARGE_ENTRY_SIZE: usize = 4 * 1024;
+
+/// How many large entries and source apps are listed
+const TOP_N: usize = 5;
+
+/// Longest preview printed per entry
+const PREVIEW_CHARS: usize = 60;
+
+/// One day of clipboard activity (`clipstack digest`)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Digest {
+    pub date: NaiveDate,
+    /// Entries last copied that day
+    pub copied: usize,
+    /// Of those, entries first captured that day
+    pub new: usize,
+    pub kinds: Vec<KindCount>,
+    /// Apps copied from most, most first
+    pub apps: Vec<AppCount>,
+    /// Local hour (0-23) with the most copies
+    pub busiest_hour: Option<u32>,
+    /// Largest entries of at least `LARGE_ENTRY_SIZE`, largest first
+    pub large: Vec<DigestEntry>,
+    /// Pinned entries first captured that day
+    pub pinned: Vec<DigestEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KindCount {
+    pub kind: EntryKind,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AppCount {
+    pub app: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DigestEntry {
+    pub id: String,
+    pub size: usize,
+    pub preview: String,
+}
+
+/// Local midnight to midnight of `date`, in unix ms
+pub fn day_bounds(date: NaiveDate) -> (i64, i64) {
+    let start = |date: NaiveDate| {
+        let midnight = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+        Local
+            .from_local_datetime(&midnight)
+            .earliest()
+            .map_or_else(|| midnight.and_utc().timestamp_millis(), |t| t.timestamp_millis())
+    };
+    (start(date), start(date.succ_opt().unwrap_or(date)))
+}
+
+/// Summarize `date` from the history. Previews that look like secrets are
+/// masked when `mask_secrets` is set.
+pub fn digest(entries: &[ClipEntry], date: NaiveDate, mask_secrets: bool) -> Digest {
+    let (start, end) = day_bounds(date);
+    let in_day = |ms: i64| (start..end).contains(&ms);
+    let first_captured_in_day = |e: &ClipEntry| e.id.parse().is_ok_and(in_day);
+    let copied: Vec<&ClipEntry> = entries.iter().filter(|e| in_day(e.timestamp)).collect();
+    let summary = |e: &ClipEntry| DigestEntry {
+        id: e.id.clone(),
+        size: e.size,
+        preview: kind::masked(&e.preview, mask_secrets).chars().take(PREVIEW_CHARS).collect(),
+    };
+
+    let kinds = EntryKind::ALL
+        .iter()
+        .map(|&kind| KindCount {
+            kind,
+            count: copied.iter().filter(|e| e.kind == kind).count(),
+        })
+        .filter(|k| k.count > 0)
+        .collect();
+
+    let mut apps: Vec<AppCount> = Vec::new();
+    for app in copied.iter().filter_map(|e| e.source_app.as_deref()) {
+        match apps.iter_mut().find(|a| a.app == app) {
+            Some(a) => a.count += 1,
+            None => apps.push(AppCount {
+                app: app.to_string(),
+                count: 1,
+            }),
+        }
+    }
+    apps.sort_by_key(|a| Reverse(a.count));
+    apps.truncate(TOP_N);
+
+    let mut hours = [0usize; 24];
+    for e in &copied {
+        if let Some(t) = Local.timestamp_millis_opt(e.timestamp).single() {
+            hours[t.hour() as usize] += 1;
+        }
+    }
+    // Earliest hour wins ties
+    let busiest_hour =
+        (0..24u32).rev().max_by_key(|&h| hours[h as usize]).filter(|_| !copied.is_empty());
+
+    let mut large: Vec<&ClipEntry> =
+        copied.iter().copied().filter(|e| e.size >= LARGE_ENTRY_SIZE).collect();
+    large.sort_by_key(|e| Reverse(e.size));
+
+    Digest {
+        date,
+        copied: copied.len(),
+        new: copied.iter().filter(|e| first_captured_in_day(e)).count(),
+        kinds,
+        apps,
+        busiest_hour,
+        large: large.into_iter().take(TOP_N).map(summary).collect(),
+        pinned: entries
+            .iter()
+            .filter(|e| e.pinned && first_captured_in_day(e))
+            .map(summary)
+            .collect(),
+    }
+}
+
+/// Print the digest as plain text, suitable for a journal or an email body
+pub fn print(d: &Digest) {
+    println!("Clipboard digest for {}", d.date.format("%A, %Y-%m-%d"));
+    println!();
+    if d.copied == 0 {
+        println!("Nothing copied.");
+        return;
+    }
+    println!("Copied:      {} entries ({} new)", d.copied, d.new);
+    let kinds: Vec<String> =
+        d.kinds.iter().map(|k| format!("{} {}", k.kind.name(), k.count)).collect();
+    println!("  Kinds:     {}", kinds.join(", "));
+    if !d.apps.is_empty() {
+        let apps: Vec<String> = d.apps.iter().map(|a| format!("{} {}", a.app, a.count)).collect();
+        println!("  Apps:      {}", apps.join(", "));
+    }
+    if let Some(hour) = d.busiest_hour {
+        println!("  Busiest:   {:02}:00-{:02}:00", hour, (hour + 1) % 24);
+    }
+
+    for (title, entries) in [("Large entries", &d.large), ("New pinned", &d.pinned)] {
+        if entries.is_empty() {
+            continue;
+        }
+        println!();
+        println!("{}:", title);
+        for e in entries {
+            println!("  {:>8}  {}  {}", util::format_size(e.size), e.id, e.preview);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::new_entry;
+    use crate::storage::EntryMeta;
+
+    fn at(date: NaiveDate, hour: u32) -> i64 {
+        let t = date.and_hms_opt(hour, 30, 0).unwrap();
+        Local.from_local_datetime(&t).earliest().unwrap().timestamp_millis()
+    }
+
+    #[test]
+    fn test_digest_counts_one_day() {
+        let day = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let before = day.pred_opt().unwrap();
+        let entry = |first: i64, last: i64, content: &str, app: Option<&str>| {
+            let meta = EntryMeta {
+                source_app: app.map(str::to_string),
+                ..Default::default()
+            };
+            let mut e = new_entry(first, content, String::new(), meta);
+            e.timestamp = last;
+            e
+        };
+        let big = "x".repeat(LARGE_ENTRY_SIZE);
+        let mut pinned = entry(at(day, 9), at(day, 9), "ssh prod", None);
+        pinned.pinned = true;
+        let entries = vec![
+            entry(at(day, 14), at(day, 14), "https://example.com", Some("firefox")),
+            entry(at(day, 14), at(day, 14), &big, Some("kitty")),
+            entry(at(before, 10), at(day, 11), "recopied", Some("kitty")),
+            pinned,
+            entry(at(before, 10), at(before, 10), "yesterday", None),
+        ];
+
+        let d = digest(&entries, day, false);
+        assert_eq!((d.copied, d.new), (4, 3));
+        let kinds: Vec<(EntryKind, usize)> = d.kinds.iter().map(|k| (k.kind, k.count)).collect();
+        assert_eq!(kinds, [(EntryKind::Text, 3), (EntryKind::Url, 1)]);
+        assert_eq!(d.apps[0], AppCount { app: "kitty".into(), count: 2 });
+        assert_eq!(d.busiest_hour, Some(14));
+        assert_eq!(d.large.len(), 1);
+        assert_eq!(d.large[0].preview.chars().count(), PREVIEW_CHARS);
+        assert_eq!(d.pinned[0].preview, "ssh prod");
+
+        let empty = digest(&entries, day.succ_opt().unwrap(), false);
+        assert_eq!((empty.copied, empty.busiest_hour), (0, None));
+    }
+}
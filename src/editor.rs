@@ -0,0 +1,58 @@
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// The user's editor: $VISUAL, then $EDITOR, then vi
+fn editor() -> String {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|e| !e.trim().is_empty()))
+        .unwrap_or_else(|| "vi".to_string())
+}
+
+/// Let the user edit `text` in $VISUAL or $EDITOR (vi if neither is set) and
+/// return the result. The file is readable by the owner only, kept in the
+/// runtime dir when there is one, and removed afterwards.
+pub fn edit(text: &str) -> Result<String> {
+    edit_with(&editor(), text)
+}
+
+fn edit_with(editor: &str, text: &str) -> Result<String> {
+    let dir = std::env::var_os("XDG_RUNTIME_DIR").map_or_else(std::env::temp_dir, PathBuf::from);
+    let path = dir.join(format!("clipstack-edit-{}.txt", std::process::id()));
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&path)
+        .and_then(|mut file| file.write_all(text.as_bytes()))
+        .with_context(|| format!("Failed to create {:?}", path))?;
+
+    // Through the shell, so the editor may come with arguments ("code --wait")
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(&path)
+        .status();
+    let edited = std::fs::read_to_string(&path);
+    let _ = std::fs::remove_file(&path);
+
+    let status = status.with_context(|| format!("Failed to run {}", editor))?;
+    anyhow::ensure!(status.success(), "{} exited with {}; entry left unchanged", editor, status);
+    Ok(edited?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_with_command() {
+        assert_eq!(edit_with("sed -i s/world/there/", "hello world").unwrap(), "hello there");
+        let err = edit_with("false", "hello").unwrap_err();
+        assert!(err.to_string().contains("left unchanged"));
+    }
+}
@@ -0,0 +1,91 @@
+use std::process::ExitCode;
+
+/// Failures callers may want to tell apart. They travel inside `anyhow::Error`
+/// like any other error; `kind_of` finds them again anywhere in the chain.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// No entry with this id
+    #[error("Entry not found: {0}")]
+    EntryNotFound(String),
+
+    /// The index or history database can't be parsed
+    #[error("{what} corrupted")]
+    StorageCorrupt {
+        what: &'static str,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    /// A wl-clipboard tool couldn't be started
+    #[error("Failed to run {program}.\n{hint}")]
+    ClipboardUnavailable {
+        program: &'static str,
+        hint: &'static str,
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// The index was written by a newer clipstack
+    #[error("History format v{found} is newer than supported (v{supported}); upgrade clipstack")]
+    IndexTooNew { found: u32, supported: u32 },
+
+    /// Another daemon holds the lock
+    #[error("Daemon already running ({})", describe_holder(*pid))]
+    DaemonLocked { pid: Option<u32> },
+}
+
+impl Error {
+    /// Process exit status for the CLI; 1 stays the code for everything else
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::EntryNotFound(_) => 3,
+            Error::StorageCorrupt { .. } => 4,
+            Error::ClipboardUnavailable { .. } => 5,
+            Error::DaemonLocked { .. } => 6,
+            Error::IndexTooNew { .. } => 7,
+        }
+    }
+}
+
+fn describe_holder(pid: Option<u32>) -> String {
+    pid.map_or("lock file is held".into(), |pid| format!("pid {}", pid))
+}
+
+/// The first `Error` in `err`'s chain, if any
+pub fn kind_of(err: &anyhow::Error) -> Option<&Error> {
+    err.chain().find_map(|cause| cause.downcast_ref::<Error>())
+}
+
+/// Exit status for a failed command
+pub fn exit_code(err: &anyhow::Error) -> ExitCode {
+    ExitCode::from(kind_of(err).map_or(1, Error::exit_code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_kind_found_through_context() {
+        let err = Err::<(), _>(Error::EntryNotFound("42".into()))
+            .context("Failed to pin")
+            .unwrap_err();
+        assert!(matches!(kind_of(&err), Some(Error::EntryNotFound(id)) if id == "42"));
+        assert_eq!(exit_code(&err), ExitCode::from(3));
+
+        let other = anyhow::anyhow!("something else");
+        assert!(kind_of(&other).is_none());
+        assert_eq!(exit_code(&other), ExitCode::from(1));
+    }
+
+    #[test]
+    fn test_messages() {
+        let locked = Error::DaemonLocked { pid: Some(7) };
+        assert_eq!(locked.to_string(), "Daemon already running (pid 7)");
+        assert_eq!(
+            Error::DaemonLocked { pid: None }.to_string(),
+            "Daemon already running (lock file is held)"
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use crate::crypto::{self, Cipher};
+use crate::storage::ClipEntry;
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+/// Identifies export files, so importing some other JSON fails clearly
+const FORMAT: &str = "clipstack-export";
+
+/// Bumped when the layout changes incompatibly
+const VERSION: u32 = 1;
+
+/// First bytes of a gzip stream
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// An entry with its content, as written by `clipstack export`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedEntry {
+    #[serde(flatten)]
+    pub entry: ClipEntry,
+    pub content: String,
+    /// Content of each of the entry's representations as stored, in order
+    /// (base64 for binary ones)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub representation_content: Vec<String>,
+}
+
+/// The whole file: the history newest first
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Export {
+    pub format: String,
+    pub version: u32,
+    /// Milliseconds since the epoch
+    pub exported_at: i64,
+    pub entries: Vec<ExportedEntry>,
+}
+
+impl Export {
+    pub fn new(entries: Vec<ExportedEntry>) -> Self {
+        Self {
+            format: FORMAT.to_string(),
+            version: VERSION,
+            exported_at: crate::util::now_millis(),
+            entries,
+        }
+    }
+}
+
+/// Write `export` to `path` (gzipped if it ends in `.gz`, readable by the owner
+/// only), or as plain JSON to stdout
+pub fn write(export: &Export, path: Option<&Path>) -> Result<()> {
+    let Some(path) = path else {
+        let mut stdout = std::io::stdout().lock();
+        serde_json::to_writer(&mut stdout, export)?;
+        return Ok(writeln!(stdout)?);
+    };
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+        .with_context(|| format!("Failed to create {:?}", path))?;
+    if path.extension().is_some_and(|ext| ext == "gz") {
+        let mut gz = GzEncoder::new(file, Compression::default());
+        serde_json::to_writer(&mut gz, export)?;
+        gz.finish()?.sync_all()?;
+    } else {
+        serde_json::to_writer(&file, export)?;
+        file.sync_all()?;
+    }
+    Ok(())
+}
+
+/// `export` as gzipped JSON
+pub fn to_gzip(export: &Export) -> Result<Vec<u8>> {
+    let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+    serde_json::to_writer(&mut gz, export)?;
+    Ok(gz.finish()?)
+}
+
+/// Read an export file, plain or gzipped (use - for stdin). Encrypted backups
+/// need the storage `cipher`.
+pub fn read(path: &Path, cipher: Option<&Cipher>) -> Result<Export> {
+    let mut data = Vec::new();
+    if path == Path::new("-") {
+        std::io::stdin().read_to_end(&mut data)?;
+    } else {
+        data = fs::read(path).with_context(|| format!("Failed to read {:?}", path))?;
+    }
+    if crypto::is_sealed(&data) {
+        let Some(cipher) = cipher else {
+            anyhow::bail!("{:?} is encrypted; set encrypt = true under [storage] to read it", path);
+        };
+        data = cipher.open(&data).with_context(|| format!("Cannot import {:?}", path))?;
+    }
+    if data.starts_with(GZIP_MAGIC) {
+        let mut json = Vec::new();
+        GzDecoder::new(data.as_slice())
+            .read_to_end(&mut json)
+            .with_context(|| format!("{:?} is not a valid gzip file", path))?;
+        data = json;
+    }
+    parse(&data).with_context(|| format!("Cannot import {:?}", path))
+}
+
+fn parse(data: &[u8]) -> Result<Export> {
+    let export: Export = serde_json::from_slice(data).context("Not a clipstack export")?;
+    anyhow::ensure!(export.format == FORMAT, "Not a clipstack export");
+    anyhow::ensure!(
+        export.version <= VERSION,
+        "Export version {} is newer than this clipstack supports ({})",
+        export.version,
+        VERSION
+    );
+    Ok(export)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::new_entry;
+    use tempfile::TempDir;
+
+    fn sample() -> Export {
+        let mut entry = new_entry(1, "hello", "blake3:x".into(), Default::default());
+        entry.pinned = true;
+        entry.tags = vec!["work".into()];
+        Export::new(vec![ExportedEntry {
+            entry,
+            content: "hello".into(),
+            representation_content: Vec::new(),
+        }])
+    }
+
+    #[test]
+    fn test_round_trip_plain_and_gzip() {
+        let dir = TempDir::new().unwrap();
+        for name in ["history.json", "history.json.gz"] {
+            let path = dir.path().join(name);
+            write(&sample(), Some(&path)).unwrap();
+            let gzipped = fs::read(&path).unwrap().starts_with(GZIP_MAGIC);
+            assert_eq!(gzipped, name.ends_with(".gz"));
+
+            let export = read(&path, None).unwrap();
+            assert_eq!(export.entries.len(), 1);
+            let item = &export.entries[0];
+            assert_eq!(item.content, "hello");
+            assert!(item.entry.pinned);
+            assert_eq!(item.entry.tags, ["work"]);
+        }
+    }
+
+    #[test]
+    fn test_rejects_other_files() {
+        assert!(parse(br#"{"entries": []}"#).is_err());
+        let mut export = sample();
+        export.version = VERSION + 1;
+        assert!(parse(&serde_json::to_vec(&export).unwrap()).is_err());
+        export.format = "something".into();
+        export.version = VERSION;
+        assert!(parse(&serde_json::to_vec(&export).unwrap()).is_err());
+    }
+}
@@ -0,0 +1,159 @@
+use serde_json::Value;
+use std::process::Command;
+
+/// The focused toplevel window as reported by the compositor
+#[derive(Debug, Clone, PartialEq)]
+pub struct FocusedWindow {
+    pub app_id: String,
+    pub title: Option<String>,
+}
+
+/// Query the compositor for the focused window.
+///
+/// Wayland's data-control protocol doesn't say who owns a selection, so the
+/// window focused when the daemon notices a change is the best available
+/// proxy. Supports Hyprland, Sway and niri; returns None elsewhere.
+pub fn focused_window() -> Option<FocusedWindow> {
+    if std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+        return query_json("hyprctl", &["activewindow", "-j"]).and_then(|v| parse_hyprland(&v));
+    }
+    if std::env::var_os("SWAYSOCK").is_some() {
+        return query_json("swaymsg", &["-t", "get_tree"]).and_then(|v| parse_sway(&v));
+    }
+    if std::env::var_os("NIRI_SOCKET").is_some() {
+        return query_json("niri", &["msg", "--json", "focused-window"]).and_then(|v| parse_niri(&v));
+    }
+    None
+}
+
+/// Names of the compositor's seats. Only sway can report more than one;
+/// elsewhere this is empty, meaning just the default seat.
+pub fn seats() -> Vec<String> {
+    if std::env::var_os("SWAYSOCK").is_some() {
+        return query_json("swaymsg", &["-t", "get_seats"])
+            .map(|v| parse_sway_seats(&v))
+            .unwrap_or_default();
+    }
+    Vec::new()
+}
+
+fn parse_sway_seats(v: &Value) -> Vec<String> {
+    v.as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|seat| seat.get("name").and_then(Value::as_str))
+        .map(str::to_string)
+        .collect()
+}
+
+fn query_json(program: &str, args: &[&str]) -> Option<Value> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    serde_json::from_slice(&output.stdout).ok()
+}
+
+fn window_from(app_id: Option<&str>, title: Option<&str>) -> Option<FocusedWindow> {
+    let app_id = app_id.filter(|s| !s.is_empty())?;
+    Some(FocusedWindow {
+        app_id: app_id.to_string(),
+        title: title.filter(|s| !s.is_empty()).map(str::to_string),
+    })
+}
+
+fn parse_hyprland(v: &Value) -> Option<FocusedWindow> {
+    window_from(
+        v.get("class").and_then(Value::as_str),
+        v.get("title").and_then(Value::as_str),
+    )
+}
+
+fn parse_niri(v: &Value) -> Option<FocusedWindow> {
+    window_from(
+        v.get("app_id").and_then(Value::as_str),
+        v.get("title").and_then(Value::as_str),
+    )
+}
+
+/// Walk the sway tree to the focused node (Xwayland windows use the X11 class)
+fn parse_sway(v: &Value) -> Option<FocusedWindow> {
+    if v.get("focused").and_then(Value::as_bool) == Some(true) {
+        let app_id = v.get("app_id").and_then(Value::as_str).or_else(|| {
+            v.pointer("/window_properties/class")
+                .and_then(Value::as_str)
+        });
+        return window_from(app_id, v.get("name").and_then(Value::as_str));
+    }
+
+    ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|key| v.get(key).and_then(Value::as_array))
+        .flatten()
+        .find_map(parse_sway)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_hyprland() {
+        let v = json!({"class": "firefox", "title": "Mozilla Firefox"});
+        let window = parse_hyprland(&v).unwrap();
+        assert_eq!(window.app_id, "firefox");
+        assert_eq!(window.title.as_deref(), Some("Mozilla Firefox"));
+        assert!(parse_hyprland(&json!({})).is_none());
+    }
+
+    #[test]
+    fn test_parse_niri() {
+        let v = json!({"app_id": "Alacritty", "title": ""});
+        let window = parse_niri(&v).unwrap();
+        assert_eq!(window.app_id, "Alacritty");
+        assert_eq!(window.title, None);
+    }
+
+    #[test]
+    fn test_parse_sway_finds_focused_node() {
+        let v = json!({
+            "focused": false,
+            "nodes": [{
+                "focused": false,
+                "nodes": [
+                    {"focused": false, "app_id": "kitty", "nodes": []},
+                    {
+                        "focused": true,
+                        "app_id": "org.keepassxc.KeePassXC",
+                        "name": "Passwords.kdbx",
+                        "nodes": []
+                    }
+                ]
+            }]
+        });
+        let window = parse_sway(&v).unwrap();
+        assert_eq!(window.app_id, "org.keepassxc.KeePassXC");
+        assert_eq!(window.title.as_deref(), Some("Passwords.kdbx"));
+    }
+
+    #[test]
+    fn test_parse_sway_seats() {
+        let v = json!([{"name": "seat0", "capabilities": 3}, {"name": "seat1"}, {}]);
+        assert_eq!(parse_sway_seats(&v), ["seat0", "seat1"]);
+        assert!(parse_sway_seats(&json!({})).is_empty());
+    }
+
+    #[test]
+    fn test_parse_sway_xwayland_class() {
+        let v = json!({
+            "focused": false,
+            "floating_nodes": [{
+                "focused": true,
+                "app_id": null,
+                "window_properties": {"class": "Slack"}
+            }]
+        });
+        assert_eq!(parse_sway(&v).unwrap().app_id, "Slack");
+    }
+}
@@ -0,0 +1,200 @@
+use crate::storage::{ClipEntry, ABSOLUTE_MAX_ENTRIES};
+use crate::util;
+use std::time::Duration;
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// Captures older than this don't count toward the current rate
+const SAMPLE_WINDOW_MS: i64 = 7 * DAY_MS;
+
+/// Shortest span the rate is computed over, so a burst of copies a minute ago
+/// doesn't extrapolate to thousands per day
+const MIN_SPAN_MS: i64 = 60 * 60 * 1000;
+
+/// History lengths the retention suggestions are given for
+pub const SUGGESTED_DAYS: [u64; 2] = [7, 30];
+
+/// Storage projection from the capture rate of recent regular (unpinned) entries
+#[derive(Debug, Clone, PartialEq)]
+pub struct Forecast {
+    /// Regular entries captured within the sample window
+    pub sampled: usize,
+    /// Time the sample covers (up to 7 days)
+    pub span: Duration,
+    pub per_day: f64,
+    /// Average size of regular entries, in bytes
+    pub avg_size: usize,
+    /// Time until max_entries regular entries exist; None if already full
+    pub until_full: Option<Duration>,
+    /// How far back history reaches once full
+    pub history_at_capacity: Duration,
+    /// Total size once full, pinned entries included
+    pub size_at_capacity: usize,
+}
+
+impl Forecast {
+    /// max_entries that keeps `days` of history at the current rate
+    pub fn entries_for_days(&self, days: u64) -> usize {
+        ((self.per_day * days as f64).ceil() as usize).clamp(1, ABSOLUTE_MAX_ENTRIES)
+    }
+}
+
+/// Project growth from `entries` (newest first, as in the index). Returns None
+/// when fewer than two regular entries were captured in the last 7 days.
+pub fn forecast(entries: &[ClipEntry], max_entries: usize, now: i64) -> Option<Forecast> {
+    let regular: Vec<&ClipEntry> = entries.iter().filter(|e| !e.pinned).collect();
+    let recent: Vec<&ClipEntry> = regular
+        .iter()
+        .copied()
+        .filter(|e| now - e.timestamp <= SAMPLE_WINDOW_MS)
+        .collect();
+    if recent.len() < 2 {
+        return None;
+    }
+
+    let oldest = recent.iter().map(|e| e.timestamp).min()?;
+    let span_ms = (now - oldest).max(MIN_SPAN_MS);
+    let per_day = recent.len() as f64 * DAY_MS as f64 / span_ms as f64;
+
+    let avg_size = regular.iter().map(|e| e.size).sum::<usize>() / regular.len();
+    let pinned_size: usize = entries.iter().filter(|e| e.pinned).map(|e| e.size).sum();
+
+    let until_full = (regular.len() < max_entries)
+        .then(|| days((max_entries - regular.len()) as f64 / per_day));
+
+    Some(Forecast {
+        sampled: recent.len(),
+        span: Duration::from_millis(span_ms as u64),
+        per_day,
+        avg_size,
+        until_full,
+        history_at_capacity: days(max_entries as f64 / per_day),
+        size_at_capacity: avg_size * max_entries + pinned_size,
+    })
+}
+
+fn days(n: f64) -> Duration {
+    Duration::from_secs_f64(n * (DAY_MS / 1000) as f64)
+}
+
+/// Print the forecast section of `clipstack stats --forecast`
+pub fn print(forecast: Option<&Forecast>, max_entries: usize) {
+    println!();
+    let Some(f) = forecast else {
+        println!("Forecast:    not enough captures in the last 7 days");
+        return;
+    };
+
+    println!(
+        "Forecast:    {} captures in the last {} (~{:.1}/day), avg {}",
+        f.sampled,
+        util::format_duration(f.span),
+        f.per_day,
+        util::format_size(f.avg_size)
+    );
+    match f.until_full {
+        Some(d) => println!("  Full in:   ~{} ({} entries)", util::format_duration(d), max_entries),
+        None => println!("  Full:      oldest entries are being pruned"),
+    }
+    println!(
+        "  At limit:  ~{}, history reaches back ~{}",
+        util::format_size(f.size_at_capacity),
+        util::format_duration(f.history_at_capacity)
+    );
+
+    println!("Suggested max_entries (config.toml or --max-entries):");
+    for days in SUGGESTED_DAYS {
+        let n = f.entries_for_days(days);
+        let current = if n == max_entries { " (current)" } else { "" };
+        println!(
+            "  {:<6} keeps ~{}d, ~{}{}",
+            n,
+            days,
+            util::format_size(n * f.avg_size),
+            current
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(age_ms: i64, size: usize, pinned: bool, now: i64) -> ClipEntry {
+        ClipEntry {
+            id: (now - age_ms).to_string(),
+            timestamp: now - age_ms,
+            size,
+            preview: String::new(),
+            hash: String::new(),
+            pinned,
+            source_app: None,
+            window_title: None,
+            tags: Vec::new(),
+            name: None,
+            context: None,
+            seat: None,
+            kind: Default::default(),
+            sensitive: false,
+            annotations: Default::default(),
+            use_count: 0,
+            last_used: None,
+            representations: Vec::new(),
+            expires: None,
+        }
+    }
+
+    #[test]
+    fn test_forecast_rate_and_capacity() {
+        let now = 100 * DAY_MS;
+        // 10 captures/day over the last 2 days, 100 bytes each
+        let mut entries: Vec<ClipEntry> =
+            (0..20).map(|i| entry(i * DAY_MS / 10, 100, false, now)).collect();
+        entries.push(entry(DAY_MS, 5000, true, now));
+
+        let f = forecast(&entries, 100, now).unwrap();
+        assert_eq!(f.sampled, 20);
+        assert!((f.per_day - 20.0 / 1.9).abs() < 0.01);
+        assert_eq!(f.avg_size, 100);
+        assert_eq!(f.size_at_capacity, 100 * 100 + 5000);
+
+        let until_full = f.until_full.unwrap().as_secs_f64() / 86400.0;
+        assert!((until_full - 80.0 / f.per_day).abs() < 0.01);
+        assert_eq!(f.entries_for_days(7), (f.per_day * 7.0).ceil() as usize);
+    }
+
+    #[test]
+    fn test_forecast_ignores_old_entries_for_rate() {
+        let now = 100 * DAY_MS;
+        let entries = vec![
+            entry(DAY_MS / 2, 10, false, now),
+            entry(DAY_MS, 10, false, now),
+            entry(30 * DAY_MS, 10, false, now),
+        ];
+
+        let f = forecast(&entries, 3, now).unwrap();
+        assert_eq!(f.sampled, 2);
+        assert!((f.per_day - 2.0).abs() < 0.01);
+        assert_eq!(f.until_full, None, "Already at max_entries");
+    }
+
+    #[test]
+    fn test_forecast_needs_recent_captures() {
+        let now = 100 * DAY_MS;
+        assert_eq!(forecast(&[], 100, now), None);
+        assert_eq!(forecast(&[entry(1000, 10, false, now)], 100, now), None);
+        // Pinned entries don't count toward the capture rate
+        let pinned = [entry(1000, 10, true, now), entry(2000, 10, true, now)];
+        assert_eq!(forecast(&pinned, 100, now), None);
+    }
+
+    #[test]
+    fn test_burst_uses_minimum_span() {
+        let now = 100 * DAY_MS;
+        let entries = vec![entry(0, 10, false, now), entry(1000, 10, false, now)];
+
+        let f = forecast(&entries, 100, now).unwrap();
+        assert!((f.per_day - 48.0).abs() < 0.01);
+        assert!(f.entries_for_days(30) <= ABSOLUTE_MAX_ENTRIES);
+    }
+}
@@ -0,0 +1,375 @@
+use crate::config::ImagePreview;
+use crate::util;
+use anyhow::{Context, Result};
+use std::fmt::Write;
+
+/// Cell size assumed when the terminal doesn't report its size in pixels
+const DEFAULT_CELL: (u16, u16) = (10, 20);
+
+/// Longest base64 payload per kitty graphics escape
+const KITTY_CHUNK: usize = 4096;
+
+/// Terminal graphics protocols the preview can draw images with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Kitty,
+    Sixel,
+}
+
+/// Format and dimensions read from an image's header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageInfo {
+    pub format: &'static str,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Where an image goes: its size in cells and in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fit {
+    pub cols: u16,
+    pub rows: u16,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// The protocol for `[picker] image_preview`, looking at the terminal for "auto"
+pub fn protocol(setting: ImagePreview) -> Option<Protocol> {
+    match setting {
+        ImagePreview::Off => None,
+        ImagePreview::Kitty => Some(Protocol::Kitty),
+        ImagePreview::Sixel => Some(Protocol::Sixel),
+        ImagePreview::Auto => detect(|name| std::env::var(name).ok()),
+    }
+}
+
+/// Guess the protocol from the environment. Multiplexers are left out: they
+/// pass graphics through only when set up for it.
+fn detect(env: impl Fn(&str) -> Option<String>) -> Option<Protocol> {
+    let term = env("TERM").unwrap_or_default();
+    let program = env("TERM_PROGRAM").unwrap_or_default();
+    if env("TMUX").is_some() || term.starts_with("screen") || term.starts_with("tmux") {
+        return None;
+    }
+    if env("KITTY_WINDOW_ID").is_some()
+        || env("KONSOLE_VERSION").is_some()
+        || ["xterm-kitty", "xterm-ghostty"].contains(&term.as_str())
+        || ["ghostty", "WezTerm"].contains(&program.as_str())
+    {
+        return Some(Protocol::Kitty);
+    }
+    if term.starts_with("foot") || term.contains("mlterm") || term.contains("sixel") {
+        return Some(Protocol::Sixel);
+    }
+    None
+}
+
+/// Read the format and dimensions of a PNG, JPEG, GIF, BMP or WebP image
+pub fn probe(data: &[u8]) -> Option<ImageInfo> {
+    let u16_be = |at: usize| Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?));
+    let u16_le = |at: usize| Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?));
+    let u32_be = |at: usize| Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?));
+    let u32_le = |at: usize| Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?));
+    let u24_le = |at: usize| Some(u32_le(at)? & 0xff_ffff);
+    let info = |format, width, height| Some(ImageInfo { format, width, height });
+
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return info("PNG", u32_be(16)?, u32_be(20)?);
+    }
+    if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        return info("GIF", u16_le(6)?.into(), u16_le(8)?.into());
+    }
+    if data.starts_with(b"BM") {
+        let height = (u32_le(22)? as i32).unsigned_abs();
+        return info("BMP", u32_le(18)?, height);
+    }
+    if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+        return match data.get(12..16)? {
+            b"VP8 " => info("WebP", (u16_le(26)? & 0x3fff).into(), (u16_le(28)? & 0x3fff).into()),
+            b"VP8L" => {
+                let bits = u32_le(21)?;
+                info("WebP", (bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1)
+            }
+            b"VP8X" => info("WebP", u24_le(24)? + 1, u24_le(27)? + 1),
+            _ => None,
+        };
+    }
+    if data.starts_with(&[0xff, 0xd8]) {
+        // Walk the segments up to the frame header
+        let mut at = 2;
+        while *data.get(at)? == 0xff {
+            let marker = *data.get(at + 1)?;
+            match marker {
+                0xff => at += 1,
+                0xd0..=0xd9 | 0x01 => at += 2,
+                0xc0..=0xcf if ![0xc4, 0xc8, 0xcc].contains(&marker) => {
+                    return info("JPEG", u16_be(at + 7)?.into(), u16_be(at + 5)?.into());
+                }
+                _ => at += 2 + usize::from(u16_be(at + 2)?),
+            }
+        }
+    }
+    None
+}
+
+/// One line about an image for the preview, e.g. `PNG 1920x1080, 2.3MB`;
+/// just the MIME type and size when the header can't be read
+pub fn summary(data: &[u8], mime: &str) -> String {
+    let size = util::format_size(data.len());
+    match probe(data) {
+        Some(info) => format!("{} {}x{}, {}", info.format, info.width, info.height, size),
+        None => format!("{}, {}", mime, size),
+    }
+}
+
+/// The terminal's cell size in pixels, or a common one when it doesn't say
+pub fn cell_size() -> (u16, u16) {
+    match crossterm::terminal::window_size() {
+        Ok(size) if size.width > 0 && size.height > 0 && size.columns > 0 && size.rows > 0 => {
+            ((size.width / size.columns).max(1), (size.height / size.rows).max(1))
+        }
+        _ => DEFAULT_CELL,
+    }
+}
+
+/// Fit an image into `cols`x`rows` cells of `cell` pixels, keeping its
+/// aspect ratio and never enlarging it
+pub fn fit(info: &ImageInfo, cols: u16, rows: u16, cell: (u16, u16)) -> Option<Fit> {
+    if info.width == 0 || info.height == 0 || cols == 0 || rows == 0 {
+        return None;
+    }
+    let box_width = f64::from(cols) * f64::from(cell.0);
+    let box_height = f64::from(rows) * f64::from(cell.1);
+    let scale = (box_width / f64::from(info.width))
+        .min(box_height / f64::from(info.height))
+        .min(1.0);
+    let width = ((f64::from(info.width) * scale) as u32).max(1);
+    let height = ((f64::from(info.height) * scale) as u32).max(1);
+    Some(Fit {
+        cols: (width.div_ceil(cell.0.into()) as u16).min(cols),
+        rows: (height.div_ceil(cell.1.into()) as u16).min(rows),
+        width,
+        height,
+    })
+}
+
+/// Escapes drawing a PNG at the cursor, or none for other formats
+pub fn draw(protocol: Protocol, png: &[u8], fit: Fit) -> Result<Option<String>> {
+    if probe(png).is_none_or(|info| info.format != "PNG") {
+        return Ok(None);
+    }
+    Ok(Some(match protocol {
+        Protocol::Kitty => kitty(png, fit),
+        Protocol::Sixel => sixel(png, fit)?,
+    }))
+}
+
+/// Escape removing what [`draw`] put on screen; sixel images go when the
+/// screen is cleared
+pub fn clear(protocol: Protocol) -> &'static str {
+    match protocol {
+        Protocol::Kitty => "\x1b_Ga=d,q=2\x1b\\",
+        Protocol::Sixel => "",
+    }
+}
+
+/// The PNG itself, which kitty scales to the cells, sent in chunks. The
+/// cursor stays put so the rest of the screen is unaffected.
+fn kitty(png: &[u8], fit: Fit) -> String {
+    let encoded = util::encode_base64(png);
+    let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(KITTY_CHUNK).collect();
+    let mut out = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        let chunk = std::str::from_utf8(chunk).expect("base64 is ASCII");
+        if i == 0 {
+            let _ = write!(
+                out,
+                "\x1b_Ga=T,f=100,q=2,C=1,c={},r={},m={};{}\x1b\\",
+                fit.cols, fit.rows, more, chunk
+            );
+        } else {
+            let _ = write!(out, "\x1b_Gm={};{}\x1b\\", more, chunk);
+        }
+    }
+    out
+}
+
+/// Decode a PNG to RGBA, 8 bits per channel
+fn decode_png(png: &[u8]) -> Result<(Vec<u8>, usize, usize)> {
+    let mut decoder = png::Decoder::new(png);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().context("Unreadable PNG")?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buf).context("Unreadable PNG")?;
+    let pixels: Vec<u8> = match frame.color_type {
+        png::ColorType::Rgba => buf,
+        png::ColorType::Rgb => buf.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+        png::ColorType::GrayscaleAlpha => {
+            buf.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect()
+        }
+        png::ColorType::Grayscale => buf.iter().flat_map(|&v| [v, v, v, 255]).collect(),
+        png::ColorType::Indexed => anyhow::bail!("Unexpected indexed PNG output"),
+    };
+    let (width, height) = (frame.width as usize, frame.height as usize);
+    anyhow::ensure!(pixels.len() >= width * height * 4, "Truncated PNG");
+    Ok((pixels, width, height))
+}
+
+/// Sixel image of the PNG scaled to the fit, in a 6x6x6 color cube.
+/// Transparent pixels are left as they are.
+fn sixel(png: &[u8], fit: Fit) -> Result<String> {
+    let (pixels, src_width, src_height) = decode_png(png)?;
+    let (width, height) = (fit.width as usize, fit.height as usize);
+    // Palette index per pixel after nearest-neighbour scaling
+    let colors: Vec<Option<usize>> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let sx = x * src_width / width;
+            let sy = y * src_height / height;
+            let p = &pixels[(sy * src_width + sx) * 4..][..4];
+            let level = |v: u8| (usize::from(v) * 5 + 127) / 255;
+            (p[3] >= 128).then(|| level(p[0]) * 36 + level(p[1]) * 6 + level(p[2]))
+        })
+        .collect();
+
+    let mut out = format!("\x1bP0;1;0q\"1;1;{};{}", width, height);
+    let mut used = [false; 216];
+    colors.iter().flatten().for_each(|&c| used[c] = true);
+    for color in (0..216).filter(|&c| used[c]) {
+        let percent = |level: usize| level * 100 / 5;
+        let (r, g, b) = (color / 36, color / 6 % 6, color % 6);
+        let _ = write!(out, "#{};2;{};{};{}", color, percent(r), percent(g), percent(b));
+    }
+
+    for top in (0..height).step_by(6) {
+        let rows = top..(top + 6).min(height);
+        let mut in_band = [false; 216];
+        for y in rows.clone() {
+            colors[y * width..][..width].iter().flatten().for_each(|&c| in_band[c] = true);
+        }
+        let mut first = true;
+        for color in (0..216).filter(|&c| in_band[c]) {
+            if !first {
+                out.push('$');
+            }
+            first = false;
+            let _ = write!(out, "#{}", color);
+            let column = |x: usize| {
+                rows.clone()
+                    .filter(|&y| colors[y * width + x] == Some(color))
+                    .fold(0u8, |bits, y| bits | 1 << (y - top))
+            };
+            let mut x = 0;
+            while x < width {
+                let bits = column(x);
+                let mut run = 1;
+                while x + run < width && column(x + run) == bits {
+                    run += 1;
+                }
+                let ch = char::from(63 + bits);
+                if run > 3 {
+                    let _ = write!(out, "!{}{}", run, ch);
+                } else {
+                    out.extend(std::iter::repeat_n(ch, run));
+                }
+                x += run;
+            }
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\");
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `width`x`height` PNG, red on the left half and transparent on the right
+    fn test_png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut encoder = png::Encoder::new(&mut data, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let pixels: Vec<u8> = (0..height)
+            .flat_map(|_| (0..width).map(|x| x < width / 2))
+            .flat_map(|red| if red { [255, 0, 0, 255] } else { [0, 0, 0, 0] })
+            .collect();
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&pixels).unwrap();
+        writer.finish().unwrap();
+        data
+    }
+
+    #[test]
+    fn test_probe_image_headers() {
+        let png = test_png(12, 7);
+        assert_eq!(probe(&png), Some(ImageInfo { format: "PNG", width: 12, height: 7 }));
+
+        let mut gif = b"GIF89a".to_vec();
+        gif.extend_from_slice(&[0x80, 0x07, 0x38, 0x04]);
+        assert_eq!(probe(&gif).unwrap().width, 1920);
+        assert_eq!(probe(&gif).unwrap().height, 1080);
+
+        // SOI, an APP0 segment, then a baseline frame header
+        let mut jpeg = vec![0xff, 0xd8, 0xff, 0xe0, 0x00, 0x04, 0x00, 0x00];
+        jpeg.extend_from_slice(&[0xff, 0xc0, 0x00, 0x11, 0x08, 0x02, 0x58, 0x03, 0x20]);
+        let info = probe(&jpeg).unwrap();
+        assert_eq!((info.format, info.width, info.height), ("JPEG", 800, 600));
+
+        assert_eq!(probe(b"plain text"), None);
+        assert_eq!(probe(&png[..12]), None, "Cut off before the size");
+        assert_eq!(summary(&png, "image/png"), format!("PNG 12x7, {}B", png.len()));
+        assert_eq!(summary(b"???", "image/x-icon"), "image/x-icon, 3B");
+    }
+
+    #[test]
+    fn test_fit_keeps_aspect_and_never_enlarges() {
+        let info = ImageInfo { format: "PNG", width: 1920, height: 1080 };
+        let fit = fit(&info, 40, 20, (10, 20)).unwrap();
+        assert_eq!((fit.width, fit.height), (400, 225));
+        assert_eq!((fit.cols, fit.rows), (40, 12));
+
+        let small = ImageInfo { format: "PNG", width: 30, height: 30 };
+        let fit = super::fit(&small, 40, 20, (10, 20)).unwrap();
+        assert_eq!((fit.width, fit.height, fit.cols, fit.rows), (30, 30, 3, 2));
+        assert_eq!(super::fit(&small, 0, 20, (10, 20)), None);
+    }
+
+    #[test]
+    fn test_draw_kitty_and_sixel() {
+        let png = test_png(8, 8);
+        let fit = Fit { cols: 2, rows: 1, width: 8, height: 8 };
+
+        let kitty = draw(Protocol::Kitty, &png, fit).unwrap().unwrap();
+        assert!(kitty.starts_with("\x1b_Ga=T,f=100,q=2,C=1,c=2,r=1,m=0;"));
+        assert!(kitty.contains(&util::encode_base64(&png)));
+
+        let sixel = draw(Protocol::Sixel, &png, fit).unwrap().unwrap();
+        assert!(sixel.starts_with("\x1bP0;1;0q\"1;1;8;8"));
+        assert!(sixel.contains("#180;2;100;0;0"), "Only red is in the palette: {}", sixel);
+        assert!(!sixel.contains("#0;"), "Transparent pixels take no color");
+        // Two bands, the first 6 rows full (~) and then 2 (B), red on 4 columns
+        assert!(sixel.contains("#180!4~!4?-#180!4B!4?-"));
+        assert!(sixel.ends_with("\x1b\\"));
+
+        let gif = b"GIF89a\x08\x00\x08\x00";
+        assert_eq!(draw(Protocol::Kitty, gif, fit).unwrap(), None);
+    }
+
+    #[test]
+    fn test_detect_protocol() {
+        let env = |vars: &'static [(&str, &str)]| {
+            move |name: &str| {
+                vars.iter().find(|(k, _)| *k == name).map(|(_, v)| v.to_string())
+            }
+        };
+        assert_eq!(detect(env(&[("TERM", "xterm-kitty")])), Some(Protocol::Kitty));
+        assert_eq!(detect(env(&[("TERM_PROGRAM", "WezTerm")])), Some(Protocol::Kitty));
+        assert_eq!(detect(env(&[("TERM", "foot")])), Some(Protocol::Sixel));
+        assert_eq!(detect(env(&[("TERM", "xterm-256color")])), None);
+        let tmux = env(&[("TERM", "tmux-256color"), ("KITTY_WINDOW_ID", "1")]);
+        assert_eq!(detect(tmux), None);
+    }
+}
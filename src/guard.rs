@@ -0,0 +1,81 @@
+use crate::config::PasteGuardConfig;
+use crate::focus::{self, FocusedWindow};
+use crate::kind;
+use anyhow::Result;
+use notify_rust::{Notification, Timeout};
+
+/// How long the confirmation notification waits for an answer
+const CONFIRM_TIMEOUT_MS: u32 = 15_000;
+
+/// Notification action that lets the copy through
+const COPY_ACTION: &str = "copy";
+
+/// The focused app to warn about before copying `content`: set when the guard
+/// is on, the content looks like a credential and the app is a guarded one
+pub fn warning_app(
+    config: &PasteGuardConfig,
+    content: &str,
+    window: Option<&FocusedWindow>,
+) -> Option<String> {
+    let window = window.filter(|_| config.enabled)?;
+    (config.guards(&window.app_id) && kind::looks_like_credential(content))
+        .then(|| window.app_id.clone())
+}
+
+/// For copies made without the picker (`paste-snippet`, `pick --select-first`):
+/// ask through a desktop notification when the focused app is guarded.
+/// Fails unless the copy was confirmed.
+pub fn confirm(config: &PasteGuardConfig, content: &str) -> Result<()> {
+    if !config.enabled || !kind::looks_like_credential(content) {
+        return Ok(());
+    }
+    let window = focus::focused_window();
+    let Some(app) = warning_app(config, content, window.as_ref()) else {
+        return Ok(());
+    };
+    if ask(&app)? {
+        return Ok(());
+    }
+    anyhow::bail!("Not copied: this looks like a credential and {} is focused", app)
+}
+
+fn ask(app: &str) -> Result<bool> {
+    let handle = Notification::new()
+        .appname("clipstack")
+        .summary("Copy a credential?")
+        .body(&format!("This entry looks like a password or token, and {} is focused.", app))
+        .action(COPY_ACTION, "Copy anyway")
+        .action("cancel", "Cancel")
+        .timeout(Timeout::Milliseconds(CONFIRM_TIMEOUT_MS))
+        .show()?;
+    let mut confirmed = false;
+    handle.wait_for_action(|action| confirmed = action == COPY_ACTION);
+    Ok(confirmed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(app_id: &str) -> FocusedWindow {
+        FocusedWindow {
+            app_id: app_id.to_string(),
+            title: None,
+        }
+    }
+
+    #[test]
+    fn test_warning_app() {
+        let mut config = PasteGuardConfig::default();
+        let secret = "Tr0ub4dor&3";
+        let firefox = window("org.mozilla.firefox");
+        assert_eq!(warning_app(&config, secret, Some(&firefox)), None, "Off by default");
+
+        config.enabled = true;
+        assert_eq!(warning_app(&config, secret, Some(&firefox)), Some(firefox.app_id.clone()));
+        assert_eq!(warning_app(&config, "just some text", Some(&firefox)), None);
+        assert_eq!(warning_app(&config, secret, Some(&window("kitty"))), None);
+        assert_eq!(warning_app(&config, secret, None), None);
+        assert!(config.guards("Slack"));
+    }
+}
@@ -0,0 +1,154 @@
+use crate::backend::new_entry;
+use crate::export::ExportedEntry;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// greenclip print shows multi-line entries on one line, newlines replaced by this
+const GREENCLIP_NEWLINE: char = '\u{a0}';
+
+/// Another clipboard manager whose history `clipstack import --from` reads
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Source {
+    /// cliphist's database, read through the cliphist binary
+    Cliphist,
+    /// clipman's JSON history file
+    Clipman,
+    /// Output of `greenclip print`; greenclip is run when no file is given
+    Greenclip,
+}
+
+impl Source {
+    /// Where the manager keeps its history by default, if it's a file we read
+    pub fn default_path(self) -> Option<PathBuf> {
+        match self {
+            Source::Cliphist => Some(dirs::cache_dir()?.join("cliphist").join("db")),
+            Source::Clipman => Some(dirs::data_dir()?.join("clipman.json")),
+            Source::Greenclip => None,
+        }
+    }
+
+    /// Text entries, oldest first. Binary entries (images) are skipped.
+    pub fn read(self, path: Option<&Path>) -> Result<Vec<String>> {
+        let path = path.map(Path::to_path_buf).or_else(|| self.default_path());
+        match (self, path) {
+            (Source::Cliphist, Some(db)) => read_cliphist(&db),
+            (Source::Clipman, Some(file)) => {
+                let data = fs::read(&file).with_context(|| format!("Failed to read {:?}", file))?;
+                parse_clipman(&data).with_context(|| format!("Cannot import {:?}", file))
+            }
+            (Source::Greenclip, Some(file)) => {
+                let data = fs::read(&file).with_context(|| format!("Failed to read {:?}", file))?;
+                Ok(parse_greenclip(&String::from_utf8_lossy(&data)))
+            }
+            (Source::Greenclip, None) => {
+                let printed = run(Command::new("greenclip").arg("print"), None)?;
+                Ok(parse_greenclip(&String::from_utf8_lossy(&printed)))
+            }
+            (_, None) => bail!("Cannot find the history file, pass its path"),
+        }
+    }
+}
+
+/// clipman keeps a JSON array of strings, newest last
+fn parse_clipman(data: &[u8]) -> Result<Vec<String>> {
+    serde_json::from_slice(data).context("Not a clipman history file")
+}
+
+/// One entry per line, newest first
+fn parse_greenclip(printed: &str) -> Vec<String> {
+    let mut entries: Vec<String> = printed
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.replace(GREENCLIP_NEWLINE, "\n"))
+        .collect();
+    entries.reverse();
+    entries
+}
+
+/// `cliphist list` prints `<id>\t<preview>`, newest first; each is decoded in turn
+fn read_cliphist(db: &Path) -> Result<Vec<String>> {
+    if !db.exists() {
+        bail!("No cliphist database at {:?}", db);
+    }
+    let cliphist = || {
+        let mut cmd = Command::new("cliphist");
+        cmd.arg("-db-path").arg(db);
+        cmd
+    };
+    let list = run(cliphist().arg("list"), None)?;
+    let mut entries = Vec::new();
+    for line in String::from_utf8_lossy(&list).lines().filter(|l| l.contains('\t')) {
+        let content = run(cliphist().arg("decode"), Some(line))?;
+        match String::from_utf8(content) {
+            Ok(text) if !text.is_empty() => entries.push(text),
+            _ => log::debug!("[import] Skipping binary cliphist entry {:?}", line),
+        }
+    }
+    entries.reverse();
+    Ok(entries)
+}
+
+fn run(cmd: &mut Command, stdin: Option<&str>) -> Result<Vec<u8>> {
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run {} (is it installed?)", program))?;
+    if let Some(mut pipe) = child.stdin.take() {
+        pipe.write_all(stdin.unwrap_or_default().as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!("{} failed: {}", program, String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(output.stdout)
+}
+
+/// Entries to hand to `Storage::import_entries`, oldest first. Other managers
+/// don't record copy times, so entries get consecutive times ending now.
+pub fn to_entries(contents: Vec<String>) -> Vec<ExportedEntry> {
+    let now = crate::util::now_millis();
+    let contents: Vec<String> = contents.into_iter().filter(|c| !c.trim().is_empty()).collect();
+    let first = now - contents.len() as i64;
+    contents
+        .into_iter()
+        .enumerate()
+        .map(|(i, content)| ExportedEntry {
+            entry: new_entry(first + i as i64 + 1, &content, String::new(), Default::default()),
+            content,
+            representation_content: Vec::new(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_clipman_and_greenclip() {
+        let clipman = br#"["first", "second\nline", "newest"]"#;
+        assert_eq!(parse_clipman(clipman).unwrap(), ["first", "second\nline", "newest"]);
+        assert!(parse_clipman(br#"{"history": []}"#).is_err());
+
+        let printed = "newest\nfor f in *;\u{a0}do echo $f;\u{a0}done\n\nfirst\n";
+        assert_eq!(
+            parse_greenclip(printed),
+            ["first", "for f in *;\ndo echo $f;\ndone", "newest"]
+        );
+    }
+
+    #[test]
+    fn test_to_entries_orders_and_skips_blank() {
+        let entries = to_entries(vec!["old".into(), "  ".into(), "new".into()]);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].content, "old");
+        assert!(entries[0].entry.timestamp < entries[1].entry.timestamp);
+        assert_eq!(entries[1].entry.id, entries[1].entry.timestamp.to_string());
+    }
+}
@@ -0,0 +1,200 @@
+use anyhow::{Context, Result};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::fs::{self, File, OpenOptions};
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Rotate the daemon log once it grows past this size
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+/// Rotated files kept next to the active log (daemon.log.1 ... daemon.log.3)
+const KEEP_ROTATED: usize = 3;
+
+/// Name of the daemon log file inside the storage dir
+pub const LOG_FILE_NAME: &str = "daemon.log";
+
+/// Logs to stderr and optionally to a size-rotated file
+struct Logger {
+    stderr_level: LevelFilter,
+    file_level: LevelFilter,
+    file: Option<Mutex<RotatingFile>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.stderr_level || metadata.level() <= self.file_level
+    }
+
+    fn log(&self, record: &Record) {
+        if record.level() <= self.stderr_level {
+            match record.level() {
+                Level::Info => eprintln!("{}", record.args()),
+                level => eprintln!("{}: {}", level.as_str().to_lowercase(), record.args()),
+            }
+        }
+
+        if record.level() <= self.file_level
+            && let Some(file) = &self.file
+            && let Ok(mut file) = file.lock()
+        {
+            let line = format!(
+                "{} {:<5} {}\n",
+                chrono::Local::now().format("%Y-%m-%dT%H:%M:%S%.3f"),
+                record.level(),
+                record.args()
+            );
+            // Nowhere left to report a failing log write
+            let _ = file.write_line(line.as_bytes());
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file
+            && let Ok(mut file) = file.lock()
+        {
+            let _ = file.file.flush();
+        }
+    }
+}
+
+/// Install the global logger for CLI commands (stderr only)
+pub fn init(level: LevelFilter) -> Result<()> {
+    install(Logger {
+        stderr_level: level,
+        file_level: LevelFilter::Off,
+        file: None,
+    })
+}
+
+/// Install the global logger for the daemon: everything at `level` goes to the
+/// rotated log file, while stderr only gets warnings and errors when it isn't a
+/// terminal (e.g. the systemd journal) so per-capture lines don't flood it.
+/// A detached daemon's stderr is the log file itself and gets nothing.
+pub fn init_daemon(level: LevelFilter, log_path: &Path, detached: bool) -> Result<()> {
+    let stderr_level = if detached {
+        LevelFilter::Off
+    } else if std::io::stderr().is_terminal() {
+        level
+    } else {
+        level.min(LevelFilter::Warn)
+    };
+    install(Logger {
+        stderr_level,
+        file_level: level,
+        file: Some(Mutex::new(RotatingFile::open(log_path.to_path_buf())?)),
+    })
+}
+
+fn install(logger: Logger) -> Result<()> {
+    let max_level = logger.stderr_level.max(logger.file_level);
+    log::set_boxed_logger(Box::new(logger)).context("Logger already initialized")?;
+    log::set_max_level(max_level);
+    Ok(())
+}
+
+/// Append-only file that is renamed to `<name>.1` (shifting older ones) when full
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf) -> Result<Self> {
+        Self::with_max_size(path, MAX_LOG_SIZE)
+    }
+
+    fn with_max_size(path: PathBuf, max_size: u64) -> Result<Self> {
+        let file = Self::open_append(&path)?;
+        let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Ok(Self {
+            path,
+            file,
+            size,
+            max_size,
+        })
+    }
+
+    fn open_append(path: &Path) -> Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open log file: {:?}", path))
+    }
+
+    fn rotated_path(&self, n: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+
+    fn write_line(&mut self, line: &[u8]) -> Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        self.file.write_all(line)?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        for n in (1..KEEP_ROTATED).rev() {
+            let from = self.rotated_path(n);
+            if from.exists() {
+                fs::rename(&from, self.rotated_path(n + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.rotated_path(1))?;
+        self.file = Self::open_append(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_rotating_file_rotates_by_size() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(LOG_FILE_NAME);
+        let mut file = RotatingFile::with_max_size(path.clone(), 10).unwrap();
+
+        for line in ["one-----\n", "two-----\n", "three---\n"] {
+            file.write_line(line.as_bytes()).unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "three---\n");
+        assert_eq!(fs::read_to_string(dir.path().join("daemon.log.1")).unwrap(), "two-----\n");
+        assert_eq!(fs::read_to_string(dir.path().join("daemon.log.2")).unwrap(), "one-----\n");
+    }
+
+    #[test]
+    fn test_rotation_keeps_limited_history() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(LOG_FILE_NAME);
+        let mut file = RotatingFile::with_max_size(path.clone(), 4).unwrap();
+
+        for _ in 0..10 {
+            file.write_line(b"line\n").unwrap();
+        }
+
+        assert!(dir.path().join(format!("daemon.log.{}", KEEP_ROTATED)).exists());
+        assert!(!dir.path().join(format!("daemon.log.{}", KEEP_ROTATED + 1)).exists());
+    }
+
+    #[test]
+    fn test_reopen_continues_size() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(LOG_FILE_NAME);
+        fs::write(&path, "existing\n").unwrap();
+
+        let mut file = RotatingFile::with_max_size(path.clone(), 12).unwrap();
+        file.write_line(b"more\n").unwrap();
+        assert!(dir.path().join("daemon.log.1").exists(), "Existing size counts toward the limit");
+    }
+}
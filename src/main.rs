@@ -1,16 +1,52 @@
+mod archive;
+mod audit;
+mod backend;
+mod backup;
 mod clipboard;
+mod completion;
+mod config;
+mod crypto;
 mod daemon;
+mod diff;
+mod digest;
+mod editor;
+mod error;
+mod export;
+mod filters;
+mod focus;
+mod forecast;
+mod graphics;
+mod guard;
+mod importers;
+mod kind;
+mod logging;
+mod metrics;
+mod multiuser;
+mod notify;
 mod picker;
+mod profile;
+mod query;
+mod schedule;
+mod shell_history;
+mod snippets;
+#[cfg(feature = "sqlite")]
+mod sqlite;
 mod storage;
+mod systemd;
+mod theme;
+mod transform;
+mod typer;
+mod update;
+mod usage;
 mod util;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::{CommandFactory, Parser, Subcommand};
-use clap_complete::{generate, Shell};
+use clap_complete::Shell;
 use std::io::{self, Read, Write};
 use std::net::TcpListener;
-use std::path::PathBuf;
-use std::process::Command;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode};
 
 #[derive(Parser)]
 #[command(name = "clipstack")]
@@ -21,11 +57,37 @@ struct Cli {
     #[arg(long, global = true)]
     storage_dir: Option<PathBuf>,
 
+    /// Config file (default: ~/.config/clipstack/config.toml)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Use a named profile: its own history, daemon and [profiles.NAME] settings
+    /// Can also be set via CLIPSTACK_PROFILE environment variable
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
     /// Maximum entries to store (1-10000, default: 100)
     /// Can also be set via CLIPSTACK_MAX_ENTRIES environment variable
     #[arg(long, global = true, value_parser = clap::value_parser!(u32).range(1..=10000))]
     max_entries: Option<u32>,
 
+    /// Maximum pinned entries (1-1000, default: 25)
+    /// Can also be set via CLIPSTACK_MAX_PINNED environment variable
+    #[arg(long, global = true, value_parser = clap::value_parser!(u32).range(1..=1000))]
+    max_pinned: Option<u32>,
+
+    /// Evict the oldest unpinned entries once content exceeds this (e.g. 50MB)
+    #[arg(long, global = true, value_parser = util::parse_size)]
+    max_total_size: Option<usize>,
+
+    /// Log verbosity (off, error, warn, info, debug, trace)
+    #[arg(long, global = true, default_value = "info")]
+    log_level: log::LevelFilter,
+
+    /// Clipboard backend; mock keeps the clipboard in files instead of Wayland
+    #[arg(long, global = true, value_enum, default_value = "wayland")]
+    backend: clipboard::Backend,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -33,35 +95,440 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Copy stdin to clipboard
-    Copy,
+    Copy {
+        /// Record a context with the entry, e.g. the project directory
+        #[arg(long)]
+        context: Option<String>,
+    },
 
     /// Paste clipboard to stdout
     Paste,
 
     /// Open picker UI to select from history
-    Pick,
+    Pick {
+        /// Show only entries copied with this context (toggle with 'c')
+        #[arg(long)]
+        context: Option<String>,
+
+        /// Open with this search typed, e.g. "kind:url"; with --select-first, what to match
+        #[arg(long)]
+        query: Option<String>,
+
+        /// Copy the best match without opening the picker (exits 1 if nothing matches)
+        #[arg(long)]
+        select_first: bool,
+
+        /// Also type the picked entry into the focused window (wtype or ydotool)
+        #[arg(long = "type", conflicts_with = "select_first")]
+        type_entry: bool,
+
+        /// Draw the picker in this many rows below the prompt instead of fullscreen
+        #[arg(
+            long,
+            conflicts_with = "select_first",
+            value_parser = clap::value_parser!(u16).range(picker::MIN_HEIGHT as i64..)
+        )]
+        height: Option<u16>,
+    },
+
+    /// Print a history entry to stdout, optionally transformed
+    Get {
+        /// Entry id or history position (0 = newest)
+        #[arg(default_value = "0")]
+        entry: String,
+        /// Transform to apply before printing
+        #[arg(short, long, value_enum)]
+        transform: Option<transform::Transform>,
+        /// Print another saved format (e.g. text/html) instead of the plain text
+        #[arg(long = "type", value_name = "MIME", conflicts_with = "transform")]
+        mime: Option<String>,
+    },
 
     /// List clipboard history
     List {
         /// Number of entries to show
-        #[arg(short, long, default_value = "10")]
+        #[arg(short, long, visible_alias = "limit", default_value = "10")]
         count: usize,
+        /// Skip this many entries first, for paging through history newest first
+        #[arg(long, default_value = "0")]
+        offset: usize,
+        /// Only entries copied with this context
+        #[arg(long)]
+        context: Option<String>,
+        /// Order of the listing
+        #[arg(long, value_enum, default_value = "time")]
+        sort: query::SortKey,
+        /// Reverse the order (oldest or smallest first)
+        #[arg(long)]
+        reverse: bool,
+        /// Only pinned entries
+        #[arg(long)]
+        pinned: bool,
+        /// Only entries of this kind
+        #[arg(long, value_enum)]
+        kind: Option<kind::EntryKind>,
+        /// Only entries first captured within this long, e.g. 2d or 30m
+        #[arg(long, value_parser = util::parse_duration)]
+        since: Option<std::time::Duration>,
+        /// Only entries with this annotation, as KEY or KEY=VALUE (repeatable)
+        #[arg(
+            long = "annotation",
+            value_name = "KEY[=VALUE]",
+            value_parser = query::AnnotationMatch::parse
+        )]
+        annotations: Vec<query::AnnotationMatch>,
+        /// Print these fields tab-separated instead of the default layout,
+        /// e.g. id,abs-time,size
+        #[arg(long, value_enum, value_delimiter = ',')]
+        columns: Vec<query::Column>,
+        /// Print a header row first (with --columns)
+        #[arg(long, requires = "columns")]
+        header: bool,
+    },
+
+    /// Search history with the picker's search terms (tag:, app:, kind:, ann:,
+    /// is:pinned, after:, before:, size:>, size:<, re:); other words must all
+    /// appear in the content
+    Search {
+        /// Terms and words, e.g. tag:work after:7d size:>1KB invoice
+        #[arg(required = true)]
+        terms: Vec<String>,
+        /// Number of entries to show
+        #[arg(short, long, visible_alias = "limit", default_value = "10")]
+        count: usize,
+        /// Skip this many matches first, for paging through results newest first
+        #[arg(long, default_value = "0")]
+        offset: usize,
+        /// Print these fields tab-separated instead of the default layout
+        #[arg(long, value_enum, value_delimiter = ',')]
+        columns: Vec<query::Column>,
+        /// Print a header row first (with --columns)
+        #[arg(long, requires = "columns")]
+        header: bool,
+    },
+
+    /// Delete entries; they stay restorable in the trash for `trash_retention`
+    Delete {
+        /// Entry ids
+        #[arg(required = true)]
+        ids: Vec<String>,
+    },
+
+    /// List, restore or empty deleted entries
+    Trash {
+        #[command(subcommand)]
+        action: TrashAction,
+    },
+
+    /// List or restore entries archived when pruned (`[storage] archive`)
+    Archive {
+        #[command(subcommand)]
+        action: ArchiveAction,
     },
 
     /// Clear clipboard history
-    Clear,
+    Clear {
+        /// Output format of the summary
+        #[arg(long, value_enum, default_value = "text")]
+        format: OutputFormat,
+    },
 
     /// Run the clipboard monitoring daemon
-    Daemon,
+    Daemon {
+        #[command(subcommand)]
+        action: Option<DaemonAction>,
+
+        /// Watch this Wayland seat instead of the default one (repeatable)
+        #[arg(long = "seat", value_name = "NAME")]
+        seats: Vec<String>,
+
+        /// Watch every seat the compositor reports
+        #[arg(long, conflicts_with = "seats")]
+        all_seats: bool,
+
+        /// Run in the background: new session, output to the daemon log, pid file written
+        #[arg(long)]
+        detach: bool,
+
+        /// Write the daemon's pid here while it runs
+        /// (default with --detach: $XDG_RUNTIME_DIR/clipstack.pid)
+        #[arg(long, value_name = "PATH")]
+        pid_file: Option<PathBuf>,
+    },
 
     /// Show storage statistics
-    Stats,
+    Stats {
+        /// Project storage growth from recent captures and suggest max_entries
+        #[arg(long)]
+        forecast: bool,
+
+        /// Include the running daemon's counters (saves, skips, errors, poll latency)
+        #[arg(long)]
+        daemon: bool,
+
+        /// Include picker sessions: selections, abandonment, searches, time to paste
+        #[arg(long)]
+        usage: bool,
+    },
+
+    /// Summarize a day's clipboard activity (default: today)
+    Digest {
+        /// Summarize yesterday instead
+        #[arg(long, conflicts_with = "date")]
+        yesterday: bool,
+        /// Summarize this day, e.g. 2024-05-01
+        #[arg(long)]
+        date: Option<chrono::NaiveDate>,
+        /// Output format
+        #[arg(long, value_enum, default_value = "text")]
+        format: OutputFormat,
+    },
 
     /// Check daemon status and system health
     Status,
 
+    /// List profiles, marking the one in use
+    Profiles,
+
     /// Attempt to recover from corrupted storage
-    Recover,
+    Recover {
+        /// Output format of the summary
+        #[arg(long, value_enum, default_value = "text")]
+        format: OutputFormat,
+    },
+
+    /// Check that every entry's content is present and matches its hash
+    /// (exits 1 if anything is broken and not repaired)
+    Verify {
+        /// Fix broken entries instead of only reporting them
+        #[arg(long, value_enum)]
+        repair: Option<storage::Repair>,
+        /// Output format of the report
+        #[arg(long, value_enum, default_value = "text")]
+        format: OutputFormat,
+    },
+
+    /// Show the audit log, newest first: when entries were saved and why
+    /// they're gone (needs `audit_log = true` under [storage])
+    Log {
+        /// Number of records to show
+        #[arg(short, long, visible_alias = "limit", default_value = "20")]
+        count: usize,
+        /// Only records of this entry
+        #[arg(long)]
+        id: Option<String>,
+        /// Only records of entries that held exactly this content
+        #[arg(long, conflicts_with = "id")]
+        content: Option<String>,
+        /// Only these actions (repeatable)
+        #[arg(long, value_enum)]
+        action: Vec<audit::AuditAction>,
+        /// Only records from the last DURATION, e.g. "2d"
+        #[arg(long, value_parser = util::parse_duration)]
+        since: Option<std::time::Duration>,
+        /// Output format
+        #[arg(long, value_enum, default_value = "text")]
+        format: OutputFormat,
+    },
+
+    /// Reclaim disk space: delete orphaned content and temp files, compress old
+    /// entries and compact the index or database
+    Vacuum {
+        /// Compress entries last copied longer ago than this, e.g. "7d"
+        #[arg(long, value_parser = util::parse_duration, default_value = "30d")]
+        compress_older_than: std::time::Duration,
+        /// Output format of the summary
+        #[arg(long, value_enum, default_value = "text")]
+        format: OutputFormat,
+    },
+
+    /// Remove entries beyond the configured limits now, optionally tightened for
+    /// this once; pinned entries are kept
+    Prune {
+        /// Also remove entries last copied longer ago than this, e.g. "30d"
+        #[arg(long, value_parser = util::parse_duration)]
+        older_than: Option<std::time::Duration>,
+        /// Keep at most this many unpinned entries
+        #[arg(long, value_name = "N")]
+        keep: Option<usize>,
+        /// Remove the oldest entries until all content fits in this size, e.g. "50MB"
+        #[arg(long, value_parser = util::parse_size)]
+        max_size: Option<usize>,
+        /// Only list what would be removed
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+        /// Output format of the summary
+        #[arg(long, value_enum, default_value = "text")]
+        format: OutputFormat,
+    },
+
+    /// Benchmark the content hash algorithms, or index loading with --index
+    Bench {
+        /// Size of the test content in bytes
+        #[arg(long, default_value = "16777216")]
+        size: usize,
+        /// Instead, time loading an index of this many entries as JSON and as
+        /// MessagePack (see binary_index under [storage])
+        #[arg(long, value_name = "ENTRIES", conflicts_with = "size")]
+        index: Option<usize>,
+    },
+
+    /// Check GitHub for a newer release (only when run, nothing is sent otherwise)
+    CheckUpdate,
+
+    /// Temporarily stop recording history (copy/paste keep working)
+    Pause {
+        /// Resume automatically after this long (e.g. 30s, 10m, 2h)
+        #[arg(long = "for", value_parser = util::parse_duration)]
+        duration: Option<std::time::Duration>,
+    },
+
+    /// Resume recording history after a pause
+    Resume,
+
+    /// Pin an entry, optionally naming it as a snippet
+    Pin {
+        /// Entry id
+        id: String,
+        /// Snippet name for `paste-snippet`
+        #[arg(long)]
+        name: Option<String>,
+    },
+
+    /// Unpin an entry (also removes its snippet name)
+    Unpin {
+        /// Entry id
+        id: String,
+    },
+
+    /// Edit an entry's content in $VISUAL or $EDITOR and save it back
+    Edit {
+        /// Entry id
+        id: String,
+    },
+
+    /// Attach key/value metadata to an entry, or print it (no flags)
+    Annotate {
+        /// Entry id
+        id: String,
+        /// Set an annotation (repeatable), e.g. --set ticket=ABC-123
+        #[arg(long, value_name = "KEY=VALUE", value_parser = query::parse_assignment)]
+        set: Vec<(String, String)>,
+        /// Remove an annotation (repeatable)
+        #[arg(long, value_name = "KEY")]
+        unset: Vec<String>,
+    },
+
+    /// Split an entry into one history entry per piece (e.g. a list of URLs)
+    Split {
+        /// Entry id
+        id: String,
+        /// What separates the pieces
+        #[arg(long, value_enum, default_value = "lines")]
+        by: transform::SplitBy,
+        /// Refuse to create more entries than this
+        #[arg(long, default_value = "50")]
+        max: usize,
+    },
+
+    /// Join entries into a new history entry (inverse of split)
+    Join {
+        /// Entry ids, joined in the given order
+        #[arg(required = true)]
+        ids: Vec<String>,
+        /// Separator placed between entries
+        #[arg(short, long, default_value = "\n")]
+        separator: String,
+    },
+
+    /// Add recent shell commands to history, tagged so the picker can show them together
+    ImportShellHistory {
+        /// How many of the most recent distinct commands to import
+        #[arg(long, default_value = "100")]
+        last: usize,
+        /// History format (default: from $SHELL)
+        #[arg(long, value_enum)]
+        shell: Option<shell_history::HistoryShell>,
+        /// History file (default: $HISTFILE, or the shell's usual file)
+        #[arg(long)]
+        file: Option<PathBuf>,
+        /// Tag given to the imported entries
+        #[arg(long, default_value = "shell")]
+        tag: String,
+    },
+
+    /// Write the whole history (content, pins, tags, times) for backup or migration
+    Export {
+        /// Output file, gzipped if it ends in .gz (default: JSON to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Write a timestamped, compressed copy of the history and delete old copies
+    Backup {
+        /// Directory for backups (default: [backup] dir, or backups/ in the storage dir)
+        #[arg(long)]
+        to: Option<PathBuf>,
+        /// How many backups to keep (default: [backup] keep, or 7)
+        #[arg(long)]
+        keep: Option<usize>,
+    },
+
+    /// Add entries from an export file, merging content already in history
+    Import {
+        /// File written by `clipstack export`, plain or gzipped (use - for stdin),
+        /// or the other manager's history with --from (default: its usual file)
+        #[arg(required_unless_present = "from")]
+        file: Option<PathBuf>,
+        /// Read another clipboard manager's history instead of an export
+        #[arg(long, value_enum)]
+        from: Option<importers::Source>,
+        /// Output format of the summary
+        #[arg(long, value_enum, default_value = "text")]
+        format: OutputFormat,
+    },
+
+    /// Add the history from another storage dir, e.g. one copied from another
+    /// machine (entries already here are merged, pins kept; the dir isn't changed)
+    Merge {
+        /// The other clipstack storage dir
+        dir: PathBuf,
+        /// Key the other history is encrypted with (default: this one's, if
+        /// `encrypt` is on)
+        #[arg(long)]
+        key: Option<PathBuf>,
+        /// Output format of the summary
+        #[arg(long, value_enum, default_value = "text")]
+        format: OutputFormat,
+    },
+
+    /// Copy a named pinned snippet to the clipboard
+    PasteSnippet {
+        /// Snippet name
+        #[arg(required_unless_present = "list")]
+        name: Option<String>,
+        /// Print all snippet names instead (for launchers and completion)
+        #[arg(long, conflicts_with = "name")]
+        list: bool,
+    },
+
+    /// Inspect and test daemon content filters
+    Filters {
+        #[command(subcommand)]
+        action: FiltersAction,
+    },
+
+    /// Manage entry tags
+    Tag {
+        #[command(subcommand)]
+        action: TagAction,
+    },
+
+    /// Manage named snippets, kept apart from the history and never pruned
+    Snippet {
+        #[command(subcommand)]
+        action: SnippetAction,
+    },
 
     /// Start a TCP server for remote clipboard (use with SSH reverse tunnel)
     Serve {
@@ -76,96 +543,538 @@ enum Commands {
         #[arg(value_enum)]
         shell: Shell,
     },
+
+    /// Print completion candidates (used by the completion scripts)
+    #[command(hide = true)]
+    Complete {
+        #[arg(value_enum)]
+        kind: completion::Candidates,
+    },
+}
+
+/// How commands report what they changed
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum OutputFormat {
+    Text,
+    /// A JSON summary on stdout, for scripts
+    Json,
+}
+
+#[derive(Subcommand)]
+enum DaemonAction {
+    /// Show whether the daemon is running (exit 1 if not)
+    Status,
+    /// Stop the running daemon
+    Stop,
+    /// Restart the daemon with its original arguments (or start it)
+    Restart,
+    /// Make the running daemon re-read its config file (same as SIGHUP)
+    Reload,
+    /// Write a systemd user unit that runs the daemon in the graphical session
+    InstallService {
+        /// Overwrite an existing unit file
+        #[arg(long)]
+        force: bool,
+
+        /// Write a system unit running `daemon system` instead (as root)
+        #[arg(long)]
+        system: bool,
+    },
+    /// Run as root: start a daemon for every user with a Wayland session
+    System {
+        /// Directory holding each user's history in `<root>/<user>`
+        #[arg(long, default_value = multiuser::DEFAULT_ROOT)]
+        root: PathBuf,
+
+        /// Ignore sessions of uids below this (system and display-manager users)
+        #[arg(long, default_value_t = 1000)]
+        min_uid: u32,
+    },
+}
+
+#[derive(Subcommand)]
+enum FiltersAction {
+    /// List configured ignore and allow filters
+    List,
+
+    /// Check whether a file's content would be skipped by the daemon
+    Test {
+        /// File to test (use - for stdin)
+        file: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum TagAction {
+    /// List all tags with entry counts
+    List,
+
+    /// Add tags to an entry
+    Add {
+        /// Entry id
+        id: String,
+        /// Tags to add
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+
+    /// Remove a tag from an entry
+    Remove {
+        /// Entry id
+        id: String,
+        /// Tag to remove
+        tag: String,
+    },
+
+    /// Rename a tag on every entry (merges if the new name exists)
+    Rename {
+        old: String,
+        new: String,
+    },
+
+    /// Remove a tag from every entry
+    Delete {
+        tag: String,
+    },
+
+    /// Merge several tags into one
+    Merge {
+        /// Tags to merge
+        #[arg(required = true)]
+        sources: Vec<String>,
+        /// Target tag
+        #[arg(long)]
+        into: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum TrashAction {
+    /// List deleted entries, most recently deleted first
+    List,
+
+    /// Put deleted entries back into the history
+    Restore {
+        /// Entry ids
+        #[arg(required = true)]
+        ids: Vec<String>,
+    },
+
+    /// Delete everything in the trash for good
+    Empty {
+        /// Output format of the summary
+        #[arg(long, value_enum, default_value = "text")]
+        format: OutputFormat,
+    },
 }
 
-fn main() -> Result<()> {
+#[derive(Subcommand)]
+enum ArchiveAction {
+    /// List archived entries, most recently archived first
+    List {
+        /// Number of entries to show (0 for all)
+        #[arg(short, long, default_value = "20")]
+        count: usize,
+        /// Output format
+        #[arg(long, value_enum, default_value = "text")]
+        format: OutputFormat,
+    },
+
+    /// Put archived entries back at the front of the history
+    Restore {
+        /// Entry ids
+        #[arg(required = true)]
+        ids: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum SnippetAction {
+    /// Add a snippet from stdin, a file or a history entry
+    Add {
+        /// Snippet name
+        name: String,
+        /// Read the content from this file instead of stdin
+        #[arg(long, conflicts_with = "entry")]
+        file: Option<PathBuf>,
+        /// Copy the content of this history entry (not a sensitive one, nor with encrypt on)
+        #[arg(long)]
+        entry: Option<String>,
+        /// Replace an existing snippet with the same name
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// List snippets with their first line
+    List {
+        /// Print names only (for launchers and completion)
+        #[arg(long)]
+        names: bool,
+    },
+
+    /// Copy a snippet to the clipboard, expanding its placeholders
+    Copy {
+        /// Snippet name
+        name: String,
+    },
+
+    /// Print a snippet's content as stored
+    Show {
+        /// Snippet name
+        name: String,
+    },
+
+    /// Remove a snippet
+    Remove {
+        /// Snippet name
+        name: String,
+    },
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            error::exit_code(&e)
+        }
+    }
+}
+
+fn run() -> Result<()> {
     let cli = Cli::parse();
 
     // Check dependencies on commands that need clipboard access
-    if matches!(
-        cli.command,
-        None | Some(Commands::Pick)
-            | Some(Commands::Copy)
-            | Some(Commands::Paste)
-            | Some(Commands::Daemon)
-    ) {
+    if cli.backend == clipboard::Backend::Wayland
+        && matches!(
+            cli.command,
+            None | Some(Commands::Pick { .. })
+                | Some(Commands::Copy { .. })
+                | Some(Commands::Paste)
+                | Some(Commands::Daemon { action: None, .. })
+                | Some(Commands::PasteSnippet { list: false, .. })
+                | Some(Commands::Snippet { action: SnippetAction::Copy { .. } })
+        )
+    {
         check_dependencies()?;
     }
 
-    // Determine max_entries: CLI > env > default (100)
-    let max_entries = cli
-        .max_entries
-        .map(|n| n as usize)
-        .or_else(|| {
-            std::env::var("CLIPSTACK_MAX_ENTRIES")
-                .ok()
-                .and_then(|s| s.parse().ok())
-        })
-        .unwrap_or(100)
+    // Before anything resolves the storage dir, lock file or config
+    profile::init(cli.profile.or_else(|| std::env::var("CLIPSTACK_PROFILE").ok()))?;
+
+    let config_path = cli.config.unwrap_or_else(config::Config::default_path);
+
+    // A broken config stops only commands whose behaviour it drives (and those
+    // compliance mode restricts); the rest, `recover` above all, run on defaults
+    let needs_config = matches!(
+        cli.command,
+        None | Some(Commands::Pick { .. })
+            | Some(Commands::Copy { .. })
+            | Some(Commands::Daemon { action: None, .. })
+            | Some(Commands::Serve { .. })
+            | Some(Commands::Export { .. })
+            | Some(Commands::Backup { .. })
+            | Some(Commands::CheckUpdate)
+    );
+    let (config, config_broken) = match config::Config::load_from(&config_path) {
+        Ok(config) => (config, false),
+        Err(e) if !needs_config => {
+            eprintln!("Warning: {:#}; using the default config", e);
+            (config::Config::default(), true)
+        }
+        Err(e) => return Err(e),
+    };
+
+    // Determine max_entries: CLI > env > config > default (100)
+    let max_entries_override = cli.max_entries.map(|n| n as usize).or_else(|| {
+        std::env::var("CLIPSTACK_MAX_ENTRIES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+    });
+    // Without the real config, keep every entry it might have allowed
+    let max_entries = max_entries_override
+        .or(config.max_entries)
+        .or(config_broken.then_some(storage::ABSOLUTE_MAX_ENTRIES))
+        .unwrap_or(storage::DEFAULT_MAX_ENTRIES)
         .clamp(1, 10000);
 
+    // Same precedence for the pin limit
+    let max_pinned_override = cli.max_pinned.map(|n| n as usize).or_else(|| {
+        std::env::var("CLIPSTACK_MAX_PINNED")
+            .ok()
+            .and_then(|s| s.parse().ok())
+    });
+    let max_pinned = max_pinned_override
+        .or(config.max_pinned)
+        .unwrap_or(storage::DEFAULT_MAX_PINNED);
+
     let storage_dir = cli.storage_dir.unwrap_or_else(storage::Storage::default_dir);
-    let storage = storage::Storage::new(storage_dir, max_entries)?;
+
+    // The daemon keeps a rotated log in the storage dir; other commands only use stderr
+    if matches!(cli.command, Some(Commands::Daemon { action: None, detach: false, .. })) {
+        std::fs::create_dir_all(&storage_dir)
+            .with_context(|| format!("Failed to create storage dir: {:?}", storage_dir))?;
+        let detached = std::env::var_os(daemon::DETACHED_ENV).is_some();
+        logging::init_daemon(cli.log_level, &storage_dir.join(logging::LOG_FILE_NAME), detached)?;
+    } else {
+        logging::init(cli.log_level)?;
+    }
+
+    let mut storage = storage::Storage::open(storage_dir, max_entries, &config.storage)?;
+    storage.set_trailing_newline(config.capture.trailing_newline);
+    storage.set_quotas(config.quotas.clone())?;
+    storage.set_max_total_size(cli.max_total_size.or(config.max_total_size))?;
+    storage.set_max_pinned(max_pinned)?;
+
+    let clip = cli.backend.open();
 
     match cli.command {
-        None | Some(Commands::Pick) => {
+        None => {
             // Default action: open picker
-            picker::pick_and_paste(storage)?;
+            picker::pick_and_paste(storage, config, &config_path, None, None, None, clip)?;
         }
 
-        Some(Commands::Copy) => {
+        Some(Commands::Pick {
+            context,
+            query,
+            select_first,
+            type_entry,
+            height,
+        }) => {
+            if select_first {
+                let query = query.unwrap_or_default();
+                picker::select_first(storage, config, context, &query, clip.as_ref())?;
+            } else {
+                let mut config = config;
+                config.picker.type_on_enter |= type_entry;
+                let query = query.as_deref();
+                let path = &config_path;
+                picker::pick_and_paste(storage, config, path, context, query, height, clip)?;
+            }
+        }
+
+        Some(Commands::Copy { context }) => {
             let mut content = String::new();
             io::stdin().read_to_string(&mut content)?;
 
-            clipboard::Clipboard::copy(&content)?;
-            storage.save_entry(&content)?;
-
-            eprintln!("Copied {} bytes", content.len());
+            daemon::mark_own_copy(&storage, &content);
+            clip.copy(&content)?;
+            if storage.is_paused() {
+                eprintln!("Copied {} bytes (history paused, not saved)", content.len());
+            } else {
+                let meta = storage::EntryMeta {
+                    context,
+                    ..Default::default()
+                };
+                storage.save_entry_with(&content, meta)?;
+                eprintln!("Copied {} bytes", content.len());
+            }
         }
 
         Some(Commands::Paste) => {
-            let content = clipboard::Clipboard::paste()?;
+            let content = clip.paste()?;
             io::stdout().write_all(content.as_bytes())?;
         }
 
-        Some(Commands::List { count }) => {
-            let index = storage.load_index()?;
+        Some(Commands::Get {
+            entry,
+            transform,
+            mime,
+        }) => {
+            let entry = storage.find_entry(&entry)?;
+            let id = entry.id.clone();
+            if let Some(mime) = mime {
+                let data = storage.load_representation(&entry, &mime)?;
+                storage.record_use(&id)?;
+                io::stdout().write_all(&data)?;
+                return Ok(());
+            }
+            let mut content = storage.load_content(&id)?;
+            storage.record_use(&id)?;
+            if let Some(t) = transform {
+                content = t
+                    .apply(&content)
+                    .with_context(|| format!("Cannot apply {} transform", t.label()))?;
+            }
+            io::stdout().write_all(content.as_bytes())?;
+        }
 
-            for entry in index.entries.iter().take(count) {
-                let time = util::format_relative_time(entry.timestamp);
-                let size = util::format_size(entry.size);
-                let preview: String = entry
-                    .preview
-                    .chars()
-                    .take(50)
-                    .collect::<String>()
-                    .replace('\n', " ");
+        Some(Commands::List {
+            count,
+            offset,
+            context,
+            sort,
+            reverse,
+            pinned,
+            kind,
+            since,
+            annotations,
+            columns,
+            header,
+        }) => {
+            let filter = query::Query {
+                context,
+                kinds: kind.into_iter().collect(),
+                pinned: pinned.then_some(true),
+                since: since.map(|d| util::now_millis().saturating_sub(util::duration_millis(d))),
+                annotations,
+                ..Default::default()
+            };
+            let mut entries = storage.query(&filter)?;
+            // Stable sorts over the index order keep pages stable between calls
+            query::sort(&mut entries, sort, reverse);
+            print_entries(entries, &config, offset, count, &columns, header);
+        }
+
+        Some(Commands::Search {
+            terms,
+            count,
+            offset,
+            columns,
+            header,
+        }) => {
+            let (filter, text) = query::parse_search(&terms.join(" "));
+            let words: Vec<String> = text.split_whitespace().map(str::to_lowercase).collect();
+            let has_words = |text: &str| {
+                let text = text.to_lowercase();
+                words.iter().all(|word| text.contains(word.as_str()))
+            };
+            let mut entries = storage.query(&filter)?;
+            // Words not in the preview may still be further into the content
+            entries.retain(|e| {
+                has_words(&e.preview) || storage.load_content(&e.id).is_ok_and(|c| has_words(&c))
+            });
+            print_entries(entries, &config, offset, count, &columns, header);
+        }
 
-                println!("{:>5} [{:>6}] {}", time, size, preview);
+        Some(Commands::Delete { ids }) => {
+            // Check every id first so a typo doesn't leave a partial delete
+            for id in &ids {
+                storage.get_entry(id)?;
+            }
+            for id in &ids {
+                storage.delete_entry(id)?;
+            }
+            if storage.trash_enabled() {
+                println!("Moved {} entries to the trash (clipstack trash restore ID)", ids.len());
+            } else {
+                println!("Deleted {} entries", ids.len());
             }
+        }
+
+        Some(Commands::Trash { action }) => {
+            run_trash_action(&storage, &config, action)?;
+        }
+
+        Some(Commands::Archive { action }) => {
+            run_archive_action(&storage, &config, action)?;
+        }
 
-            if index.entries.len() > count {
-                println!("... and {} more", index.entries.len() - count);
+        Some(Commands::Clear { format }) => {
+            let summary = storage.clear()?;
+            match format {
+                OutputFormat::Json => println!("{}", serde_json::to_string(&summary)?),
+                OutputFormat::Text => println!(
+                    "Clipboard history cleared ({} entries, {} freed)",
+                    summary.removed.len(),
+                    util::format_size(summary.reclaimed_bytes as usize)
+                ),
             }
         }
 
-        Some(Commands::Clear) => {
-            storage.clear()?;
-            println!("Clipboard history cleared");
+        Some(Commands::Daemon {
+            action: Some(action),
+            seats,
+            all_seats,
+            ..
+        }) => {
+            // Bake non-default paths into the daemon's arguments so it sees the same history.
+            // Per-user daemons get their storage dir from the supervisor instead.
+            let per_user = matches!(
+                action,
+                DaemonAction::System { .. } | DaemonAction::InstallService { system: true, .. }
+            );
+            let mut args = Vec::new();
+            if !per_user && *storage.base_dir() != storage::Storage::default_dir() {
+                args.push("--storage-dir".to_string());
+                args.push(storage.base_dir().to_string_lossy().into_owned());
+            }
+            if config_path != config::Config::default_path() {
+                args.push("--config".to_string());
+                args.push(config_path.to_string_lossy().into_owned());
+            }
+            if let Some(profile) = profile::current() {
+                args.push(format!("--profile={}", profile));
+            }
+            if let Some(n) = cli.max_entries {
+                args.push(format!("--max-entries={}", n));
+            }
+            if let Some(size) = cli.max_total_size {
+                args.push(format!("--max-total-size={}", size));
+            }
+            if let Some(n) = cli.max_pinned {
+                args.push(format!("--max-pinned={}", n));
+            }
+            for seat in seats {
+                args.push(format!("--seat={}", seat));
+            }
+            if all_seats {
+                args.push("--all-seats".to_string());
+            }
+            if cli.backend != clipboard::Backend::Wayland {
+                args.push(format!("--backend={}", cli.backend.name()));
+            }
+            run_daemon_action(action, &args, storage.base_dir())?;
+        }
+
+        Some(Commands::Daemon {
+            action: None,
+            detach: true,
+            pid_file,
+            ..
+        }) => {
+            let log_path = storage.base_dir().join(logging::LOG_FILE_NAME);
+            let pid = daemon::detach(pid_file.as_deref(), &log_path)?;
+            println!("Started daemon (pid {}), logging to {}", pid, log_path.display());
         }
 
-        Some(Commands::Daemon) => {
+        Some(Commands::Daemon {
+            action: None,
+            seats,
+            all_seats,
+            pid_file,
+            ..
+        }) => {
+            let seats = if all_seats {
+                daemon::SeatSelection::All
+            } else if seats.is_empty() {
+                daemon::SeatSelection::Default
+            } else {
+                daemon::SeatSelection::Named(seats)
+            };
+
             // Use custom storage dir if provided, but always use global lock file
-            let daemon = daemon::Daemon::new(Some(storage.base_dir().to_path_buf()), max_entries)?;
+            let storage_dir = Some(storage.base_dir().to_path_buf());
+            let mut daemon = daemon::Daemon::new(storage_dir, max_entries, &config.storage)?
+                .with_config_path(config_path)
+                .with_fixed_max_entries(max_entries_override.is_some())
+                .with_fixed_max_total_size(cli.max_total_size)?
+                .with_fixed_max_pinned(max_pinned_override)?
+                .with_seats(seats)
+                .with_clipboard(clip)
+                .with_pid_file(pid_file);
 
-            // Handle Ctrl+C
-            let running = daemon.stop_handle();
-            ctrlc_handler(running);
+            // SIGINT/SIGTERM stop the loop, SIGHUP reloads the config, SIGUSR1 dumps metrics
+            signal_handler(daemon.stop_handle(), daemon.reload_handle(), daemon.dump_handle())?;
 
             daemon.run()?;
         }
 
-        Some(Commands::Stats) => {
+        Some(Commands::Stats {
+            forecast,
+            daemon,
+            usage,
+        }) => {
             let index = storage.load_index()?;
             let total_size: usize = index.entries.iter().map(|e| e.size).sum();
             let pinned_count = index.entries.iter().filter(|e| e.pinned).count();
@@ -174,14 +1083,44 @@ fn main() -> Result<()> {
             // Determine source of max_entries setting
             let source = if std::env::var("CLIPSTACK_MAX_ENTRIES").is_ok() {
                 " (env)"
+            } else if config.max_entries.is_some() {
+                " (config)"
             } else {
                 ""
             };
 
             println!("Entries:     {}", index.entries.len());
-            println!("  Pinned:    {} (protected)", pinned_count);
+            let max_pinned = storage.max_pinned();
+            let pin_room = max_pinned.saturating_sub(pinned_count);
+            println!(
+                "  Pinned:    {}/{} (protected, {} more allowed)",
+                pinned_count, max_pinned, pin_room
+            );
             println!("  Regular:   {}/{}{}", unpinned_count, storage.max_entries(), source);
-            println!("Total size:  {}", util::format_size(total_size));
+            // Regular entries by kind, against their quota if one is set
+            let kinds: Vec<String> = kind::EntryKind::ALL
+                .iter()
+                .filter_map(|&k| {
+                    let count = index.entries.iter().filter(|e| !e.pinned && e.kind == k).count();
+                    match storage.quotas().get(&k) {
+                        Some(cap) => Some(format!("{} {}/{}", k.name(), count, cap)),
+                        None if count > 0 => Some(format!("{} {}", k.name(), count)),
+                        None => None,
+                    }
+                })
+                .collect();
+            if !kinds.is_empty() {
+                println!("  Kinds:     {}", kinds.join(", "));
+            }
+            match storage.max_total_size() {
+                Some(budget) => println!(
+                    "Total size:  {} of {} ({}%)",
+                    util::format_size(total_size),
+                    util::format_size(budget),
+                    total_size * 100 / budget.max(1)
+                ),
+                None => println!("Total size:  {}", util::format_size(total_size)),
+            }
 
             if let Some(oldest) = index.entries.last() {
                 println!("Oldest:      {}", util::format_relative_time(oldest.timestamp));
@@ -189,16 +1128,117 @@ fn main() -> Result<()> {
             if let Some(newest) = index.entries.first() {
                 println!("Newest:      {}", util::format_relative_time(newest.timestamp));
             }
+
+            let skipped = storage.skip_counts();
+            if skipped.total() > 0 {
+                println!("Skipped:     {}", skipped.total());
+                for (label, count) in [
+                    ("Too small", skipped.too_small),
+                    ("Too large", skipped.too_large),
+                    ("Pattern", skipped.ignore_pattern),
+                    ("App", skipped.ignored_app),
+                    ("Password", skipped.password_manager),
+                    ("Allowlist", skipped.not_allowed),
+                ] {
+                    if count > 0 {
+                        println!("  {:<10} {}", format!("{}:", label), count);
+                    }
+                }
+            }
+
+            if forecast {
+                let max = storage.max_entries();
+                let projection = forecast::forecast(&index.entries, max, util::now_millis());
+                forecast::print(projection.as_ref(), max);
+            }
+
+            if daemon {
+                println!();
+                print_daemon_metrics()?;
+            }
+
+            if usage {
+                println!();
+                let stats = usage::read(&storage.base_dir().join(usage::USAGE_FILE_NAME));
+                usage::print(&stats, config.picker.usage_stats);
+            }
+        }
+
+        Some(Commands::Digest {
+            yesterday,
+            date,
+            format,
+        }) => {
+            let today = chrono::Local::now().date_naive();
+            let date = match date {
+                Some(date) => date,
+                None if yesterday => today.pred_opt().unwrap_or(today),
+                None => today,
+            };
+            let index = storage.load_index()?;
+            let digest = digest::digest(&index.entries, date, config.mask_secrets);
+            match format {
+                OutputFormat::Json => println!("{}", serde_json::to_string(&digest)?),
+                OutputFormat::Text => digest::print(&digest),
+            }
         }
 
         Some(Commands::Status) => {
-            print_status(&storage)?;
+            print_status(&storage, &config)?;
         }
 
-        Some(Commands::Recover) => {
+        Some(Commands::Bench { size, index }) => match index {
+            Some(entries) => run_index_bench(entries)?,
+            None => run_bench(size),
+        },
+
+        Some(Commands::CheckUpdate) => {
+            if config.compliance.enabled {
+                let path = &config_path;
+                anyhow::bail!(
+                    "check-update is disabled in compliance mode ([compliance] in {:?})",
+                    path
+                );
+            }
+            let current = env!("CARGO_PKG_VERSION");
+            let release = update::fetch_latest()?;
+            match update::is_newer(current, &release.tag_name) {
+                Some(false) => {
+                    println!("clipstack {} is up to date (latest: {})", current, release.tag_name);
+                    return Ok(());
+                }
+                Some(true) => {
+                    println!("clipstack {} is available (you have {})", release.tag_name, current)
+                }
+                None => println!("Latest release: {} (you have {})", release.tag_name, current),
+            }
+
+            let highlights =
+                update::highlights(release.body.as_deref().unwrap_or(""), update::MAX_HIGHLIGHTS);
+            if !highlights.is_empty() {
+                println!();
+                println!("Highlights:");
+                for item in highlights {
+                    println!("  - {}", item);
+                }
+            }
+            println!();
+            println!("Download: {}", release.html_url);
+        }
+
+        Some(Commands::Recover { format }) => {
             match storage.attempt_recovery() {
-                Ok(count) => {
-                    println!("Recovery complete. Recovered {} entries.", count);
+                Ok(summary) if format == OutputFormat::Json => {
+                    println!("{}", serde_json::to_string(&summary)?);
+                }
+                Ok(summary) => {
+                    println!("Recovery complete. Recovered {} entries.", summary.remaining);
+                    if !summary.restored.is_empty() {
+                        println!("Restored {} orphaned entries", summary.restored.len());
+                    }
+                    if !summary.removed.is_empty() {
+                        println!("Dropped {} duplicates", summary.removed.len());
+                    }
                 }
                 Err(e) => {
                     eprintln!("Recovery failed: {}", e);
@@ -207,12 +1247,392 @@ fn main() -> Result<()> {
             }
         }
 
+        Some(Commands::Profiles) => {
+            let current = profile::current();
+            let names = profile::list(&config);
+            for name in std::iter::once(None).chain(names.iter().map(|n| Some(n.as_str()))) {
+                let marker = if name == current { '*' } else { ' ' };
+                let dir = storage::Storage::profile_dir(name);
+                println!("{} {:<16} {}", marker, profile::label(name), dir.display());
+            }
+        }
+
+        Some(Commands::Verify { repair, format }) => {
+            let summary = storage.verify(repair)?;
+            match format {
+                OutputFormat::Json => println!("{}", serde_json::to_string(&summary)?),
+                OutputFormat::Text => print_verify_summary(&summary),
+            }
+            if !summary.is_clean() && repair.is_none() {
+                std::process::exit(1);
+            }
+        }
+
+        Some(Commands::Log {
+            count,
+            id,
+            content,
+            action,
+            since,
+            format,
+        }) => {
+            let matcher = content.as_deref().map(|c| storage.hash_matcher(c));
+            let cutoff = since.map(|d| util::now_millis().saturating_sub(util::duration_millis(d)));
+            let mut records = storage.audit_records()?;
+            records.retain(|r| {
+                id.as_ref().is_none_or(|id| r.id == *id)
+                    && matcher
+                        .as_ref()
+                        .is_none_or(|m| r.hash.as_ref().is_some_and(|h| m.matches(h)))
+                    && (action.is_empty() || action.contains(&r.action))
+                    && cutoff.is_none_or(|c| r.time >= c)
+            });
+            records.reverse();
+            records.truncate(count);
+            match format {
+                OutputFormat::Json => println!("{}", serde_json::to_string(&records)?),
+                OutputFormat::Text => {
+                    if records.is_empty() && !config.storage.audit_log {
+                        eprintln!("The audit log is off; set audit_log = true under [storage]");
+                    }
+                    for record in &records {
+                        println!("{}", format_audit_record(record));
+                    }
+                }
+            }
+        }
+
+        Some(Commands::Vacuum {
+            compress_older_than,
+            format,
+        }) => {
+            let summary = storage.vacuum(compress_older_than)?;
+            match format {
+                OutputFormat::Json => println!("{}", serde_json::to_string(&summary)?),
+                OutputFormat::Text => println!(
+                    "Removed {} orphaned files, compressed {} entries, {} freed",
+                    summary.orphans.len(),
+                    summary.compressed.len(),
+                    util::format_size(summary.reclaimed_bytes as usize)
+                ),
+            }
+        }
+
+        Some(Commands::Prune {
+            older_than,
+            keep,
+            max_size,
+            dry_run,
+            format,
+        }) => {
+            let criteria = storage::PruneCriteria {
+                older_than,
+                keep,
+                max_size,
+            };
+            let summary = storage.prune_with(criteria, dry_run)?;
+            match format {
+                OutputFormat::Json => println!("{}", serde_json::to_string(&summary)?),
+                OutputFormat::Text => print_prune_summary(&storage, &summary),
+            }
+        }
+
+        Some(Commands::Pause { duration }) => {
+            storage.pause(duration)?;
+            match duration {
+                Some(d) => println!("History capture paused for {}", util::format_duration(d)),
+                None => println!("History capture paused (run 'clipstack resume' to continue)"),
+            }
+        }
+
+        Some(Commands::Resume) => {
+            if storage.resume()? {
+                println!("History capture resumed");
+            } else {
+                println!("History capture was not paused");
+            }
+        }
+
+        Some(Commands::Pin { id, name }) => {
+            match name {
+                Some(name) => {
+                    storage.set_name(&id, &name)?;
+                    println!("Pinned {} as '{}'", id, name);
+                }
+                None => {
+                    storage.get_entry(&id)?;
+                    storage.set_pinned(&id, true)?;
+                    println!("Pinned {}", id);
+                }
+            }
+        }
+
+        Some(Commands::Unpin { id }) => {
+            storage.get_entry(&id)?;
+            storage.set_pinned(&id, false)?;
+            println!("Unpinned {}", id);
+        }
+
+        Some(Commands::Edit { id }) => {
+            storage.get_entry(&id)?;
+            let content = storage.load_content(&id)?;
+            let edited = editor::edit(&content)?;
+            if edited == content {
+                println!("No changes to {}", id);
+            } else {
+                let entry = storage.update_entry(&id, &edited)?;
+                println!("Updated {} ({})", id, util::format_size(entry.size));
+            }
+        }
+
+        Some(Commands::PasteSnippet { name, list }) => {
+            if list {
+                for name in storage.snippet_names()? {
+                    println!("{}", name);
+                }
+            } else {
+                let name = name.expect("clap requires name without --list");
+                let entry = storage
+                    .find_by_name(&name)?
+                    .with_context(|| format!("No pinned snippet named '{}'", name))?;
+                let content = transform::expand_template(&storage.load_content(&entry.id)?);
+                guard::confirm(&config.paste_guard, &content)?;
+                daemon::record_own_copy(&storage, &content, &[entry.id])?;
+                clip.copy(&content)?;
+                eprintln!("Copied snippet '{}' ({} bytes)", name, content.len());
+            }
+        }
+
+        Some(Commands::Split { id, by, max }) => {
+            let entry = storage.get_entry(&id)?;
+            let content = storage.load_content(&id)?;
+            let pieces = transform::split(&content, by);
+
+            // More pieces than the history holds would prune the first ones right away
+            let limit = max.min(storage.max_entries());
+            if pieces.len() < 2 {
+                anyhow::bail!("Nothing to split: entry has {} piece(s)", pieces.len());
+            }
+            if pieces.len() > limit {
+                anyhow::bail!(
+                    "Entry has {} pieces; split is limited to {} (see --max and --max-entries)",
+                    pieces.len(),
+                    limit
+                );
+            }
+
+            // Save in reverse so the first piece ends up on top of the history
+            let meta = storage::EntryMeta {
+                source_app: entry.source_app,
+                window_title: entry.window_title,
+                seat: entry.seat,
+                context: entry.context,
+            };
+            for piece in pieces.iter().rev() {
+                storage.save_entry_with(piece, meta.clone())?;
+            }
+            println!("Split into {} entries", pieces.len());
+        }
+
+        Some(Commands::Join { ids, separator }) => {
+            let parts = ids
+                .iter()
+                .map(|id| storage.load_content(id))
+                .collect::<Result<Vec<_>>>()?;
+            let entry = storage.save_entry(&parts.join(&separator))?;
+            println!("Joined {} entries into {}", parts.len(), entry.id);
+        }
+
+        Some(Commands::ImportShellHistory {
+            last,
+            shell,
+            file,
+            tag,
+        }) => {
+            let tag = storage::Storage::normalize_tag(&tag)?;
+            let shell = shell.unwrap_or_else(shell_history::HistoryShell::detect);
+            let path = file.unwrap_or_else(|| shell.default_path());
+            let commands = shell_history::recent(&shell_history::read(shell, &path)?, last);
+
+            // More commands than the history holds would prune the first ones right away
+            if commands.len() > storage.max_entries() {
+                anyhow::bail!(
+                    "{} commands would not fit in a history of {} (see --last and --max-entries)",
+                    commands.len(),
+                    storage.max_entries()
+                );
+            }
+
+            // Oldest first, so the most recent command ends up on top
+            let mut added = 0;
+            for command in &commands {
+                let (entry, existed) = storage.save_entry_checked(command, Default::default())?;
+                storage.add_tag(&entry.id, &tag)?;
+                added += usize::from(!existed);
+            }
+            println!(
+                "Imported {} commands from {:?} ({} already in history), tagged '{}'",
+                commands.len(),
+                path,
+                commands.len() - added,
+                tag
+            );
+        }
+
+        Some(Commands::Export { output }) => {
+            if config.compliance.enabled {
+                let path = &config_path;
+                anyhow::bail!("export is disabled in compliance mode ([compliance] in {:?})", path);
+            }
+            let entries = storage.export_entries()?;
+            let count = entries.len();
+            export::write(&export::Export::new(entries), output.as_deref())?;
+            if let Some(path) = output {
+                println!("Exported {} entries to {:?}", count, path);
+            }
+        }
+
+        Some(Commands::Backup { to, keep }) => {
+            if config.compliance.enabled {
+                let path = &config_path;
+                anyhow::bail!("backup is disabled in compliance mode ([compliance] in {:?})", path);
+            }
+            let dir = to
+                .or_else(|| config.backup.dir.clone())
+                .unwrap_or_else(|| backup::default_dir(&storage));
+            let keep = keep.unwrap_or(config.backup.keep);
+            let cipher = config.storage.cipher()?;
+            let backup = backup::create(&storage, &dir, keep, cipher.as_ref())?;
+            println!("Backed up {} entries to {:?}", backup.entries, backup.path);
+            if !backup.removed.is_empty() {
+                println!("Removed {} older backups (keeping {})", backup.removed.len(), keep);
+            }
+        }
+
+        Some(Commands::Import { file, from, format }) => {
+            let entries = match (from, file) {
+                (Some(source), file) => importers::to_entries(source.read(file.as_deref())?),
+                (None, Some(file)) => {
+                    let cipher = config.storage.cipher()?;
+                    export::read(&file, cipher.as_ref())?.entries
+                }
+                (None, None) => unreachable!("clap requires a file without --from"),
+            };
+            let summary = storage.import_entries(entries)?;
+            print_import_summary(&summary, format)?;
+        }
+
+        Some(Commands::Merge { dir, key, format }) => {
+            let cipher = match key {
+                Some(key) => Some(crypto::Cipher::load(&key)?),
+                None => config.storage.cipher()?,
+            };
+            let summary = storage.merge_from(&dir, cipher)?;
+            print_import_summary(&summary, format)?;
+        }
+
+        Some(Commands::Filters { action }) => {
+            let filters = filters::Filters::from_config(&config)?;
+            match action {
+                FiltersAction::List => {
+                    if filters.patterns().next().is_none()
+                        && filters.ignored_apps().next().is_none()
+                        && filters.min_size().is_none()
+                        && filters.max_size().is_none()
+                        && !filters.allowlist_active()
+                    {
+                        println!("No ignore filters configured in {:?}", config_path);
+                    }
+                    if let Some(min) = filters.min_size() {
+                        println!("min_size {}", util::format_size(min));
+                    }
+                    if let Some(max) = filters.max_size() {
+                        println!("max_size {}", util::format_size(max));
+                    }
+                    for pattern in filters.patterns() {
+                        println!("pattern  {}", pattern);
+                    }
+                    for app in filters.ignored_apps() {
+                        println!("app      {}", app);
+                    }
+                    for pattern in filters.allow_patterns() {
+                        println!("allow    {}", pattern);
+                    }
+                    for app in filters.allowed_apps() {
+                        println!("allow    app {}", app);
+                    }
+                }
+                FiltersAction::Test { file } => {
+                    let mut content = String::new();
+                    if file.as_os_str() == "-" {
+                        io::stdin().read_to_string(&mut content)?;
+                    } else {
+                        content = std::fs::read_to_string(&file)
+                            .with_context(|| format!("Failed to read {:?}", file))?;
+                    }
+                    if filters.too_small(content.len()) || filters.too_large(content.len()) {
+                        println!(
+                            "Would be skipped: {} is outside the size limits",
+                            util::format_size(content.len())
+                        );
+                        std::process::exit(1);
+                    }
+                    if let Some(pattern) = filters.ignore_match(&content) {
+                        println!("Would be skipped: matches '{}'", pattern);
+                        std::process::exit(1);
+                    }
+                    if !filters.allows(&content, None) {
+                        let apps: Vec<&str> = filters.allowed_apps().collect();
+                        if apps.is_empty() {
+                            println!("Would be skipped: matches no allow pattern");
+                        } else {
+                            println!(
+                                "Would be skipped unless copied from an allowed app ({})",
+                                apps.join(", ")
+                            );
+                        }
+                        std::process::exit(1);
+                    }
+                    println!("Would be saved: no ignore filter matches");
+                }
+            }
+        }
+
+        Some(Commands::Annotate { id, set, unset }) => {
+            let entry = if set.is_empty() && unset.is_empty() {
+                storage.get_entry(&id)?
+            } else {
+                storage.annotate(&id, &set, &unset)?
+            };
+            for (key, value) in &entry.annotations {
+                println!("{}={}", key, value);
+            }
+        }
+
+        Some(Commands::Tag { action }) => {
+            run_tag_action(&storage, action)?;
+        }
+
+        Some(Commands::Snippet { action }) => {
+            run_snippet_action(&storage, &config, clip.as_ref(), action)?;
+        }
+
         Some(Commands::Serve { port }) => {
-            serve_clipboard(storage, port)?;
+            if config.compliance.enabled {
+                let path = &config_path;
+                anyhow::bail!("serve is disabled in compliance mode ([compliance] in {:?})", path);
+            }
+            serve_clipboard(storage, port, clip.as_ref())?;
         }
 
         Some(Commands::Completions { shell }) => {
-            generate_completions(shell);
+            generate_completions(shell)?;
+        }
+
+        Some(Commands::Complete { kind }) => {
+            for line in completion::candidates(&storage, kind)? {
+                println!("{}", line);
+            }
         }
     }
 
@@ -243,13 +1663,201 @@ fn check_dependencies() -> Result<()> {
     }
 }
 
+/// Ask the running daemon to publish fresh metrics (SIGUSR1) and print them
+fn print_daemon_metrics() -> Result<()> {
+    use daemon::{Daemon, DaemonState};
+
+    let DaemonState::Running(pid) = Daemon::state() else {
+        println!("Daemon:      not running");
+        return Ok(());
+    };
+    let requested = util::now_millis();
+    if let Some(pid) = pid {
+        daemon::signal_process(pid, "USR1")?;
+        // Published on the daemon's next poll
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(2);
+        while std::time::Instant::now() < deadline
+            && Daemon::metrics().is_none_or(|m| m.updated < requested)
+        {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+    }
+
+    match Daemon::metrics() {
+        Some(m) => {
+            metrics::print(&m);
+            if m.updated < requested {
+                println!("  (published {})", util::format_relative_time(m.updated));
+            }
+        }
+        None => println!("Daemon:      running, no metrics published (older version?)"),
+    }
+    Ok(())
+}
+
+/// Print entries from `offset` on for `list` and `search`: `columns`
+/// tab-separated, or the default layout with a count of the rest
+fn print_entries(
+    mut entries: Vec<storage::ClipEntry>,
+    config: &config::Config,
+    offset: usize,
+    count: usize,
+    columns: &[query::Column],
+    header: bool,
+) {
+    if config.mask_secrets {
+        for entry in &mut entries {
+            if kind::looks_like_credential(&entry.preview) {
+                entry.preview = kind::MASK.to_string();
+            }
+        }
+    }
+
+    let page = entries.iter().skip(offset).take(count);
+    if !columns.is_empty() {
+        // Column output is for other tools: no footer
+        if header {
+            println!("{}", query::header(columns));
+        }
+        for entry in page {
+            println!("{}", query::row(entry, columns));
+        }
+        return;
+    }
+    for entry in page {
+        let time = util::format_relative_time(entry.timestamp);
+        let size = util::format_size(entry.size);
+        let preview: String = entry
+            .preview
+            .chars()
+            .take(50)
+            .collect::<String>()
+            .replace('\n', " ");
+
+        println!("{:>5} [{:>6}] {}", time, size, preview);
+    }
+
+    let shown = offset.saturating_add(count);
+    if entries.len() > shown {
+        println!("... and {} more", entries.len() - shown);
+    }
+}
+
+/// One line of `clipstack log`: local time, action, id and a short content hash
+fn format_audit_record(record: &audit::AuditRecord) -> String {
+    let time = chrono::DateTime::from_timestamp_millis(record.time)
+        .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%dT%H:%M:%S").to_string())
+        .unwrap_or_default();
+    // Enough of the hash to tell entries apart at a glance
+    let hash: String = record.hash.as_deref().unwrap_or("-").chars().take(19).collect();
+    format!("{}  {:<8}  {:<13}  {}", time, record.action.name(), record.id, hash)
+}
+
+/// Report of `clipstack prune`
+fn print_prune_summary(storage: &storage::Storage, summary: &storage::PruneSummary) {
+    if summary.removed.is_empty() {
+        println!("Nothing to prune");
+        return;
+    }
+    let verb = if summary.dry_run { "Would remove" } else { "Removed" };
+    println!(
+        "{} {} entries ({} of content), {} of them expired",
+        verb,
+        summary.removed.len(),
+        util::format_size(summary.content_bytes as usize),
+        summary.expired
+    );
+    // The entries are still there to show
+    if summary.dry_run {
+        for entry in summary.removed.iter().filter_map(|id| storage.get_entry(id).ok()) {
+            let age = util::format_relative_time(entry.timestamp);
+            println!("  {}  {:>8}  {}", entry.id, age, entry.preview);
+        }
+    }
+}
+
+/// Report of `clipstack import` and `clipstack merge`
+fn print_import_summary(summary: &storage::ImportSummary, format: OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string(summary)?),
+        OutputFormat::Text => {
+            print!("Imported {} new entries", summary.added);
+            if summary.merged > 0 {
+                print!(", merged {} already in history", summary.merged);
+            }
+            println!();
+            if summary.pruned > 0 {
+                println!(
+                    "Pruned {} older entries to stay within the limits (see --max-entries)",
+                    summary.pruned
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Report of `clipstack verify`: broken entries one per line, then what was repaired
+fn print_verify_summary(summary: &storage::VerifySummary) {
+    let problems = [
+        ("missing", &summary.missing),
+        ("mismatched", &summary.mismatched),
+        ("unreadable", &summary.unreadable),
+    ];
+    for (problem, ids) in problems {
+        for id in ids {
+            println!("{:<11} {}", problem, id);
+        }
+    }
+    if summary.is_clean() {
+        println!("Checked {} entries, all content present and matching", summary.checked);
+        return;
+    }
+    println!(
+        "Checked {} entries: {} missing, {} mismatched, {} unreadable",
+        summary.checked,
+        summary.missing.len(),
+        summary.mismatched.len(),
+        summary.unreadable.len()
+    );
+    if !summary.rehashed.is_empty() || !summary.dropped.is_empty() {
+        println!(
+            "Rehashed {} entries, dropped {}",
+            summary.rehashed.len(),
+            summary.dropped.len()
+        );
+    } else if !summary.missing.is_empty() || !summary.mismatched.is_empty() {
+        println!("Run 'clipstack verify --repair rehash' (or --repair drop) to fix them");
+    }
+}
+
+/// One-line summary of why the daemon stopped saving
+fn describe_degraded(degraded: &daemon::Degraded) -> String {
+    let retry_in = (degraded.retry_at - util::now_millis()).max(0) as u64;
+    format!(
+        "{} failed saves since {} (last: {}); retrying in {}",
+        degraded.failures,
+        util::format_relative_time(degraded.since),
+        degraded.last_error,
+        util::format_duration(std::time::Duration::from_millis(retry_in))
+    )
+}
+
 /// Print daemon and system status
-fn print_status(storage: &storage::Storage) -> Result<()> {
+fn print_status(storage: &storage::Storage, config: &config::Config) -> Result<()> {
     // Check daemon status
     let daemon_running = daemon::Daemon::is_running();
 
     if daemon_running {
-        println!("Daemon:  \x1b[32mrunning\x1b[0m");
+        match daemon::Daemon::state() {
+            daemon::DaemonState::Running(Some(pid)) => {
+                println!("Daemon:  \x1b[32mrunning\x1b[0m (pid {})", pid)
+            }
+            _ => println!("Daemon:  \x1b[32mrunning\x1b[0m"),
+        }
+        if let Some(degraded) = daemon::Daemon::degraded() {
+            println!("         \x1b[31mdegraded\x1b[0m: {}", describe_degraded(&degraded));
+        }
     } else {
         println!("Daemon:  \x1b[33mnot running\x1b[0m");
         println!("         Start with: clipstack daemon");
@@ -270,6 +1878,21 @@ fn print_status(storage: &storage::Storage) -> Result<()> {
         println!("Latest:  {}", util::format_relative_time(newest.timestamp));
     }
 
+    match storage.pause_state().map(|p| p.remaining()) {
+        Some(Some(remaining)) => println!(
+            "Capture: \x1b[33mpaused\x1b[0m (resumes in {})",
+            util::format_duration(remaining)
+        ),
+        Some(None) => println!("Capture: \x1b[33mpaused\x1b[0m (until 'clipstack resume')"),
+        None => println!("Capture: \x1b[32mactive\x1b[0m"),
+    }
+    let schedule = &config.daemon.schedule;
+    if !schedule.is_empty() {
+        let now = chrono::Local::now().naive_local();
+        let state = if schedule.active_at(now) { "inside" } else { "outside" };
+        println!("         schedule: {} ({} now)", schedule.describe(), state);
+    }
+
     println!();
 
     // Configuration info
@@ -281,6 +1904,34 @@ fn print_status(storage: &storage::Storage) -> Result<()> {
         "default"
     };
     println!("  Max entries: {} ({})", max_entries, source);
+    if let Some(profile) = profile::current() {
+        println!("  Profile:     {}", profile);
+    }
+    if let Some(budget) = storage.max_total_size() {
+        println!("  Max size:    {}", util::format_size(budget));
+    }
+    if let Some(max_age) = storage.max_age() {
+        println!("  Max age:     {}", util::format_duration(max_age));
+    }
+    let storage_config = &config.storage;
+    if let Some(max) = storage_config.max_entry_size {
+        let policy = storage_config.oversize.name();
+        println!("  Max entry:   {} ({} larger copies)", util::format_size(max), policy);
+    }
+    if storage_config.encrypt {
+        println!("  Encryption:  on (key {:?})", storage_config.key_path());
+    }
+    if storage_config.audit_log {
+        println!("  Audit log:   {:?}", storage.base_dir().join(audit::AUDIT_FILE_NAME));
+    }
+    if config.compliance.enabled {
+        // What `Config::apply_compliance` enforces and which commands it turns away
+        let max_age = util::format_duration(config::COMPLIANCE_MAX_AGE);
+        println!("  Compliance:  on");
+        println!("               encryption, audit log and mask_secrets on, archive off");
+        println!("               max_age and trash_retention at most {}", max_age);
+        println!("               no scheduled backups; serve, export, backup and check-update off");
+    }
 
     println!();
 
@@ -295,14 +1946,451 @@ fn print_status(storage: &storage::Storage) -> Result<()> {
     Ok(())
 }
 
+/// Execute a `clipstack tag` subcommand
+fn run_tag_action(storage: &storage::Storage, action: TagAction) -> Result<()> {
+    match action {
+        TagAction::List => {
+            let counts = storage.tag_counts()?;
+            if counts.is_empty() {
+                println!("No tags");
+            }
+            for (tag, count) in counts {
+                println!("{:>5}  {}", count, tag);
+            }
+        }
+        TagAction::Add { id, tags } => {
+            for tag in &tags {
+                storage.add_tag(&id, tag)?;
+            }
+            println!("Tagged {} with {}", id, tags.join(", "));
+        }
+        TagAction::Remove { id, tag } => {
+            if storage.remove_tag(&id, &tag)? {
+                println!("Removed '{}' from {}", tag, id);
+            } else {
+                println!("Entry {} has no tag '{}'", id, tag);
+            }
+        }
+        TagAction::Rename { old, new } => {
+            let changed = storage.rename_tag(&old, &new)?;
+            println!("Renamed '{}' to '{}' on {} entries", old, new, changed);
+        }
+        TagAction::Delete { tag } => {
+            let changed = storage.delete_tag(&tag)?;
+            println!("Removed '{}' from {} entries", tag, changed);
+        }
+        TagAction::Merge { sources, into } => {
+            let changed = storage.merge_tags(&sources, &into)?;
+            println!("Merged {} into '{}' on {} entries", sources.join(", "), into, changed);
+        }
+    }
+    Ok(())
+}
+
+fn run_trash_action(
+    storage: &storage::Storage,
+    config: &config::Config,
+    action: TrashAction,
+) -> Result<()> {
+    match action {
+        TrashAction::List => {
+            let trash = storage.trash()?;
+            if trash.is_empty() {
+                println!("Trash is empty");
+            }
+            for item in trash {
+                let e = &item.entry;
+                let preview: String =
+                    kind::masked(&e.preview, config.mask_secrets).chars().take(50).collect();
+                println!(
+                    "{}  deleted {:>7} [{:>6}] {}",
+                    e.id,
+                    util::format_relative_time(item.deleted_at),
+                    util::format_size(e.size),
+                    preview
+                );
+            }
+        }
+        TrashAction::Restore { ids } => {
+            for id in ids {
+                let entry = storage.restore_entry(&id)?;
+                println!("Restored {} ({})", id, util::format_size(entry.size));
+            }
+        }
+        TrashAction::Empty { format } => {
+            let summary = storage.empty_trash()?;
+            match format {
+                OutputFormat::Json => println!("{}", serde_json::to_string(&summary)?),
+                OutputFormat::Text => println!(
+                    "Trash emptied ({} entries, {} freed)",
+                    summary.removed.len(),
+                    util::format_size(summary.reclaimed_bytes as usize)
+                ),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run_archive_action(
+    storage: &storage::Storage,
+    config: &config::Config,
+    action: ArchiveAction,
+) -> Result<()> {
+    match action {
+        ArchiveAction::List { count, format } => {
+            let mut items = storage.archived_entries()?;
+            if count > 0 {
+                items.truncate(count);
+            }
+            if format == OutputFormat::Json {
+                let entries: Vec<&storage::ClipEntry> = items.iter().map(|i| &i.entry).collect();
+                println!("{}", serde_json::to_string(&entries)?);
+                return Ok(());
+            }
+            if items.is_empty() && !config.storage.archive {
+                println!("Archive is empty (set archive = true under [storage] to fill it)");
+            } else if items.is_empty() {
+                println!("Archive is empty");
+            }
+            for item in items {
+                let e = &item.entry;
+                let preview: String =
+                    kind::masked(&e.preview, config.mask_secrets).chars().take(50).collect();
+                println!(
+                    "{}  copied {:>7} [{:>6}] {}",
+                    e.id,
+                    util::format_relative_time(e.timestamp),
+                    util::format_size(e.size),
+                    preview
+                );
+            }
+        }
+        ArchiveAction::Restore { ids } => {
+            for id in ids {
+                let summary = storage.restore_archived(&id)?;
+                if summary.merged > 0 {
+                    println!("Restored {} into the entry already holding its content", id);
+                } else {
+                    println!("Restored {}", id);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run_snippet_action(
+    storage: &storage::Storage,
+    config: &config::Config,
+    clip: &dyn clipboard::ClipboardBackend,
+    action: SnippetAction,
+) -> Result<()> {
+    let snippets = storage.snippets();
+    let find = |name: &str| -> Result<snippets::Snippet> {
+        snippets.get(name)?.with_context(|| format!("No snippet named '{}'", name))
+    };
+    match action {
+        SnippetAction::Add {
+            name,
+            file,
+            entry,
+            force,
+        } => {
+            let content = match (file, entry) {
+                (Some(file), _) => std::fs::read_to_string(&file)
+                    .with_context(|| format!("Failed to read {:?}", file))?,
+                (None, Some(id)) => {
+                    // snippets.json is plain JSON, so history that is kept
+                    // encrypted or hidden must not be copied into it
+                    anyhow::ensure!(
+                        !config.storage.encrypt,
+                        "--entry is unavailable with encrypt on: snippets.json is not encrypted"
+                    );
+                    anyhow::ensure!(
+                        !storage.get_entry(&id)?.sensitive,
+                        "Entry {} holds a secret; snippets.json would keep it in plain text",
+                        id
+                    );
+                    storage.load_content(&id)?
+                }
+                (None, None) => {
+                    let mut content = String::new();
+                    io::stdin().read_to_string(&mut content)?;
+                    content
+                }
+            };
+            let verb = if snippets.add(&name, &content, force)? { "Replaced" } else { "Added" };
+            println!("{} snippet '{}' ({})", verb, name.trim(), util::format_size(content.len()));
+        }
+        SnippetAction::List { names } => {
+            let all = snippets.list()?;
+            if all.is_empty() && !names {
+                println!("No snippets");
+            }
+            for snippet in all {
+                if names {
+                    println!("{}", snippet.name);
+                } else {
+                    println!("{:<20}  {}", snippet.name, snippet.first_line());
+                }
+            }
+        }
+        SnippetAction::Copy { name } => {
+            let content = transform::expand_template(&find(&name)?.content);
+            guard::confirm(&config.paste_guard, &content)?;
+            daemon::record_own_copy(storage, &content, &[])?;
+            clip.copy(&content)?;
+            eprintln!("Copied snippet '{}' ({} bytes)", name, content.len());
+        }
+        SnippetAction::Show { name } => {
+            io::stdout().write_all(find(&name)?.content.as_bytes())?;
+        }
+        SnippetAction::Remove { name } => {
+            if snippets.remove(&name)? {
+                println!("Removed snippet '{}'", name);
+            } else {
+                println!("No snippet named '{}'", name);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Run a `clipstack daemon` subcommand: status, stop, restart, reload or service setup
+fn run_daemon_action(action: DaemonAction, args: &[String], storage_dir: &Path) -> Result<()> {
+    use daemon::{Daemon, DaemonState};
+
+    const STOP_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+    match action {
+        DaemonAction::Status => match Daemon::state() {
+            DaemonState::Running(pid) => {
+                match pid {
+                    Some(pid) => println!("running (pid {})", pid),
+                    None => println!("running"),
+                }
+                if let Some(degraded) = Daemon::degraded() {
+                    println!("degraded: {}", describe_degraded(&degraded));
+                }
+            }
+            DaemonState::Stale(pid) => {
+                println!("not running (stale lock from pid {}, cleaned on next start)", pid);
+                std::process::exit(1);
+            }
+            DaemonState::NotRunning => {
+                println!("not running");
+                std::process::exit(1);
+            }
+        },
+
+        DaemonAction::Stop => match Daemon::state() {
+            DaemonState::Running(Some(pid)) => {
+                daemon::stop_process(pid, STOP_TIMEOUT)?;
+                println!("Stopped daemon (pid {})", pid);
+            }
+            DaemonState::Running(None) => {
+                anyhow::bail!("Daemon is running but its lock file has no pid; stop it manually")
+            }
+            DaemonState::Stale(_) | DaemonState::NotRunning => println!("Daemon is not running"),
+        },
+
+        DaemonAction::Reload => match Daemon::state() {
+            DaemonState::Running(Some(pid)) => {
+                daemon::signal_process(pid, "HUP")?;
+                println!("Asked daemon (pid {}) to reload its config", pid);
+            }
+            DaemonState::Running(None) => {
+                anyhow::bail!("Daemon is running but its lock file has no pid; send SIGHUP manually")
+            }
+            DaemonState::Stale(_) | DaemonState::NotRunning => {
+                anyhow::bail!("Daemon is not running")
+            }
+        },
+
+        DaemonAction::Restart => {
+            // Relaunch with the running daemon's own argv so custom paths are kept
+            let mut argv = None;
+            if let DaemonState::Running(Some(pid)) = Daemon::state() {
+                if systemd::manages_pid(pid) {
+                    let status = Command::new("systemctl")
+                        .args(["--user", "restart", &systemd::unit_name()])
+                        .status()
+                        .context("Failed to run systemctl")?;
+                    if !status.success() {
+                        anyhow::bail!("systemctl --user restart {} failed", systemd::unit_name());
+                    }
+                    println!("Restarted {}", systemd::unit_name());
+                    return Ok(());
+                }
+                argv = daemon::process_cmdline(pid);
+                daemon::stop_process(pid, STOP_TIMEOUT)?;
+            }
+
+            let argv = match argv {
+                Some(argv) => argv,
+                None => {
+                    let exe = std::env::current_exe().context("Cannot locate the clipstack binary")?;
+                    let mut argv = vec![exe.to_string_lossy().into_owned(), "daemon".to_string()];
+                    argv.extend(args.iter().cloned());
+                    argv
+                }
+            };
+
+            let child = Command::new(&argv[0])
+                .args(&argv[1..])
+                .stdin(std::process::Stdio::null())
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null())
+                .spawn()
+                .with_context(|| format!("Failed to start {}", argv[0]))?;
+            println!("Started daemon (pid {})", child.id());
+        }
+
+        DaemonAction::InstallService {
+            force,
+            system: false,
+        } => {
+            let exe = std::env::current_exe().context("Cannot locate the clipstack binary")?;
+            let unit = systemd::unit_file(&exe, args, storage_dir);
+            let path = systemd::unit_path();
+            systemd::install_service(&path, &unit, force)?;
+
+            println!("Wrote {}", path.display());
+            println!("Enable it with:");
+            println!("  systemctl --user daemon-reload");
+            println!("  systemctl --user enable --now {}", systemd::unit_name());
+        }
+
+        DaemonAction::InstallService {
+            force,
+            system: true,
+        } => {
+            let exe = std::env::current_exe().context("Cannot locate the clipstack binary")?;
+            let root = Path::new(multiuser::DEFAULT_ROOT);
+            let unit = systemd::system_unit_file(&exe, root, args);
+            let path = systemd::system_unit_path();
+            systemd::install_service(&path, &unit, force)?;
+
+            println!("Wrote {}", path.display());
+            println!("Enable it with:");
+            println!("  systemctl daemon-reload");
+            println!("  systemctl enable --now {}", systemd::SYSTEM_UNIT_NAME);
+            println!("Point users' clipstack at their history, e.g. in /etc/profile.d:");
+            println!("  export {}={}/$USER", storage::STORAGE_DIR_ENV, root.display());
+        }
+
+        DaemonAction::System { root, min_uid } => multiuser::run(&root, min_uid, args)?,
+    }
+    Ok(())
+}
+
+/// Seconds per call of `f`, repeated until the total is long enough to be measurable
+fn time_per_run(mut f: impl FnMut()) -> f64 {
+    use std::time::{Duration, Instant};
+
+    const MIN_RUNTIME: Duration = Duration::from_millis(500);
+
+    let start = Instant::now();
+    let mut runs = 0u32;
+    while runs == 0 || start.elapsed() < MIN_RUNTIME {
+        f();
+        runs += 1;
+    }
+    start.elapsed().as_secs_f64() / runs as f64
+}
+
+/// Time each hash algorithm over `size` bytes of text and print throughput
+fn run_bench(size: usize) {
+    use util::HashAlgorithm;
+
+    let pattern = "fn main() { println!(\"clipstack\"); } // lorem ipsum dolor sit amet\n";
+    let content: String = pattern.chars().cycle().take(size.max(1)).collect();
+
+    println!("Hashing {} of content:", util::format_size(content.len()));
+    let mut baseline: Option<f64> = None;
+    for &algo in HashAlgorithm::ALL {
+        let per_run = time_per_run(|| {
+            std::hint::black_box(algo.digest(std::hint::black_box(&content)));
+        });
+        let throughput = content.len() as f64 / per_run / (1024.0 * 1024.0);
+
+        let speedup = match baseline {
+            Some(base) => format!("  ({:.1}x)", base / per_run),
+            None => String::new(),
+        };
+        baseline.get_or_insert(per_run);
+        println!(
+            "  {:<7} {:>9.1} MB/s  {:>9.3}ms per entry{}",
+            algo.name(),
+            throughput,
+            per_run * 1000.0,
+            speedup
+        );
+    }
+}
+
+/// Compare parsing a synthetic index from JSON (with the version check every
+/// load does) and from the MessagePack copy
+fn run_index_bench(entries: usize) -> Result<()> {
+    let index = storage::ClipIndex {
+        max_entries: entries,
+        entries: (0..entries as i64)
+            .map(|i| {
+                let content = format!("cargo build --release --features entry-{}", i);
+                let meta = storage::EntryMeta {
+                    source_app: Some("kitty".into()),
+                    window_title: Some(format!("~/src/project-{}", i % 20)),
+                    ..Default::default()
+                };
+                let hash = util::HashAlgorithm::Blake3.hash_string(&content);
+                let mut entry = backend::new_entry(1_700_000_000_000 + i, &content, hash, meta);
+                entry.tags = vec!["shell".into()];
+                entry
+            })
+            .collect(),
+        ..Default::default()
+    };
+    let json = serde_json::to_string_pretty(&index)?;
+    let binary = rmp_serde::to_vec_named(&index)?;
+
+    println!("Loading an index of {} entries:", entries);
+    let json_load = time_per_run(|| {
+        let value = serde_json::from_str(std::hint::black_box(&json)).unwrap();
+        std::hint::black_box(storage::ClipIndex::from_json(value).unwrap());
+    });
+    let binary_load = time_per_run(|| {
+        let data = std::hint::black_box(&binary);
+        std::hint::black_box(rmp_serde::from_slice::<storage::ClipIndex>(data).unwrap());
+    });
+    for (format, data_len, per_run) in
+        [("json", json.len(), json_load), ("msgpack", binary.len(), binary_load)]
+    {
+        let speedup = match format {
+            "json" => String::new(),
+            _ => format!("  ({:.1}x)", json_load / per_run),
+        };
+        println!(
+            "  {:<8} {:>9}  {:>9.3}ms per load{}",
+            format,
+            util::format_size(data_len),
+            per_run * 1000.0,
+            speedup
+        );
+    }
+    Ok(())
+}
+
 /// Generate shell completions
-fn generate_completions(shell: Shell) {
-    let mut cmd = Cli::command();
-    let name = cmd.get_name().to_string();
-    generate(shell, &mut cmd, name, &mut io::stdout());
+fn generate_completions(shell: Shell) -> Result<()> {
+    completion::generate(shell, &mut Cli::command(), &mut io::stdout())
 }
 
-fn serve_clipboard(storage: storage::Storage, port: u16) -> Result<()> {
+fn serve_clipboard(
+    storage: storage::Storage,
+    port: u16,
+    clip: &dyn clipboard::ClipboardBackend,
+) -> Result<()> {
     let addr = format!("127.0.0.1:{}", port);
     let listener = TcpListener::bind(&addr)?;
     eprintln!("Clipboard server listening on {}", addr);
@@ -322,10 +2410,19 @@ fn serve_clipboard(storage: storage::Storage, port: u16) -> Result<()> {
                     continue;
                 }
 
+                daemon::mark_own_copy(&storage, &content);
+                if storage.is_paused() {
+                    if let Err(e) = clip.copy(&content) {
+                        eprintln!("Warning: couldn't copy to system clipboard: {}", e);
+                    }
+                    eprintln!("Received {} bytes (history paused, not saved)", content.len());
+                    continue;
+                }
+
                 // Save to storage and clipboard
                 match storage.save_entry(&content) {
                     Ok(entry) => {
-                        if let Err(e) = clipboard::Clipboard::copy(&content) {
+                        if let Err(e) = clip.copy(&content) {
                             eprintln!("Warning: couldn't copy to system clipboard: {}", e);
                         }
                         // Use chars().take() for safe Unicode truncation
@@ -346,9 +2443,23 @@ fn serve_clipboard(storage: storage::Storage, port: u16) -> Result<()> {
     Ok(())
 }
 
-fn ctrlc_handler(running: std::sync::Arc<std::sync::atomic::AtomicBool>) {
-    ctrlc::set_handler(move || {
-        running.store(false, std::sync::atomic::Ordering::SeqCst);
-    })
-    .expect("Error setting Ctrl-C handler");
+fn signal_handler(
+    running: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    reload: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    dump: std::sync::Arc<std::sync::atomic::AtomicBool>,
+) -> Result<()> {
+    use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM, SIGUSR1};
+
+    let mut signals = signal_hook::iterator::Signals::new([SIGINT, SIGTERM, SIGHUP, SIGUSR1])
+        .context("Failed to install signal handlers")?;
+    std::thread::spawn(move || {
+        for signal in signals.forever() {
+            match signal {
+                SIGHUP => reload.store(true, std::sync::atomic::Ordering::SeqCst),
+                SIGUSR1 => dump.store(true, std::sync::atomic::Ordering::SeqCst),
+                _ => running.store(false, std::sync::atomic::Ordering::SeqCst),
+            }
+        }
+    });
+    Ok(())
 }
@@ -0,0 +1,134 @@
+use crate::util;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+/// Daemon counters since it started, published next to the lock file for
+/// `clipstack stats --daemon` and logged on SIGUSR1
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Metrics {
+    pub pid: u32,
+    /// Unix ms the daemon started
+    pub started: i64,
+    /// Unix ms these numbers were published
+    pub updated: i64,
+    pub polls: u64,
+    /// New entries written to history
+    pub saved: u64,
+    /// Copies of content already in history (moved to the front instead), or
+    /// coalesced with a capture moments earlier
+    pub duplicates: u64,
+    /// Captures rejected by filters, size limits or password manager hints
+    pub skipped: u64,
+    /// Failed saves
+    pub errors: u64,
+    /// Time taken to read every watched selection, in microseconds
+    pub poll_last_us: u64,
+    pub poll_max_us: u64,
+    pub poll_total_us: u64,
+}
+
+impl Metrics {
+    pub fn new(pid: u32, now_ms: i64) -> Self {
+        Self {
+            pid,
+            started: now_ms,
+            updated: now_ms,
+            ..Default::default()
+        }
+    }
+
+    pub fn record_poll(&mut self, elapsed: Duration) {
+        let us = elapsed.as_micros() as u64;
+        self.polls += 1;
+        self.poll_last_us = us;
+        self.poll_max_us = self.poll_max_us.max(us);
+        self.poll_total_us += us;
+    }
+
+    pub fn poll_avg(&self) -> Duration {
+        Duration::from_micros(self.poll_total_us.checked_div(self.polls).unwrap_or(0))
+    }
+
+    pub fn uptime(&self) -> Duration {
+        Duration::from_millis((self.updated - self.started).max(0) as u64)
+    }
+
+    /// One line for the daemon log
+    pub fn summary(&self) -> String {
+        format!(
+            "up {}, {} polls, {} saved, {} duplicates, {} skipped, {} errors, \
+             poll avg {:?} max {:?}",
+            util::format_duration(self.uptime()),
+            self.polls,
+            self.saved,
+            self.duplicates,
+            self.skipped,
+            self.errors,
+            self.poll_avg(),
+            Duration::from_micros(self.poll_max_us)
+        )
+    }
+}
+
+pub fn read(path: &Path) -> Option<Metrics> {
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+pub fn write(path: &Path, metrics: &Metrics) -> Result<()> {
+    let json = serde_json::to_string(metrics)?;
+    fs::write(path, json).with_context(|| format!("Failed to write {:?}", path))
+}
+
+/// Print the `clipstack stats --daemon` section
+pub fn print(metrics: &Metrics) {
+    println!("Daemon:      pid {}, up {}", metrics.pid, util::format_duration(metrics.uptime()));
+    println!("  Saved:     {}", metrics.saved);
+    println!("  Duplicate: {}", metrics.duplicates);
+    println!("  Skipped:   {}", metrics.skipped);
+    println!("  Errors:    {}", metrics.errors);
+    println!(
+        "  Polls:     {} (avg {:?}, max {:?}, last {:?})",
+        metrics.polls,
+        metrics.poll_avg(),
+        Duration::from_micros(metrics.poll_max_us),
+        Duration::from_micros(metrics.poll_last_us)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_poll_latency() {
+        let mut metrics = Metrics::new(1, 0);
+        assert_eq!(metrics.poll_avg(), Duration::ZERO);
+
+        metrics.record_poll(Duration::from_micros(100));
+        metrics.record_poll(Duration::from_micros(300));
+        assert_eq!(metrics.polls, 2);
+        assert_eq!(metrics.poll_avg(), Duration::from_micros(200));
+        assert_eq!(metrics.poll_max_us, 300);
+        assert_eq!(metrics.poll_last_us, 300);
+    }
+
+    #[test]
+    fn test_metrics_roundtrip() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("clipstack.metrics");
+        assert_eq!(read(&path), None);
+
+        let mut metrics = Metrics::new(42, 1000);
+        metrics.saved = 3;
+        metrics.updated = 61_000;
+        write(&path, &metrics).unwrap();
+
+        let loaded = read(&path).unwrap();
+        assert_eq!(loaded, metrics);
+        assert_eq!(loaded.uptime(), Duration::from_secs(60));
+    }
+}
@@ -0,0 +1,265 @@
+use crate::daemon;
+use crate::storage::STORAGE_DIR_ENV;
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::ffi::{CStr, OsStr};
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Where per-user histories live by default (`daemon system --root`)
+pub const DEFAULT_ROOT: &str = "/var/lib/clipstack";
+
+/// Per-user runtime dirs (`/run/user/<uid>`), where Wayland sockets show a session
+const RUNTIME_ROOT: &str = "/run/user";
+
+/// How often sessions are rescanned
+const SCAN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long a user daemon gets to exit after SIGTERM
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A user logged into a Wayland session
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Session {
+    uid: u32,
+    /// Socket name in the runtime dir, e.g. "wayland-0"
+    wayland_display: String,
+}
+
+#[derive(Debug)]
+struct User {
+    name: String,
+    uid: u32,
+    gid: u32,
+    home: PathBuf,
+}
+
+/// A daemon started for one session
+struct UserDaemon {
+    child: Child,
+    session: Session,
+}
+
+/// `clipstack daemon system`: run as root, start a daemon as each user with a
+/// Wayland session and stop it when the session ends. Every user's history is
+/// kept in `root/<name>`, owned by them with mode 0700; the daemon runs with
+/// their uid and gid only, and its lock and state stay in their runtime dir.
+/// `args` (config, limits, seats) are passed on to every user daemon.
+pub fn run(root: &Path, min_uid: u32, args: &[String]) -> Result<()> {
+    // SAFETY: geteuid has no preconditions
+    if unsafe { libc::geteuid() } != 0 {
+        bail!("daemon system manages other users' daemons and must run as root");
+    }
+    prepare_root(root)?;
+    let exe = std::env::current_exe().context("Cannot locate the clipstack binary")?;
+
+    let stop_requested = Arc::new(AtomicBool::new(false));
+    for signal in [signal_hook::consts::SIGINT, signal_hook::consts::SIGTERM] {
+        signal_hook::flag::register(signal, stop_requested.clone())
+            .context("Failed to install signal handlers")?;
+    }
+    let running = || !stop_requested.load(Ordering::SeqCst);
+
+    log::info!("clipstack system daemon started, histories in {:?}", root);
+    let mut daemons: HashMap<u32, UserDaemon> = HashMap::new();
+    while running() {
+        let sessions = find_sessions(Path::new(RUNTIME_ROOT), min_uid);
+        daemons.retain(|uid, d| {
+            if let Ok(Some(status)) = d.child.try_wait() {
+                log::warn!("[system] Daemon for uid {} exited ({}), restarting", uid, status);
+                return false;
+            }
+            if sessions.contains(&d.session) {
+                return true;
+            }
+            log::info!("[system] Session of uid {} ended, stopping its daemon", uid);
+            stop(&mut d.child);
+            false
+        });
+
+        for session in sessions {
+            if daemons.contains_key(&session.uid) {
+                continue;
+            }
+            match start(&exe, root, &session, args) {
+                Ok(child) => {
+                    let (uid, pid) = (session.uid, child.id());
+                    log::info!("[system] Started daemon for uid {} (pid {})", uid, pid);
+                    daemons.insert(session.uid, UserDaemon { child, session });
+                }
+                Err(e) => log::error!("[system] uid {}: {:#}", session.uid, e),
+            }
+        }
+
+        let next_scan = Instant::now() + SCAN_INTERVAL;
+        while running() && Instant::now() < next_scan {
+            std::thread::sleep(Duration::from_millis(200));
+        }
+    }
+
+    for d in daemons.values_mut() {
+        stop(&mut d.child);
+    }
+    log::info!("clipstack system daemon stopped");
+    Ok(())
+}
+
+/// Users with a Wayland socket in their runtime dir, lowest uid first
+fn find_sessions(runtime_root: &Path, min_uid: u32) -> Vec<Session> {
+    let Ok(dirs) = fs::read_dir(runtime_root) else {
+        return Vec::new();
+    };
+    let mut sessions: Vec<Session> = dirs
+        .flatten()
+        .filter_map(|dir| {
+            let uid: u32 = dir.file_name().to_str()?.parse().ok()?;
+            if uid < min_uid {
+                return None;
+            }
+            let mut sockets: Vec<String> = fs::read_dir(dir.path())
+                .ok()?
+                .flatten()
+                .filter_map(|f| f.file_name().into_string().ok())
+                .filter(|name| name.starts_with("wayland-") && !name.ends_with(".lock"))
+                .collect();
+            sockets.sort();
+            let wayland_display = sockets.into_iter().next()?;
+            Some(Session {
+                uid,
+                wayland_display,
+            })
+        })
+        .collect();
+    sessions.sort_by_key(|s| s.uid);
+    sessions
+}
+
+/// The root is root-owned and 0711: users reach their own dir but can't list others
+fn prepare_root(root: &Path) -> Result<()> {
+    fs::create_dir_all(root).with_context(|| format!("Failed to create {:?}", root))?;
+    let meta = fs::symlink_metadata(root)?;
+    if !meta.is_dir() || meta.uid() != 0 {
+        bail!("{:?} must be a directory owned by root", root);
+    }
+    fs::set_permissions(root, fs::Permissions::from_mode(0o711))?;
+    Ok(())
+}
+
+/// Create or fix `root/<name>`: a real directory owned by the user, mode 0700
+fn prepare_storage(root: &Path, user: &User) -> Result<PathBuf> {
+    let dir = root.join(&user.name);
+    match fs::symlink_metadata(&dir) {
+        Ok(meta) if !meta.is_dir() => bail!("{:?} is not a directory, refusing to use it", dir),
+        Ok(_) => {}
+        Err(_) => fs::create_dir(&dir).with_context(|| format!("Failed to create {:?}", dir))?,
+    }
+    std::os::unix::fs::chown(&dir, Some(user.uid), Some(user.gid))?;
+    fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))?;
+    Ok(dir)
+}
+
+fn start(exe: &Path, root: &Path, session: &Session, args: &[String]) -> Result<Child> {
+    let user = lookup_user(session.uid).context("No passwd entry")?;
+    let storage_dir = prepare_storage(root, &user)?;
+    let runtime_dir = Path::new(RUNTIME_ROOT).join(user.uid.to_string());
+
+    // Nothing of root's environment is passed on; std drops supplementary
+    // groups when switching uid
+    Command::new(exe)
+        .arg("daemon")
+        .args(args)
+        .env_clear()
+        .env("HOME", &user.home)
+        .env("USER", &user.name)
+        .env("LOGNAME", &user.name)
+        .env("PATH", std::env::var_os("PATH").unwrap_or_else(|| "/usr/bin:/bin".into()))
+        .env("XDG_RUNTIME_DIR", &runtime_dir)
+        .env("WAYLAND_DISPLAY", &session.wayland_display)
+        .env("DBUS_SESSION_BUS_ADDRESS", format!("unix:path={}/bus", runtime_dir.display()))
+        .env(STORAGE_DIR_ENV, &storage_dir)
+        .env(daemon::DETACHED_ENV, "1")
+        .current_dir(&storage_dir)
+        .uid(user.uid)
+        .gid(user.gid)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Failed to start daemon for {}", user.name))
+}
+
+fn stop(child: &mut Child) {
+    let _ = daemon::signal_process(child.id(), "TERM");
+    let deadline = Instant::now() + STOP_TIMEOUT;
+    while Instant::now() < deadline {
+        if let Ok(Some(_)) = child.try_wait() {
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+fn lookup_user(uid: u32) -> Option<User> {
+    let mut buf = vec![0 as libc::c_char; 16 * 1024];
+    // SAFETY: passwd is plain data; getpwuid_r fills it with pointers into buf,
+    // which outlives every use below
+    unsafe {
+        let mut pwd: libc::passwd = std::mem::zeroed();
+        let mut result = std::ptr::null_mut();
+        let rc = libc::getpwuid_r(uid, &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result);
+        if rc != 0 || result.is_null() {
+            return None;
+        }
+        Some(User {
+            name: CStr::from_ptr(pwd.pw_name).to_string_lossy().into_owned(),
+            uid,
+            gid: pwd.pw_gid,
+            home: PathBuf::from(OsStr::from_bytes(CStr::from_ptr(pwd.pw_dir).to_bytes())),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_sessions() {
+        let dir = TempDir::new().unwrap();
+        let add = |path: &str| {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        };
+        add("1001/wayland-1");
+        add("1001/wayland-1.lock");
+        add("1000/wayland-0.lock");
+        add("1000/wayland-0");
+        add("1002/bus");
+        add("42/wayland-0");
+        add("gdm/wayland-0");
+
+        let sessions = find_sessions(dir.path(), 1000);
+        let found: Vec<(u32, &str)> =
+            sessions.iter().map(|s| (s.uid, s.wayland_display.as_str())).collect();
+        assert_eq!(found, [(1000, "wayland-0"), (1001, "wayland-1")]);
+        assert!(find_sessions(&dir.path().join("missing"), 0).is_empty());
+    }
+
+    #[test]
+    fn test_lookup_user() {
+        let root = lookup_user(0).expect("uid 0 has a passwd entry");
+        assert_eq!(root.name, "root");
+        assert_eq!(root.gid, 0);
+    }
+}
@@ -0,0 +1,115 @@
+use crate::config::NotificationConfig;
+use crate::util;
+use notify_rust::Notification;
+use std::time::Instant;
+
+/// Preview length in the notification body
+const BODY_PREVIEW_LEN: usize = 60;
+
+/// Sends desktop notifications for new captures, honoring per-source
+/// switches and the rate limit from config
+pub struct Notifier {
+    config: NotificationConfig,
+    last_sent: Option<Instant>,
+}
+
+impl Notifier {
+    pub fn new(config: NotificationConfig) -> Self {
+        Self {
+            config,
+            last_sent: None,
+        }
+    }
+
+    /// Swap in a reloaded config, keeping the rate limit window
+    pub fn set_config(&mut self, config: NotificationConfig) {
+        self.config = config;
+    }
+
+    /// Notify about a saved entry if enabled for this source and not rate limited
+    pub fn entry_saved(&mut self, source: &str, size: usize, preview: &str) {
+        if !self.should_notify(source, Instant::now()) {
+            return;
+        }
+
+        let summary = format!("Copied {}", util::format_size(size));
+        if let Err(e) = Notification::new()
+            .appname("clipstack")
+            .summary(&summary)
+            .body(&body_preview(preview))
+            .show()
+        {
+            log::warn!("[notify] Failed to show notification: {}", e);
+        }
+    }
+
+    fn should_notify(&mut self, source: &str, now: Instant) -> bool {
+        let source_enabled = match source {
+            "primary" => self.config.primary,
+            _ => self.config.clipboard,
+        };
+        if !self.config.enabled || !source_enabled {
+            return false;
+        }
+        if let Some(last) = self.last_sent
+            && now.duration_since(last) < self.config.min_interval
+        {
+            return false;
+        }
+        self.last_sent = Some(now);
+        true
+    }
+}
+
+fn body_preview(preview: &str) -> String {
+    let line = preview.split_whitespace().collect::<Vec<_>>().join(" ");
+    if line.chars().count() > BODY_PREVIEW_LEN {
+        let truncated: String = line.chars().take(BODY_PREVIEW_LEN).collect();
+        format!("{}...", truncated)
+    } else {
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn enabled() -> NotificationConfig {
+        NotificationConfig {
+            enabled: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_disabled_by_default() {
+        let mut notifier = Notifier::new(NotificationConfig::default());
+        assert!(!notifier.should_notify("clipboard", Instant::now()));
+    }
+
+    #[test]
+    fn test_per_source_switches() {
+        let mut notifier = Notifier::new(enabled());
+        assert!(!notifier.should_notify("primary", Instant::now()));
+        assert!(notifier.should_notify("clipboard", Instant::now()));
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let mut notifier = Notifier::new(enabled());
+        let start = Instant::now();
+
+        assert!(notifier.should_notify("clipboard", start));
+        assert!(!notifier.should_notify("clipboard", start + Duration::from_secs(1)));
+        assert!(notifier.should_notify("clipboard", start + Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn test_body_preview() {
+        assert_eq!(body_preview("fn main() {\n    println!()\n}"), "fn main() { println!() }");
+        let long = "x".repeat(100);
+        assert_eq!(body_preview(&long), format!("{}...", "x".repeat(BODY_PREVIEW_LEN)));
+    }
+}
@@ -0,0 +1,87 @@
+use crate::config::Config;
+use crate::storage::{Storage, DEFAULT_MAX_ENTRIES, DEFAULT_MAX_PINNED};
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Directory under the default storage dir holding one storage dir per profile
+pub const PROFILES_DIR: &str = "profiles";
+
+/// Names that would be confused with the default history or the system unit
+const RESERVED: [&str; 2] = ["default", "system"];
+
+static CURRENT: OnceLock<Option<String>> = OnceLock::new();
+
+/// Select the profile this process runs as (`--profile`). Only the first call counts.
+pub fn init(profile: Option<String>) -> Result<()> {
+    if let Some(name) = &profile {
+        validate(name)?;
+    }
+    let _ = CURRENT.set(profile);
+    Ok(())
+}
+
+/// The profile this process runs as, None for the default history
+pub fn current() -> Option<&'static str> {
+    CURRENT.get().and_then(Option::as_deref)
+}
+
+pub fn validate(name: &str) -> Result<()> {
+    anyhow::ensure!(
+        !name.is_empty()
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+        "Invalid profile name '{}': use letters, digits, - and _",
+        name
+    );
+    anyhow::ensure!(!RESERVED.contains(&name), "Profile name '{}' is reserved", name);
+    Ok(())
+}
+
+/// Profiles configured under `[profiles]` or with a storage dir already, sorted
+pub fn list(config: &Config) -> Vec<String> {
+    let mut names: Vec<String> = config.profiles.keys().cloned().collect();
+    if let Ok(dirs) = fs::read_dir(Storage::profile_dir(None).join(PROFILES_DIR)) {
+        for dir in dirs.flatten().filter(|d| d.path().is_dir()) {
+            let name = dir.file_name().to_string_lossy().into_owned();
+            if validate(&name).is_ok() && !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names.sort();
+    names
+}
+
+/// Label for status lines and titles
+pub fn label(profile: Option<&str>) -> &str {
+    profile.unwrap_or(RESERVED[0])
+}
+
+/// Load a profile's config and open its history, for switching profiles in the
+/// picker. CLI overrides of the running process don't carry over.
+pub fn open(config_path: &Path, profile: Option<&str>) -> Result<(Config, Storage)> {
+    let config = Config::load_profile(config_path, profile)?;
+    let max_entries = config.max_entries.unwrap_or(DEFAULT_MAX_ENTRIES);
+    let mut storage = Storage::open(Storage::profile_dir(profile), max_entries, &config.storage)?;
+    storage.set_trailing_newline(config.capture.trailing_newline);
+    storage.set_quotas(config.quotas.clone())?;
+    storage.set_max_total_size(config.max_total_size)?;
+    storage.set_max_pinned(config.max_pinned.unwrap_or(DEFAULT_MAX_PINNED))?;
+    Ok((config, storage))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        for name in ["work", "client-a", "v2_test"] {
+            assert!(validate(name).is_ok(), "{}", name);
+        }
+        for name in ["", "../etc", "two words", "default", "system"] {
+            assert!(validate(name).is_err(), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,501 @@
+use crate::kind::EntryKind;
+use crate::storage::ClipEntry;
+use crate::util;
+use regex::Regex;
+
+/// Which entries a listing or search shows; unset fields match everything.
+/// `Storage::query` runs it over the history, and both `list` flags and the
+/// search syntax of the picker and `search` (`parse_search`) compile to it.
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    /// Only entries copied with this context
+    pub context: Option<String>,
+    /// Only entries of one of these kinds
+    pub kinds: Vec<EntryKind>,
+    /// Only pinned (true) or only unpinned (false) entries
+    pub pinned: Option<bool>,
+    /// Only entries first captured at or after this unix ms
+    pub since: Option<i64>,
+    /// Only entries first captured before this unix ms
+    pub until: Option<i64>,
+    /// Only entries of more than this many bytes
+    pub larger_than: Option<usize>,
+    /// Only entries of fewer than this many bytes
+    pub smaller_than: Option<usize>,
+    /// Only entries carrying one of these tags, lowercase
+    pub tags: Vec<String>,
+    /// Only entries copied from an app whose id contains one of these, lowercase
+    pub apps: Vec<String>,
+    /// Only entries carrying all of these annotations
+    pub annotations: Vec<AnnotationMatch>,
+    /// Only entries whose preview matches
+    pub preview: Option<Regex>,
+}
+
+impl Query {
+    /// Whether the query matches every entry
+    pub fn is_empty(&self) -> bool {
+        self.context.is_none()
+            && self.kinds.is_empty()
+            && self.pinned.is_none()
+            && self.since.is_none()
+            && self.until.is_none()
+            && self.larger_than.is_none()
+            && self.smaller_than.is_none()
+            && self.tags.is_empty()
+            && self.apps.is_empty()
+            && self.annotations.is_empty()
+            && self.preview.is_none()
+    }
+
+    pub fn matches(&self, entry: &ClipEntry) -> bool {
+        let from_app = || {
+            entry.source_app.as_deref().is_some_and(|source| {
+                let source = source.to_lowercase();
+                self.apps.iter().any(|app| source.contains(app.as_str()))
+            })
+        };
+        let tagged = || entry.tags.iter().any(|t| self.tags.contains(&t.to_lowercase()));
+        self.context.as_ref().is_none_or(|c| entry.context.as_ref() == Some(c))
+            && (self.kinds.is_empty() || self.kinds.contains(&entry.kind))
+            && self.pinned.is_none_or(|pinned| entry.pinned == pinned)
+            && self.since.is_none_or(|since| entry.timestamp >= since)
+            && self.until.is_none_or(|until| entry.timestamp < until)
+            && self.larger_than.is_none_or(|size| entry.size > size)
+            && self.smaller_than.is_none_or(|size| entry.size < size)
+            && (self.tags.is_empty() || tagged())
+            && (self.apps.is_empty() || from_app())
+            && self.annotations.iter().all(|a| a.matches(entry))
+            && self.preview.as_ref().is_none_or(|re| re.is_match(&entry.preview))
+    }
+}
+
+/// Split the search terms off a search query: `app:NAME`, `tag:NAME`,
+/// `ann:KEY[=VALUE]`, `kind:KIND`, `is:pinned`, `is:unpinned`, `after:WHEN`,
+/// `before:WHEN` (a duration ago like `2d`, or a date like `2024-05-01`),
+/// `size:>SIZE`, `size:<SIZE` and `re:REGEX` (over previews). Returns the
+/// query and the remaining text, unchanged when there are no terms. Terms
+/// that don't parse stay text.
+pub fn parse_search(search: &str) -> (Query, String) {
+    let mut query = Query::default();
+    let mut words = Vec::new();
+    for word in search.split_whitespace() {
+        match word.split_once(':') {
+            Some(("app", app)) if !app.is_empty() => query.apps.push(app.to_lowercase()),
+            Some(("tag", tag)) if !tag.is_empty() => query.tags.push(tag.to_lowercase()),
+            Some(("ann", spec)) if let Ok(annotation) = AnnotationMatch::parse(spec) => {
+                query.annotations.push(annotation)
+            }
+            Some(("kind", name)) if let Some(kind) = EntryKind::from_name(name) => {
+                query.kinds.push(kind)
+            }
+            Some(("is", "pinned")) => query.pinned = Some(true),
+            Some(("is", "unpinned")) => query.pinned = Some(false),
+            Some(("after", when)) if let Some(time) = parse_when(when) => query.since = Some(time),
+            Some(("before", when)) if let Some(time) = parse_when(when) => {
+                query.until = Some(time)
+            }
+            Some(("size", bound)) if let Some(size) = size_bound(bound, '>') => {
+                query.larger_than = Some(size)
+            }
+            Some(("size", bound)) if let Some(size) = size_bound(bound, '<') => {
+                query.smaller_than = Some(size)
+            }
+            Some(("re", pattern)) if let Ok(re) = Regex::new(pattern) => query.preview = Some(re),
+            _ => words.push(word),
+        }
+    }
+    if query.is_empty() {
+        return (query, search.to_string());
+    }
+    (query, words.join(" "))
+}
+
+/// The size in a `size:` term starting with `op`
+fn size_bound(bound: &str, op: char) -> Option<usize> {
+    util::parse_size(bound.strip_prefix(op)?).ok()
+}
+
+/// A point in time for `after:`/`before:`: a duration ago (`2d`, `30m`) or a
+/// local date (`2024-05-01`, its midnight)
+pub fn parse_when(when: &str) -> Option<i64> {
+    if let Ok(ago) = util::parse_duration(when) {
+        return Some(util::now_millis().saturating_sub(util::duration_millis(ago)));
+    }
+    let date = chrono::NaiveDate::parse_from_str(when, "%Y-%m-%d").ok()?;
+    let midnight = date.and_hms_opt(0, 0, 0)?.and_local_timezone(chrono::Local).earliest()?;
+    Some(midnight.timestamp_millis())
+}
+
+/// `KEY` (any value) or `KEY=VALUE` (exactly that value)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnnotationMatch {
+    pub key: String,
+    pub value: Option<String>,
+}
+
+impl AnnotationMatch {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (key, value) = match s.split_once('=') {
+            Some((key, value)) => (key, Some(value.to_string())),
+            None => (s, None),
+        };
+        if key.trim().is_empty() {
+            return Err(format!("invalid annotation '{}' (expected KEY or KEY=VALUE)", s));
+        }
+        Ok(Self {
+            key: key.trim().to_string(),
+            value,
+        })
+    }
+
+    pub fn matches(&self, entry: &ClipEntry) -> bool {
+        entry
+            .annotations
+            .get(&self.key)
+            .is_some_and(|v| self.value.as_ref().is_none_or(|want| v == want))
+    }
+}
+
+/// A `KEY=VALUE` pair for `annotate --set`
+pub fn parse_assignment(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("invalid annotation '{}' (expected KEY=VALUE)", s))
+}
+
+/// Listing order
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    /// Most recently copied first (the history order)
+    #[default]
+    Time,
+    /// Largest first
+    Size,
+    /// Grouped by kind (text, url, path), most recent first within each
+    Kind,
+    /// Most often pasted or captured again first
+    Uses,
+    /// Uses weighted by how recently the entry was last copied or used
+    Frecency,
+}
+
+/// Weight of one use by the age of an entry's latest copy or use: an hour,
+/// a day, a week, then anything older
+const FRECENCY_WEIGHTS: [(i64, u64); 3] = [
+    (60 * 60 * 1000, 8),
+    (24 * 60 * 60 * 1000, 4),
+    (7 * 24 * 60 * 60 * 1000, 2),
+];
+
+/// Frecency at `now` (Unix ms): the copy itself and every use since, weighted
+/// by recency. Without uses it follows the history order.
+pub fn frecency(entry: &ClipEntry, now: i64) -> u64 {
+    let latest = entry.last_used.map_or(entry.timestamp, |used| used.max(entry.timestamp));
+    let age = now.saturating_sub(latest);
+    let weight = FRECENCY_WEIGHTS
+        .iter()
+        .find(|(limit, _)| age < *limit)
+        .map_or(1, |(_, weight)| *weight);
+    (u64::from(entry.use_count) + 1) * weight
+}
+
+/// Sort entries given in history order. Ties keep history order, and
+/// `reverse` flips the whole result.
+pub fn sort(entries: &mut [ClipEntry], key: SortKey, reverse: bool) {
+    match key {
+        SortKey::Time => {}
+        SortKey::Size => entries.sort_by_key(|e| std::cmp::Reverse(e.size)),
+        SortKey::Kind => {
+            entries.sort_by_key(|e| EntryKind::ALL.iter().position(|&k| k == e.kind))
+        }
+        SortKey::Uses => entries.sort_by_key(|e| std::cmp::Reverse(e.use_count)),
+        SortKey::Frecency => {
+            let now = util::now_millis();
+            entries.sort_by_key(|e| std::cmp::Reverse(frecency(e, now)))
+        }
+    }
+    if reverse {
+        entries.reverse();
+    }
+}
+
+/// A field of `list --columns` output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Column {
+    Id,
+    /// Local capture time, e.g. 2024-05-01T14:03:12
+    AbsTime,
+    /// Capture age, e.g. 5m ago
+    RelTime,
+    /// Size in bytes
+    Size,
+    Kind,
+    /// Comma-separated
+    Tags,
+    /// yes or no
+    Pinned,
+    /// Space-separated KEY=VALUE pairs
+    Annotations,
+    /// Times pasted or captured again
+    Uses,
+    /// Age of the last use, e.g. 2h ago (empty if never used)
+    LastUsed,
+    Preview,
+}
+
+impl Column {
+    pub fn header(self) -> &'static str {
+        match self {
+            Column::Id => "ID",
+            Column::AbsTime => "TIME",
+            Column::RelTime => "AGE",
+            Column::Size => "SIZE",
+            Column::Kind => "KIND",
+            Column::Tags => "TAGS",
+            Column::Pinned => "PINNED",
+            Column::Annotations => "ANNOTATIONS",
+            Column::Uses => "USES",
+            Column::LastUsed => "LAST USED",
+            Column::Preview => "PREVIEW",
+        }
+    }
+
+    /// The field's value, without tabs or newlines so rows stay one line
+    pub fn value(self, entry: &ClipEntry) -> String {
+        let value = match self {
+            Column::Id => entry.id.clone(),
+            Column::AbsTime => chrono::DateTime::from_timestamp_millis(entry.timestamp)
+                .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%dT%H:%M:%S").to_string())
+                .unwrap_or_default(),
+            Column::RelTime => util::format_relative_time(entry.timestamp),
+            Column::Size => entry.size.to_string(),
+            Column::Kind => entry.kind.name().to_string(),
+            Column::Tags => entry.tags.join(","),
+            Column::Pinned => if entry.pinned { "yes" } else { "no" }.to_string(),
+            Column::Annotations => format_annotations(entry),
+            Column::Uses => entry.use_count.to_string(),
+            Column::LastUsed => entry.last_used.map(util::format_relative_time).unwrap_or_default(),
+            Column::Preview => entry.preview.clone(),
+        };
+        value.replace(['\t', '\n', '\r'], " ")
+    }
+}
+
+/// An entry's annotations as space-separated `KEY=VALUE` pairs
+pub fn format_annotations(entry: &ClipEntry) -> String {
+    let pairs: Vec<String> =
+        entry.annotations.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+    pairs.join(" ")
+}
+
+/// One tab-separated row of the given columns
+pub fn row(entry: &ClipEntry, columns: &[Column]) -> String {
+    columns.iter().map(|c| c.value(entry)).collect::<Vec<_>>().join("\t")
+}
+
+/// Tab-separated header names for the given columns
+pub fn header(columns: &[Column]) -> String {
+    columns.iter().map(|c| c.header()).collect::<Vec<_>>().join("\t")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, size: usize, content: &str, pinned: bool) -> ClipEntry {
+        ClipEntry {
+            id: id.to_string(),
+            timestamp: id.parse().unwrap(),
+            size,
+            preview: content.to_string(),
+            hash: String::new(),
+            pinned,
+            source_app: None,
+            window_title: None,
+            tags: Vec::new(),
+            name: None,
+            context: None,
+            seat: None,
+            kind: EntryKind::detect(content),
+            sensitive: false,
+            annotations: Default::default(),
+            use_count: 0,
+            last_used: None,
+            representations: Vec::new(),
+            expires: None,
+        }
+    }
+
+    fn ids(entries: &[ClipEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_filter() {
+        let entries = [
+            entry("3", 10, "https://example.com", false),
+            entry("2", 20, "notes", true),
+            entry("1", 30, "/etc/hosts", false),
+        ];
+        let matching = |filter: &Query| -> Vec<&str> {
+            entries.iter().filter(|e| filter.matches(e)).map(|e| e.id.as_str()).collect()
+        };
+
+        assert_eq!(matching(&Query::default()), ["3", "2", "1"]);
+        let urls = Query {
+            kinds: vec![EntryKind::Url],
+            ..Default::default()
+        };
+        assert_eq!(matching(&urls), ["3"]);
+        let pinned = Query {
+            pinned: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(matching(&pinned), ["2"]);
+        let recent = Query {
+            since: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(matching(&recent), ["3", "2"]);
+        let unpinned = Query {
+            pinned: Some(false),
+            until: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(matching(&unpinned), ["1"]);
+        let sized = Query {
+            larger_than: Some(10),
+            smaller_than: Some(30),
+            ..Default::default()
+        };
+        assert_eq!(matching(&sized), ["2"]);
+    }
+
+    #[test]
+    fn test_parse_search() {
+        let (query, text) = parse_search("tag:Work is:pinned size:>1KB size:<2MB invoice re:^INV");
+        assert_eq!(text, "invoice");
+        assert_eq!(query.tags, ["work"]);
+        assert_eq!(query.pinned, Some(true));
+        assert_eq!((query.larger_than, query.smaller_than), (Some(1024), Some(2 * 1024 * 1024)));
+        assert!(query.preview.unwrap().is_match("INV-42"));
+
+        let (query, _) = parse_search("after:2d before:2024-05-01");
+        let two_days_ago = util::now_millis() - 2 * 24 * 3600 * 1000;
+        assert!(query.since.unwrap().abs_diff(two_days_ago) < 1000);
+        assert_eq!(query.until, parse_when("2024-05-01"));
+        assert!(query.until.unwrap() > 1714000000000);
+
+        // Terms that don't parse are searched as text, and plain text is kept as typed
+        let (query, text) = parse_search("kind:image size:>big after:soon re:(");
+        assert!(query.is_empty());
+        assert_eq!(text, "kind:image size:>big after:soon re:(");
+        assert_eq!(parse_search("a  b").1, "a  b");
+    }
+
+    #[test]
+    fn test_annotation_filter() {
+        let mut ticketed = entry("2", 10, "fix", false);
+        ticketed.annotations.insert("ticket".into(), "ABC-1".into());
+        ticketed.annotations.insert("url".into(), "https://x.test/?a=b".into());
+        let plain = entry("1", 10, "notes", false);
+
+        let filter = |specs: &[&str]| Query {
+            annotations: specs.iter().map(|s| AnnotationMatch::parse(s).unwrap()).collect(),
+            ..Default::default()
+        };
+        assert!(filter(&["ticket"]).matches(&ticketed));
+        assert!(!filter(&["ticket"]).matches(&plain));
+        assert!(filter(&["ticket=ABC-1", "url=https://x.test/?a=b"]).matches(&ticketed));
+        assert!(!filter(&["ticket=ABC-2"]).matches(&ticketed));
+        assert!(AnnotationMatch::parse("=x").is_err());
+
+        assert_eq!(parse_assignment("url=a=b"), Ok(("url".into(), "a=b".into())));
+        assert!(parse_assignment("ticket").is_err());
+        assert_eq!(format_annotations(&ticketed), "ticket=ABC-1 url=https://x.test/?a=b");
+    }
+
+    #[test]
+    fn test_columns_are_tab_separated_single_line() {
+        let mut e = entry("1700000000000", 42, "a\tb\nc", true);
+        e.tags = vec!["work".to_string(), "sql".to_string()];
+        let columns = [Column::Id, Column::Size, Column::Kind, Column::Tags, Column::Pinned];
+
+        assert_eq!(header(&columns), "ID\tSIZE\tKIND\tTAGS\tPINNED");
+        assert_eq!(row(&e, &columns), "1700000000000\t42\ttext\twork,sql\tyes");
+        assert_eq!(Column::Preview.value(&e), "a b c");
+        assert_eq!(Column::AbsTime.value(&e).len(), "2023-11-14T22:13:20".len());
+    }
+
+    #[test]
+    fn test_sort_keeps_history_order_for_ties() {
+        let history = vec![
+            entry("4", 10, "https://a.com", false),
+            entry("3", 50, "text", false),
+            entry("2", 10, "more text", false),
+            entry("1", 50, "https://b.com", false),
+        ];
+
+        let mut entries = history.clone();
+        sort(&mut entries, SortKey::Time, false);
+        assert_eq!(ids(&entries), ["4", "3", "2", "1"]);
+
+        sort(&mut entries, SortKey::Size, false);
+        assert_eq!(ids(&entries), ["3", "1", "4", "2"]);
+
+        let mut entries = history.clone();
+        sort(&mut entries, SortKey::Kind, false);
+        assert_eq!(ids(&entries), ["3", "2", "4", "1"]);
+
+        let mut entries = history.clone();
+        sort(&mut entries, SortKey::Time, true);
+        assert_eq!(ids(&entries), ["1", "2", "3", "4"]);
+
+        let mut entries = history;
+        entries[2].use_count = 3;
+        entries[3].use_count = 1;
+        sort(&mut entries, SortKey::Uses, false);
+        assert_eq!(ids(&entries), ["2", "1", "4", "3"]);
+    }
+
+    #[test]
+    fn test_frecency_weighs_uses_by_recency() {
+        let now = 100 * 24 * 60 * 60 * 1000;
+        let minutes = |n: i64| n * 60 * 1000;
+        let at = |age: i64, uses: u32| {
+            let mut e = entry("1", 1, "text", false);
+            e.timestamp = now - age;
+            e.use_count = uses;
+            e
+        };
+
+        assert_eq!(frecency(&at(minutes(5), 0), now), 8);
+        assert_eq!(frecency(&at(minutes(120), 0), now), 4);
+        assert_eq!(frecency(&at(minutes(3 * 24 * 60), 3), now), 8);
+        assert_eq!(frecency(&at(minutes(30 * 24 * 60), 5), now), 6);
+
+        // A recent use counts from when it happened, not when it was first copied
+        let mut used = at(minutes(30 * 24 * 60), 1);
+        used.last_used = Some(now - minutes(10));
+        assert_eq!(frecency(&used, now), 16);
+
+        // Without uses, the history order
+        let now = util::now_millis();
+        let mut entries = vec![
+            entry("3", 1, "c", false),
+            entry("2", 1, "b", false),
+            entry("1", 1, "a", false),
+        ];
+        for (i, e) in entries.iter_mut().enumerate() {
+            e.timestamp = now - minutes(i as i64 * 90);
+        }
+        sort(&mut entries, SortKey::Frecency, false);
+        assert_eq!(ids(&entries), ["3", "2", "1"]);
+        entries[2].use_count = 2;
+        sort(&mut entries, SortKey::Frecency, false);
+        assert_eq!(ids(&entries), ["1", "3", "2"]);
+    }
+}
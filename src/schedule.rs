@@ -0,0 +1,179 @@
+use anyhow::{bail, Context, Result};
+use chrono::{Datelike, NaiveDateTime, Timelike, Weekday};
+use serde::{Deserialize, Serialize};
+
+const DAY_NAMES: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// When the daemon records history (`[daemon] schedule`), as local-time windows
+/// like "mon-fri 09:00-18:00". An empty schedule records all the time.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<String>", into = "Vec<String>")]
+pub struct Schedule {
+    windows: Vec<Window>,
+}
+
+/// One capture window. `end` before `start` runs past midnight into the next day.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Window {
+    spec: String,
+    days: [bool; 7], // Monday first
+    start: u32,      // Minutes after midnight
+    end: u32,
+}
+
+impl Schedule {
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+
+    /// Whether capture is on at this local time
+    pub fn active_at(&self, now: NaiveDateTime) -> bool {
+        let minute = now.hour() * 60 + now.minute();
+        let today = now.weekday();
+        self.is_empty() || self.windows.iter().any(|w| w.contains(today, minute))
+    }
+
+    /// The windows as configured, for display
+    pub fn describe(&self) -> String {
+        let specs: Vec<&str> = self.windows.iter().map(|w| w.spec.as_str()).collect();
+        specs.join(", ")
+    }
+}
+
+impl Window {
+    fn contains(&self, day: Weekday, minute: u32) -> bool {
+        let on = |d: Weekday| self.days[d.num_days_from_monday() as usize];
+        if self.start <= self.end {
+            on(day) && (self.start..self.end).contains(&minute)
+        } else {
+            // Overnight: the evening of a listed day, or the early hours after one
+            on(day) && minute >= self.start || on(day.pred()) && minute < self.end
+        }
+    }
+
+    /// Parse "[days] HH:MM-HH:MM"; days are "mon-fri", "sat,sun", "daily" or absent
+    fn parse(spec: &str) -> Result<Self> {
+        let words: Vec<&str> = spec.split_whitespace().collect();
+        let (days, times) = match words.as_slice() {
+            [times] => ([true; 7], *times),
+            [days, times] => (parse_days(days)?, *times),
+            _ => bail!("expected '[days] HH:MM-HH:MM'"),
+        };
+        let (start, end) = times.split_once('-').context("expected HH:MM-HH:MM")?;
+        let (start, end) = (parse_time(start)?, parse_time(end)?);
+        if start == end {
+            bail!("window is empty");
+        }
+        Ok(Self {
+            spec: spec.to_string(),
+            days,
+            start,
+            end,
+        })
+    }
+}
+
+fn parse_days(spec: &str) -> Result<[bool; 7]> {
+    if matches!(spec, "daily" | "every") {
+        return Ok([true; 7]);
+    }
+    let day = |name: &str| {
+        DAY_NAMES
+            .iter()
+            .position(|d| name.eq_ignore_ascii_case(d))
+            .with_context(|| format!("unknown day '{}' (use mon..sun)", name))
+    };
+    let mut days = [false; 7];
+    for part in spec.split(',') {
+        match part.split_once('-') {
+            // Ranges wrap around the week: "fri-mon" is fri, sat, sun, mon
+            Some((from, to)) => {
+                let (from, to) = (day(from)?, day(to)?);
+                let len = (to + 7 - from) % 7 + 1;
+                for i in 0..len {
+                    days[(from + i) % 7] = true;
+                }
+            }
+            None => days[day(part)?] = true,
+        }
+    }
+    Ok(days)
+}
+
+/// "HH:MM" as minutes after midnight; "24:00" ends a window at midnight
+fn parse_time(s: &str) -> Result<u32> {
+    let (h, m) = s.split_once(':').with_context(|| format!("bad time '{}'", s))?;
+    let (h, m): (u32, u32) = match (h.parse(), m.parse()) {
+        (Ok(h), Ok(m)) if m < 60 && (h < 24 || h == 24 && m == 0) => (h, m),
+        _ => bail!("bad time '{}'", s),
+    };
+    Ok(h * 60 + m)
+}
+
+impl TryFrom<Vec<String>> for Schedule {
+    type Error = anyhow::Error;
+
+    fn try_from(specs: Vec<String>) -> Result<Self> {
+        let windows = specs
+            .iter()
+            .map(|s| Window::parse(s).with_context(|| format!("Invalid schedule '{}'", s)))
+            .collect::<Result<_>>()?;
+        Ok(Self { windows })
+    }
+}
+
+impl From<Schedule> for Vec<String> {
+    fn from(schedule: Schedule) -> Self {
+        schedule.windows.into_iter().map(|w| w.spec).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn schedule(specs: &[&str]) -> Schedule {
+        Schedule::try_from(specs.iter().map(|s| s.to_string()).collect::<Vec<_>>()).unwrap()
+    }
+
+    /// 2024-01-01 was a Monday
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 1, day).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_work_hours() {
+        let s = schedule(&["mon-fri 09:00-18:00"]);
+        assert!(!s.active_at(at(1, 8, 59)));
+        assert!(s.active_at(at(1, 9, 0)));
+        assert!(s.active_at(at(5, 17, 59)));
+        assert!(!s.active_at(at(5, 18, 0)));
+        assert!(!s.active_at(at(6, 12, 0)), "Saturday");
+        assert!(schedule(&[]).active_at(at(6, 12, 0)), "Empty schedule is always on");
+    }
+
+    #[test]
+    fn test_overnight_and_day_lists() {
+        let s = schedule(&["fri 22:00-02:00", "sat,sun 10:00-24:00"]);
+        assert!(s.active_at(at(5, 23, 0)));
+        assert!(s.active_at(at(6, 1, 59)), "Early Saturday belongs to Friday night");
+        assert!(!s.active_at(at(6, 2, 0)));
+        assert!(s.active_at(at(7, 23, 59)));
+        assert!(!s.active_at(at(1, 1, 0)), "Monday night isn't scheduled");
+
+        let wrap = schedule(&["fri-mon 12:00-13:00"]);
+        assert!(wrap.active_at(at(1, 12, 30)));
+        assert!(!wrap.active_at(at(2, 12, 30)));
+        assert!(schedule(&["08:00-09:00"]).active_at(at(3, 8, 0)));
+    }
+
+    #[test]
+    fn test_invalid_windows() {
+        for spec in ["9-17", "mon-fri", "xyz 09:00-10:00", "09:00-25:00", "10:00-10:00"] {
+            assert!(Schedule::try_from(vec![spec.to_string()]).is_err(), "{}", spec);
+        }
+        let s = schedule(&["mon-fri 09:00-18:00", "sat 10:00-12:00"]);
+        assert_eq!(s.describe(), "mon-fri 09:00-18:00, sat 10:00-12:00");
+    }
+}
@@ -0,0 +1,149 @@
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Zsh escapes some bytes in its history file as this marker plus the byte xor 32
+const ZSH_META: u8 = 0x83;
+
+/// History file format
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum HistoryShell {
+    Bash,
+    Zsh,
+}
+
+impl HistoryShell {
+    /// The login shell from $SHELL, bash if it's neither
+    pub fn detect() -> Self {
+        match std::env::var("SHELL") {
+            Ok(shell) if shell.ends_with("zsh") => HistoryShell::Zsh,
+            _ => HistoryShell::Bash,
+        }
+    }
+
+    /// $HISTFILE when set, otherwise the shell's default file in $HOME
+    pub fn default_path(self) -> PathBuf {
+        if let Some(path) = std::env::var_os("HISTFILE") {
+            return PathBuf::from(path);
+        }
+        let name = match self {
+            HistoryShell::Bash => ".bash_history",
+            HistoryShell::Zsh => ".zsh_history",
+        };
+        dirs::home_dir().unwrap_or_default().join(name)
+    }
+
+    /// Commands in the order they were run
+    pub fn parse(self, data: &[u8]) -> Vec<String> {
+        match self {
+            HistoryShell::Bash => String::from_utf8_lossy(data)
+                .lines()
+                // HISTTIMEFORMAT writes a `#<unix time>` line before each command
+                .filter(|line| !is_timestamp_comment(line))
+                .map(str::to_string)
+                .collect(),
+            HistoryShell::Zsh => parse_zsh(&String::from_utf8_lossy(&unmetafy(data))),
+        }
+    }
+}
+
+fn is_timestamp_comment(line: &str) -> bool {
+    line.strip_prefix('#').is_some_and(|t| !t.is_empty() && t.bytes().all(|b| b.is_ascii_digit()))
+}
+
+fn unmetafy(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut bytes = data.iter();
+    while let Some(&b) = bytes.next() {
+        match b {
+            ZSH_META => out.extend(bytes.next().map(|&next| next ^ 32)),
+            _ => out.push(b),
+        }
+    }
+    out
+}
+
+/// Lines are `: <start>:<duration>;<command>` with EXTENDED_HISTORY, or just the
+/// command. A command spanning lines ends each but the last with a backslash.
+fn parse_zsh(data: &str) -> Vec<String> {
+    let mut commands = Vec::new();
+    let mut current: Option<String> = None;
+    for line in data.lines() {
+        let line = match current.as_mut() {
+            Some(command) => {
+                command.push('\n');
+                command.push_str(line);
+                current.take().unwrap()
+            }
+            None => strip_zsh_timestamp(line).to_string(),
+        };
+        match line.strip_suffix('\\') {
+            Some(partial) => current = Some(partial.to_string()),
+            None => commands.push(line),
+        }
+    }
+    commands.extend(current);
+    commands
+}
+
+fn strip_zsh_timestamp(line: &str) -> &str {
+    line.strip_prefix(": ")
+        .and_then(|rest| rest.split_once(';'))
+        .filter(|(stamp, _)| stamp.bytes().all(|b| b.is_ascii_digit() || b == b':'))
+        .map_or(line, |(_, command)| command)
+}
+
+/// The `last` most recent distinct commands, oldest first. Blank lines are skipped.
+pub fn recent(commands: &[String], last: usize) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut recent: Vec<String> = commands
+        .iter()
+        .rev()
+        .map(|c| c.trim_end())
+        .filter(|c| !c.trim().is_empty() && seen.insert(*c))
+        .take(last)
+        .map(str::to_string)
+        .collect();
+    recent.reverse();
+    recent
+}
+
+/// Read and parse a history file
+pub fn read(shell: HistoryShell, path: &Path) -> Result<Vec<String>> {
+    let data = fs::read(path).with_context(|| format!("Failed to read {:?}", path))?;
+    Ok(shell.parse(&data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bash_with_timestamps() {
+        let data = b"#1700000000\ngit status\n#1700000005\ncargo build\nls\n";
+        assert_eq!(HistoryShell::Bash.parse(data), ["git status", "cargo build", "ls"]);
+    }
+
+    #[test]
+    fn test_parse_zsh_extended_and_multiline() {
+        let mut data = b": 1700000000:0;git status\n".to_vec();
+        data.extend(b": 1700000001:2;for f in *; do\\\n  echo $f\\\ndone\n");
+        // "caf\xc3\xa9" with the \xc3 byte metafied
+        data.extend(b"echo caf\x83\xe3\xa9\nplain line\n");
+        assert_eq!(
+            HistoryShell::Zsh.parse(&data),
+            ["git status", "for f in *; do\n  echo $f\ndone", "echo café", "plain line"]
+        );
+    }
+
+    #[test]
+    fn test_recent_dedupes_keeping_latest() {
+        let commands: Vec<String> = ["ls", "git pull", "ls", "  ", "make", "git pull"]
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        assert_eq!(recent(&commands, 10), ["ls", "make", "git pull"]);
+        assert_eq!(recent(&commands, 2), ["make", "git pull"]);
+    }
+}
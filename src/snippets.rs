@@ -0,0 +1,146 @@
+use crate::backend;
+use crate::error::Error;
+use crate::util;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// File in the storage dir the snippets are kept in
+pub const SNIPPETS_FILE_NAME: &str = "snippets.json";
+
+/// Reusable text kept apart from the history (`clipstack snippet`). Snippets
+/// are never pruned, expired or cleared; they go only when removed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snippet {
+    pub name: String,
+    pub content: String,
+    /// Unix millis when the snippet was added or last replaced
+    pub updated: i64,
+}
+
+impl Snippet {
+    /// First non-blank line, for lists
+    pub fn first_line(&self) -> &str {
+        self.content.lines().map(str::trim).find(|l| !l.is_empty()).unwrap_or_default()
+    }
+}
+
+/// The snippets file of one storage dir
+pub struct Snippets {
+    path: PathBuf,
+}
+
+impl Snippets {
+    pub fn new(base_dir: &Path) -> Self {
+        Self {
+            path: base_dir.join(SNIPPETS_FILE_NAME),
+        }
+    }
+
+    /// All snippets, sorted by name
+    pub fn list(&self) -> Result<Vec<Snippet>> {
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", self.path)),
+        };
+        serde_json::from_slice(&data).map_err(|source| {
+            Error::StorageCorrupt {
+                what: "Snippets file",
+                source,
+            }
+            .into()
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Result<Option<Snippet>> {
+        Ok(self.list()?.into_iter().find(|s| s.name == name))
+    }
+
+    /// Add a snippet. An existing one with the same name is only replaced with
+    /// `replace`; returns whether one was.
+    pub fn add(&self, name: &str, content: &str, replace: bool) -> Result<bool> {
+        let name = name.trim();
+        anyhow::ensure!(!name.is_empty(), "Snippet name cannot be empty");
+        anyhow::ensure!(
+            !name.chars().any(char::is_whitespace),
+            "Snippet name cannot contain whitespace: '{}'",
+            name
+        );
+        anyhow::ensure!(!content.is_empty(), "Snippet '{}' would be empty", name);
+
+        let mut snippets = self.list()?;
+        let existing = snippets.iter().position(|s| s.name == name);
+        if existing.is_some() && !replace {
+            anyhow::bail!("Snippet '{}' already exists (use --force to replace it)", name);
+        }
+        let snippet = Snippet {
+            name: name.to_string(),
+            content: content.to_string(),
+            updated: util::now_millis(),
+        };
+        match existing {
+            Some(i) => snippets[i] = snippet,
+            None => snippets.push(snippet),
+        }
+        snippets.sort_by(|a, b| a.name.cmp(&b.name));
+        self.save(&snippets)?;
+        Ok(existing.is_some())
+    }
+
+    /// Remove a snippet. Returns false if there was none by that name.
+    pub fn remove(&self, name: &str) -> Result<bool> {
+        let mut snippets = self.list()?;
+        let before = snippets.len();
+        snippets.retain(|s| s.name != name);
+        if snippets.len() == before {
+            return Ok(false);
+        }
+        self.save(&snippets)?;
+        Ok(true)
+    }
+
+    fn save(&self, snippets: &[Snippet]) -> Result<()> {
+        let json = serde_json::to_vec_pretty(snippets)?;
+        backend::atomic_write(&self.path, &json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_add_replace_remove() {
+        let dir = TempDir::new().unwrap();
+        let snippets = Snippets::new(dir.path());
+        assert!(snippets.list().unwrap().is_empty());
+
+        assert!(!snippets.add("sig", "--\nJane Doe", false).unwrap());
+        assert!(!snippets.add("apache", "Licensed under the Apache License", false).unwrap());
+        let names: Vec<String> = snippets.list().unwrap().into_iter().map(|s| s.name).collect();
+        assert_eq!(names, ["apache", "sig"]);
+        assert_eq!(snippets.get("sig").unwrap().unwrap().first_line(), "--");
+
+        assert!(snippets.add("sig", "Jane", false).is_err());
+        assert!(snippets.add("sig", "Jane", true).unwrap());
+        assert_eq!(snippets.get("sig").unwrap().unwrap().content, "Jane");
+        assert!(snippets.add("two words", "x", false).is_err());
+        assert!(snippets.add("empty", "", false).is_err());
+
+        assert!(snippets.remove("sig").unwrap());
+        assert!(!snippets.remove("sig").unwrap());
+        assert!(snippets.get("sig").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_corrupt_file_is_reported() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join(SNIPPETS_FILE_NAME), "not json").unwrap();
+        let err = Snippets::new(dir.path()).list().unwrap_err();
+        assert!(matches!(crate::error::kind_of(&err), Some(Error::StorageCorrupt { .. })));
+    }
+}
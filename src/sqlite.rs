@@ -0,0 +1,590 @@
+use crate::backend::{self, Capture, Limits, Recorded, StorageBackend};
+use crate::error::Error;
+use crate::kind::EntryKind;
+use crate::storage::{ClipEntry, ClipIndex, DEFAULT_MAX_ENTRIES, INDEX_VERSION};
+use crate::util;
+use anyhow::{Context, Result};
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, Connection, OptionalExtension, Transaction, TransactionBehavior};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Database file in the storage dir
+pub const DB_FILE_NAME: &str = "history.db";
+
+/// How long a write waits for another process (daemon, CLI) holding the database
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Entries are kept as JSON with the columns queries need alongside; `seq`
+/// orders the history (highest first) since a recaptured entry moves to the front.
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS entries (
+        id TEXT PRIMARY KEY,
+        seq INTEGER NOT NULL,
+        hash TEXT NOT NULL,
+        pinned INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS entries_seq ON entries (seq);
+    CREATE INDEX IF NOT EXISTS entries_hash ON entries (hash);
+    CREATE TABLE IF NOT EXISTS content (
+        id TEXT PRIMARY KEY,
+        data TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS meta (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+";
+
+/// History in a single SQLite database: saves are one transaction and look
+/// duplicates up by hash instead of loading the whole index
+pub struct SqliteBackend {
+    conn: Mutex<Connection>,
+    path: PathBuf,
+}
+
+impl SqliteBackend {
+    /// Open or create the database. Returns true as well if it was just created.
+    pub fn open(path: &Path) -> Result<(Self, bool)> {
+        let created = !path.exists();
+        let conn = Connection::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.execute_batch(SCHEMA).context("Failed to create history tables")?;
+        if created {
+            conn.execute(
+                "INSERT INTO meta (key, value) VALUES ('version', ?1)",
+                [INDEX_VERSION.to_string()],
+            )?;
+        }
+        let db = Self {
+            conn: Mutex::new(conn),
+            path: path.to_path_buf(),
+        };
+        Ok((db, created))
+    }
+
+    fn with_transaction<T>(&self, f: impl FnOnce(&Transaction) -> Result<T>) -> Result<T> {
+        let mut conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let result = f(&tx)?;
+        tx.commit()?;
+        Ok(result)
+    }
+
+    /// The database and its write-ahead log, in bytes
+    fn file_size(&self) -> u64 {
+        let size = |path: &Path| fs::metadata(path).map_or(0, |m| m.len());
+        size(&self.path) + size(&self.path.with_extension("db-wal"))
+    }
+}
+
+fn decode(data: String) -> Result<ClipEntry> {
+    serde_json::from_str(&data).map_err(|source| {
+        Error::StorageCorrupt {
+            what: "Entry in history database",
+            source,
+        }
+        .into()
+    })
+}
+
+/// Store content as text, or gzipped as a blob
+fn insert_content(conn: &Connection, id: &str, content: &str, compress: bool) -> Result<()> {
+    let sql = "INSERT OR REPLACE INTO content (id, data) VALUES (?1, ?2)";
+    if compress {
+        conn.execute(sql, params![id, backend::compress(content)?])?;
+    } else {
+        conn.execute(sql, params![id, content])?;
+    }
+    Ok(())
+}
+
+/// Put an entry at the front of the history, replacing any row with its id
+fn put_front(tx: &Transaction, entry: &ClipEntry) -> Result<()> {
+    let seq: i64 = tx.query_row("SELECT COALESCE(MAX(seq), 0) + 1 FROM entries", [], |r| r.get(0))?;
+    insert(tx, entry, seq)
+}
+
+fn insert(tx: &Transaction, entry: &ClipEntry, seq: i64) -> Result<()> {
+    tx.execute(
+        "INSERT OR REPLACE INTO entries (id, seq, hash, pinned, kind, data)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            entry.id,
+            seq,
+            entry.hash,
+            entry.pinned,
+            entry.kind.name(),
+            serde_json::to_string(entry)?
+        ],
+    )?;
+    Ok(())
+}
+
+/// Delete the oldest unpinned entries (of `kind`, if given) beyond `limit`
+fn prune_where(tx: &Transaction, limit: usize, kind: Option<EntryKind>) -> Result<Vec<ClipEntry>> {
+    let mut stmt = tx.prepare(
+        "SELECT data FROM entries WHERE pinned = 0 AND (?1 IS NULL OR kind = ?1)
+         ORDER BY seq DESC LIMIT -1 OFFSET ?2",
+    )?;
+    let rows = stmt.query_map(params![kind.map(EntryKind::name), limit as i64], |r| r.get(0))?;
+    delete_rows(tx, rows)
+}
+
+/// Delete unpinned entries last copied before `cutoff` (ms), or past their own expiry
+fn expire(tx: &Transaction, cutoff: Option<i64>) -> Result<Vec<ClipEntry>> {
+    let mut stmt = tx.prepare(
+        "SELECT data FROM entries WHERE pinned = 0
+         AND (json_extract(data, '$.timestamp') < ?1 OR json_extract(data, '$.expires') <= ?2)",
+    )?;
+    let rows = stmt.query_map(params![cutoff, util::now_millis()], |r| r.get(0))?;
+    delete_rows(tx, rows)
+}
+
+fn delete_rows(
+    tx: &Transaction,
+    rows: impl Iterator<Item = rusqlite::Result<String>>,
+) -> Result<Vec<ClipEntry>> {
+    let entries = rows.map(|data| decode(data?)).collect::<Result<Vec<_>>>()?;
+    for entry in &entries {
+        tx.execute("DELETE FROM entries WHERE id = ?1", [&entry.id])?;
+    }
+    Ok(entries)
+}
+
+fn prune_tx(tx: &Transaction, limits: &Limits) -> Result<Vec<ClipEntry>> {
+    let mut removed = expire(tx, limits.expiry_cutoff())?;
+    for (&kind, &cap) in limits.quotas {
+        removed.extend(prune_where(tx, cap, Some(kind))?);
+    }
+    removed.extend(prune_where(tx, limits.max_entries, None)?);
+    if let Some(budget) = limits.max_total_size {
+        removed.extend(prune_to_size(tx, budget)?);
+    }
+    for entry in removed.iter().filter(|_| !limits.keep_content) {
+        tx.execute("DELETE FROM content WHERE id = ?1", [&entry.id])?;
+        for id in entry.representation_ids() {
+            tx.execute("DELETE FROM content WHERE id = ?1", [&id])?;
+        }
+    }
+    Ok(removed)
+}
+
+/// Delete the oldest unpinned entries, never the newest, until the content
+/// totals at most `budget` bytes
+fn prune_to_size(tx: &Transaction, budget: usize) -> Result<Vec<ClipEntry>> {
+    let total: i64 = tx.query_row(
+        "SELECT COALESCE(SUM(json_extract(data, '$.size')), 0) FROM entries",
+        [],
+        |r| r.get(0),
+    )?;
+    let mut excess = total - budget as i64;
+    if excess <= 0 {
+        return Ok(Vec::new());
+    }
+    let mut stmt = tx.prepare(
+        "SELECT data, json_extract(data, '$.size') FROM entries
+         WHERE pinned = 0 AND seq < (SELECT MAX(seq) FROM entries) ORDER BY seq ASC",
+    )?;
+    let mut evicted = Vec::new();
+    for row in stmt.query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, i64>(1)?)))? {
+        if excess <= 0 {
+            break;
+        }
+        let (data, size) = row?;
+        excess -= size;
+        evicted.push(Ok(data));
+    }
+    delete_rows(tx, evicted.into_iter())
+}
+
+impl StorageBackend for SqliteBackend {
+    fn read_index(&self) -> Result<Option<ClipIndex>> {
+        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let meta = |key: &str| {
+            conn.query_row("SELECT value FROM meta WHERE key = ?1", [key], |r| {
+                r.get::<_, String>(0)
+            })
+            .optional()
+        };
+        let max_entries = meta("max_entries")?;
+        let max_pinned = meta("max_pinned")?;
+        let version = meta("version")?;
+        let trash = meta("trash")?;
+        // Entries go through the same migrations as the files backend's index
+        let mut stmt = conn.prepare("SELECT data FROM entries ORDER BY seq DESC")?;
+        let entries = stmt
+            .query_map([], |r| r.get::<_, String>(0))?
+            .map(|data| {
+                serde_json::from_str::<Value>(&data?).map_err(|source| {
+                    Error::StorageCorrupt {
+                        what: "Entry in history database",
+                        source,
+                    }
+                    .into()
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if max_entries.is_none() && entries.is_empty() {
+            return Ok(None);
+        }
+        let max_entries = max_entries.and_then(|v| v.parse().ok()).unwrap_or(DEFAULT_MAX_ENTRIES);
+        let mut index = serde_json::json!({ "max_entries": max_entries, "entries": entries });
+        if let Some(max_pinned) = max_pinned.and_then(|v| v.parse::<u64>().ok()) {
+            index["max_pinned"] = max_pinned.into();
+        }
+        if let Some(version) = version {
+            index["version"] = version.parse::<u64>().unwrap_or(u64::MAX).into();
+        }
+        // The trash is small and read whole, so it's kept as one JSON value
+        if let Some(trash) = trash {
+            index["trash"] = serde_json::from_str(&trash).map_err(|source| {
+                Error::StorageCorrupt {
+                    what: "Trash in history database",
+                    source,
+                }
+            })?;
+        }
+        ClipIndex::from_json(index).map(Some)
+    }
+
+    fn write_index(&self, index: &ClipIndex) -> Result<()> {
+        self.with_transaction(|tx| {
+            tx.execute("DELETE FROM entries", [])?;
+            for (i, entry) in index.entries.iter().enumerate() {
+                insert(tx, entry, (index.entries.len() - i) as i64)?;
+            }
+            tx.execute(
+                "INSERT OR REPLACE INTO meta (key, value) VALUES ('max_entries', ?1)",
+                [index.max_entries.to_string()],
+            )?;
+            tx.execute(
+                "INSERT OR REPLACE INTO meta (key, value) VALUES ('max_pinned', ?1)",
+                [index.max_pinned.to_string()],
+            )?;
+            tx.execute(
+                "INSERT OR REPLACE INTO meta (key, value) VALUES ('version', ?1)",
+                [index.version.to_string()],
+            )?;
+            tx.execute(
+                "INSERT OR REPLACE INTO meta (key, value) VALUES ('trash', ?1)",
+                [serde_json::to_string(&index.trash)?],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn read_content(&self, id: &str) -> Result<String> {
+        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let data = conn
+            .query_row("SELECT data FROM content WHERE id = ?1", [id], |r| r.get(0))
+            .optional()?
+            .with_context(|| format!("Failed to read content: {}", id))?;
+        match data {
+            SqlValue::Text(content) => Ok(content),
+            SqlValue::Blob(data) => String::from_utf8(backend::decompress(data)?)
+                .with_context(|| format!("Content {} is not valid UTF-8", id)),
+            _ => anyhow::bail!("Content {} has an unexpected type", id),
+        }
+    }
+
+    fn write_content(&self, id: &str, content: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        insert_content(&conn, id, content, false)
+    }
+
+    fn write_compressed(&self, id: &str, content: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        insert_content(&conn, id, content, true)
+    }
+
+    fn remove_content(&self, id: &str) -> Result<u64> {
+        self.with_transaction(|tx| {
+            let size: Option<i64> = tx
+                .query_row(
+                    "SELECT length(CAST(data AS BLOB)) FROM content WHERE id = ?1",
+                    [id],
+                    |r| r.get(0),
+                )
+                .optional()?;
+            tx.execute("DELETE FROM content WHERE id = ?1", [id])?;
+            Ok(size.unwrap_or(0) as u64)
+        })
+    }
+
+    fn content_ids(&self) -> Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let mut stmt = conn.prepare("SELECT id FROM content")?;
+        let ids = stmt.query_map([], |r| r.get(0))?.collect::<rusqlite::Result<_>>()?;
+        Ok(ids)
+    }
+
+    fn stored_size(&self, id: &str) -> Result<u64> {
+        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let size: Option<i64> = conn
+            .query_row(
+                "SELECT length(CAST(data AS BLOB)) FROM content WHERE id = ?1",
+                [id],
+                |r| r.get(0),
+            )
+            .optional()?;
+        Ok(size.unwrap_or(0) as u64)
+    }
+
+    fn compact(&self) -> Result<u64> {
+        let before = self.file_size();
+        {
+            let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+            conn.execute_batch("VACUUM; PRAGMA wal_checkpoint(TRUNCATE);")
+                .context("Failed to compact the history database")?;
+        }
+        Ok(before.saturating_sub(self.file_size()))
+    }
+
+    fn change_stamp(&self) -> Option<u64> {
+        // Counts commits made through other connections, which is all a
+        // watcher needs; its own writes it already knows about
+        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        conn.query_row("PRAGMA data_version", [], |r| r.get::<_, i64>(0))
+            .ok()
+            .map(|v| v as u64)
+    }
+
+    fn content_file(&self, _id: &str) -> PathBuf {
+        self.path.clone()
+    }
+
+    fn record(&self, capture: Capture<'_>, limits: &Limits) -> Result<Recorded> {
+        self.with_transaction(|tx| {
+            let mut existing = tx
+                .query_row("SELECT data FROM entries WHERE hash = ?1", [&capture.hash], |r| {
+                    r.get::<_, String>(0)
+                })
+                .optional()?
+                .map(decode)
+                .transpose()?;
+            if existing.is_none() {
+                // Hashes saved by older versions use another algorithm
+                let mut stmt =
+                    tx.prepare("SELECT data FROM entries WHERE hash NOT LIKE 'blake3:%'")?;
+                for data in stmt.query_map([], |r| r.get::<_, String>(0))? {
+                    let entry = decode(data?)?;
+                    if capture.matcher.matches(&entry.hash) {
+                        existing = Some(entry);
+                        break;
+                    }
+                }
+            }
+
+            if let Some(mut existing) = existing {
+                existing.expires = capture.expires;
+                let (hash, meta) = (capture.hash, capture.meta);
+                if backend::merge_duplicate(&mut existing, hash, capture.content, meta) {
+                    insert_content(tx, &existing.id, capture.content, capture.compress)?;
+                }
+                put_front(tx, &existing)?;
+                return Ok(Recorded {
+                    entry: existing,
+                    duplicate: true,
+                    pruned: Vec::new(),
+                });
+            }
+
+            let timestamp = backend::next_timestamp(|id| {
+                tx.query_row("SELECT 1 FROM entries WHERE id = ?1", [id], |_| Ok(()))
+                    .optional()
+                    .is_ok_and(|found| found.is_some())
+            });
+            let mut entry =
+                backend::new_entry(timestamp, capture.content, capture.hash, capture.meta);
+            entry.expires = capture.expires;
+            insert_content(tx, &entry.id, capture.content, capture.compress)?;
+            put_front(tx, &entry)?;
+            let pruned = prune_tx(tx, limits)?;
+            Ok(Recorded {
+                entry,
+                duplicate: false,
+                pruned,
+            })
+        })
+    }
+
+    fn get_entry(&self, id: &str) -> Result<Option<ClipEntry>> {
+        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        conn.query_row("SELECT data FROM entries WHERE id = ?1", [id], |r| r.get::<_, String>(0))
+            .optional()?
+            .map(decode)
+            .transpose()
+    }
+
+    fn prune(&self, limits: &Limits) -> Result<Vec<ClipEntry>> {
+        self.with_transaction(|tx| prune_tx(tx, limits))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::EntryMeta;
+    use crate::util::{self, HashMatcher};
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    fn capture(content: &str) -> Capture<'_> {
+        Capture {
+            content,
+            hash: util::compute_hash_string(content),
+            matcher: HashMatcher::new(content),
+            meta: EntryMeta::default(),
+            compress: false,
+            expires: None,
+        }
+    }
+
+    #[test]
+    fn test_record_dedupes_and_prunes() {
+        let dir = TempDir::new().unwrap();
+        let (db, created) = SqliteBackend::open(&dir.path().join(DB_FILE_NAME)).unwrap();
+        assert!(created);
+        assert!(db.read_index().unwrap().is_none());
+        let quotas = HashMap::new();
+        let limits = Limits {
+            max_entries: 2,
+            quotas: &quotas,
+            max_age: None,
+            max_total_size: None,
+            keep_content: false,
+        };
+
+        let first = db.record(capture("one"), &limits).unwrap().entry;
+        db.record(capture("two"), &limits).unwrap();
+        let again = db.record(capture("one"), &limits).unwrap();
+        assert!(again.duplicate);
+        assert_eq!(again.entry.id, first.id);
+        let pruned = db.record(capture("three"), &limits).unwrap().pruned;
+        assert_eq!(pruned.len(), 1);
+        assert_eq!(pruned[0].preview, "two");
+
+        let index = db.read_index().unwrap().unwrap();
+        let previews: Vec<&str> = index.entries.iter().map(|e| e.preview.as_str()).collect();
+        assert_eq!(previews, ["three", "one"], "'two' was the oldest");
+        assert_eq!(db.content_ids().unwrap().len(), 2);
+        assert_eq!(db.read_content(&first.id).unwrap(), "one");
+        db.write_compressed(&first.id, "one").unwrap();
+        assert_eq!(db.read_content(&first.id).unwrap(), "one");
+        db.write_content(&first.id, "one").unwrap();
+        assert_eq!(db.stored_size(&first.id).unwrap(), 3);
+        assert_eq!(db.remove_content(&first.id).unwrap(), 3);
+        assert!(db.read_content(&first.id).is_err());
+    }
+
+    #[test]
+    fn test_legacy_hash_still_matches() {
+        let dir = TempDir::new().unwrap();
+        let (db, _) = SqliteBackend::open(&dir.path().join(DB_FILE_NAME)).unwrap();
+        let mut legacy = backend::new_entry(1, "old", String::new(), EntryMeta::default());
+        legacy.hash = util::HashAlgorithm::Sha256.hash_string("old");
+        db.write_index(&ClipIndex {
+            max_entries: 10,
+            max_pinned: 7,
+            entries: vec![legacy],
+            ..Default::default()
+        })
+        .unwrap();
+
+        let quotas = HashMap::new();
+        let limits = Limits {
+            max_entries: 10,
+            quotas: &quotas,
+            max_age: None,
+            max_total_size: None,
+            keep_content: false,
+        };
+        let recorded = db.record(capture("old"), &limits).unwrap();
+        assert!(recorded.duplicate);
+        assert_eq!(recorded.entry.id, "1");
+        assert!(recorded.entry.hash.starts_with("blake3:"));
+        let index = db.read_index().unwrap().unwrap();
+        assert_eq!((index.max_entries, index.max_pinned), (10, 7));
+    }
+
+    #[test]
+    fn test_prune_expires_by_last_copy() {
+        let dir = TempDir::new().unwrap();
+        let (db, _) = SqliteBackend::open(&dir.path().join(DB_FILE_NAME)).unwrap();
+        let quotas = HashMap::new();
+        let mut limits = Limits {
+            max_entries: 10,
+            quotas: &quotas,
+            max_age: None,
+            max_total_size: None,
+            keep_content: false,
+        };
+        let old = db.record(capture("old"), &limits).unwrap().entry;
+        let recopied = db.record(capture("recopied"), &limits).unwrap().entry;
+        let mut index = db.read_index().unwrap().unwrap();
+        for entry in &mut index.entries {
+            entry.timestamp -= 7_200_000;
+        }
+        db.write_index(&index).unwrap();
+        let again = db.record(capture("recopied"), &limits).unwrap();
+        assert!(again.entry.timestamp >= recopied.timestamp, "Copying again refreshes the time");
+
+        limits.max_age = Some(Duration::from_secs(3600));
+        let expired = db.prune(&limits).unwrap();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].id, old.id);
+        assert!(db.read_content(&old.id).is_err());
+        assert_eq!(db.read_index().unwrap().unwrap().entries.len(), 1);
+    }
+
+    #[test]
+    fn test_prune_to_total_size() {
+        let dir = TempDir::new().unwrap();
+        let (db, _) = SqliteBackend::open(&dir.path().join(DB_FILE_NAME)).unwrap();
+        let quotas = HashMap::new();
+        let mut limits = Limits {
+            max_entries: 10,
+            quotas: &quotas,
+            max_age: None,
+            max_total_size: None,
+            keep_content: false,
+        };
+        for content in ["aaaa", "bbbb", "cccc", "dddddddd"] {
+            db.record(capture(content), &limits).unwrap();
+        }
+
+        limits.max_total_size = Some(12);
+        let removed: Vec<String> =
+            db.prune(&limits).unwrap().into_iter().map(|e| e.preview).collect();
+        assert_eq!(removed, ["aaaa", "bbbb"]);
+
+        limits.max_total_size = Some(1);
+        db.prune(&limits).unwrap();
+        let index = db.read_index().unwrap().unwrap();
+        let previews: Vec<&str> = index.entries.iter().map(|e| e.preview.as_str()).collect();
+        assert_eq!(previews, ["dddddddd"], "The newest entry stays");
+    }
+
+    #[test]
+    fn test_change_stamp_sees_other_connections() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(DB_FILE_NAME);
+        let (reader, _) = SqliteBackend::open(&path).unwrap();
+        let (writer, _) = SqliteBackend::open(&path).unwrap();
+        let before = reader.change_stamp();
+        assert!(before.is_some());
+
+        writer.write_content("1", "new").unwrap();
+        let after = reader.change_stamp();
+        assert_ne!(after, before);
+        assert_eq!(reader.change_stamp(), after);
+        assert_eq!(reader.content_file("1"), path, "Every entry lives in the database");
+    }
+}
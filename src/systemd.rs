@@ -0,0 +1,242 @@
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// User unit of the profile this process runs as; each profile's daemon gets its own
+pub fn unit_name() -> String {
+    match crate::profile::current() {
+        Some(profile) => format!("clipstack-{}.service", profile),
+        None => "clipstack.service".to_string(),
+    }
+}
+
+/// System unit running `daemon system` for every user
+pub const SYSTEM_UNIT_NAME: &str = "clipstack-system.service";
+
+/// Path of the generated systemd user unit
+pub fn unit_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("systemd")
+        .join("user")
+        .join(unit_name())
+}
+
+/// Path of the generated system unit
+pub fn system_unit_path() -> PathBuf {
+    Path::new("/etc/systemd/system").join(SYSTEM_UNIT_NAME)
+}
+
+/// `<exe> daemon` followed by `args`, quoted for ExecStart
+fn exec_start(exe: &Path, args: &[String]) -> String {
+    let mut exec_start = quote_arg(&exe.to_string_lossy());
+    exec_start.push_str(" daemon");
+    for arg in args {
+        exec_start.push(' ');
+        exec_start.push_str(&quote_arg(arg));
+    }
+    exec_start
+}
+
+/// Render the user unit. `args` are appended to `<exe> daemon` so custom
+/// storage/config paths survive into the service.
+pub fn unit_file(exe: &Path, args: &[String], storage_dir: &Path) -> String {
+    let exec_start = exec_start(exe, args);
+    format!(
+        "[Unit]
+Description=Clipstack clipboard history daemon
+PartOf=graphical-session.target
+After=graphical-session.target
+
+[Service]
+Type=notify
+ExecStart={exec_start}
+ExecReload=/bin/kill -HUP $MAINPID
+Restart=on-failure
+RestartSec=5
+WatchdogSec=30
+
+# Security hardening
+NoNewPrivileges=true
+ProtectSystem=strict
+ProtectHome=read-only
+ReadWritePaths={storage} %t
+
+[Install]
+WantedBy=graphical-session.target
+",
+        storage = quote_arg(&storage_dir.to_string_lossy()),
+    )
+}
+
+/// Render the system unit for `daemon system`. It runs as root so it can
+/// start each user's daemon under their own uid; `args` reach every user daemon.
+pub fn system_unit_file(exe: &Path, root: &Path, args: &[String]) -> String {
+    let mut daemon_args = vec!["system".to_string(), format!("--root={}", root.display())];
+    daemon_args.extend(args.iter().cloned());
+    let exec_start = exec_start(exe, &daemon_args);
+    format!(
+        "[Unit]
+Description=Clipstack clipboard history for every Wayland session
+After=systemd-user-sessions.service
+
+[Service]
+ExecStart={exec_start}
+Restart=on-failure
+RestartSec=5
+# User daemons are stopped by the supervisor itself on SIGTERM
+KillMode=mixed
+ProtectSystem=full
+
+[Install]
+WantedBy=multi-user.target
+"
+    )
+}
+
+/// Write a unit file, refusing to clobber an existing one unless forced
+pub fn install_service(path: &Path, unit: &str, force: bool) -> Result<()> {
+    if path.exists() && !force {
+        bail!("{:?} already exists (use --force to overwrite)", path);
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {:?}", parent))?;
+    }
+    fs::write(path, unit).with_context(|| format!("Failed to write unit: {:?}", path))?;
+    Ok(())
+}
+
+/// Whether a process runs inside our systemd unit (so systemctl should manage it)
+pub fn manages_pid(pid: u32) -> bool {
+    fs::read_to_string(format!("/proc/{}/cgroup", pid))
+        .map(|cgroup| cgroup.lines().any(|l| l.ends_with(&format!("/{}", unit_name()))))
+        .unwrap_or(false)
+}
+
+/// Quote an ExecStart argument if it contains whitespace, quotes or specifiers
+fn quote_arg(arg: &str) -> String {
+    let arg = arg.replace('%', "%%");
+    if arg.is_empty() || arg.contains(|c: char| c.is_whitespace() || c == '"' || c == '\\') {
+        format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        arg
+    }
+}
+
+/// Send a state update (`READY=1`, `WATCHDOG=1`, ...) to the service manager.
+/// Returns false when not running under systemd or the send failed.
+pub fn notify(state: &str) -> bool {
+    match std::env::var("NOTIFY_SOCKET") {
+        Ok(socket) => send_to(&socket, state).is_ok(),
+        Err(_) => false,
+    }
+}
+
+fn send_to(socket: &str, state: &str) -> std::io::Result<()> {
+    let sock = UnixDatagram::unbound()?;
+    // A leading '@' denotes a Linux abstract socket
+    if let Some(name) = socket.strip_prefix('@') {
+        use std::os::linux::net::SocketAddrExt;
+        let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+        sock.send_to_addr(state.as_bytes(), &addr)?;
+    } else {
+        sock.send_to(state.as_bytes(), socket)?;
+    }
+    Ok(())
+}
+
+/// How often to ping the watchdog: half the configured `WatchdogSec`, or
+/// None if systemd didn't enable a watchdog for this process
+pub fn watchdog_interval() -> Option<Duration> {
+    parse_watchdog(
+        std::env::var("WATCHDOG_USEC").ok().as_deref(),
+        std::env::var("WATCHDOG_PID").ok().as_deref(),
+        std::process::id(),
+    )
+}
+
+fn parse_watchdog(usec: Option<&str>, pid: Option<&str>, own_pid: u32) -> Option<Duration> {
+    let usec: u64 = usec?.parse().ok().filter(|&u| u > 0)?;
+    if let Some(pid) = pid
+        && pid.parse::<u32>().ok() != Some(own_pid)
+    {
+        return None;
+    }
+    Some(Duration::from_micros(usec / 2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_unit_file_contents() {
+        let unit = unit_file(
+            Path::new("/usr/bin/clipstack"),
+            &["--storage-dir".to_string(), "/home/me/My Clips".to_string()],
+            Path::new("/home/me/My Clips"),
+        );
+
+        assert!(unit.contains(
+            "ExecStart=/usr/bin/clipstack daemon --storage-dir \"/home/me/My Clips\"\n"
+        ));
+        assert!(unit.contains("Type=notify\n"));
+        assert!(unit.contains("Restart=on-failure\n"));
+        assert!(unit.contains("WantedBy=graphical-session.target\n"));
+    }
+
+    #[test]
+    fn test_system_unit_file_contents() {
+        let unit = system_unit_file(
+            Path::new("/usr/bin/clipstack"),
+            Path::new("/var/lib/clipstack"),
+            &["--config".to_string(), "/etc/clipstack/config.toml".to_string()],
+        );
+
+        assert!(unit.contains(
+            "ExecStart=/usr/bin/clipstack daemon system --root=/var/lib/clipstack \
+             --config /etc/clipstack/config.toml\n"
+        ));
+        assert!(unit.contains("WantedBy=multi-user.target\n"));
+        assert!(!unit.contains("User="), "Runs as root to switch to each user");
+    }
+
+    #[test]
+    fn test_quote_arg() {
+        assert_eq!(quote_arg("plain"), "plain");
+        assert_eq!(quote_arg("100%"), "100%%");
+        assert_eq!(quote_arg("a \"b\""), "\"a \\\"b\\\"\"");
+    }
+
+    #[test]
+    fn test_send_to_socket() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("notify.sock");
+        let listener = UnixDatagram::bind(&path).unwrap();
+
+        send_to(path.to_str().unwrap(), "READY=1").unwrap();
+
+        let mut buf = [0u8; 64];
+        let n = listener.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"READY=1");
+    }
+
+    #[test]
+    fn test_parse_watchdog() {
+        assert_eq!(
+            parse_watchdog(Some("30000000"), None, 42),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(
+            parse_watchdog(Some("30000000"), Some("42"), 42),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(parse_watchdog(Some("30000000"), Some("7"), 42), None);
+        assert_eq!(parse_watchdog(Some("0"), None, 42), None);
+        assert_eq!(parse_watchdog(None, None, 42), None);
+    }
+}
@@ -0,0 +1,231 @@
+use crate::config::ThemeConfig;
+use anyhow::{Context, Result};
+use ratatui::style::{Color, Modifier, Style};
+
+/// Names accepted by `[theme] name`
+pub const THEME_NAMES: &[&str] = &["dark", "light", "solarized"];
+
+/// Colors the picker draws with, from a built-in theme and `[theme]` overrides
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    /// Borders of panes without focus
+    pub border: Color,
+    /// Search box in search mode, action menu, preview border, size column
+    pub accent: Color,
+    /// Preview border while it has focus
+    pub focus: Color,
+    /// Background of the selected row
+    pub highlight_bg: Color,
+    /// Text of the selected row
+    pub highlight_fg: Color,
+    /// Pin star and snippet names
+    pub pin: Color,
+    /// Characters matched by the search
+    pub matched: Color,
+    /// Times, hints and other secondary text
+    pub dim: Color,
+    /// Mark order, content matches and the tag manager
+    pub mark: Color,
+    /// Snippets section and the source app column
+    pub snippet: Color,
+    /// Kind icons
+    pub kind: Color,
+    pub success: Color,
+    pub warning: Color,
+    /// No colors at all (`NO_COLOR`): the selection is shown reversed instead
+    pub plain: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl Theme {
+    fn dark() -> Self {
+        Self {
+            border: Color::White,
+            accent: Color::Cyan,
+            focus: Color::Yellow,
+            highlight_bg: Color::Blue,
+            highlight_fg: Color::White,
+            pin: Color::Yellow,
+            matched: Color::Yellow,
+            dim: Color::DarkGray,
+            mark: Color::Magenta,
+            snippet: Color::Green,
+            kind: Color::Blue,
+            success: Color::Green,
+            warning: Color::Yellow,
+            plain: false,
+        }
+    }
+
+    fn light() -> Self {
+        Self {
+            border: Color::Black,
+            accent: Color::Blue,
+            focus: Color::Magenta,
+            highlight_bg: Color::Blue,
+            highlight_fg: Color::White,
+            pin: Color::Red,
+            matched: Color::Red,
+            dim: Color::Gray,
+            mark: Color::Magenta,
+            snippet: Color::Green,
+            kind: Color::Blue,
+            success: Color::Green,
+            warning: Color::Red,
+            plain: false,
+        }
+    }
+
+    fn solarized() -> Self {
+        Self {
+            border: Color::Rgb(0x83, 0x94, 0x96),
+            accent: Color::Rgb(0x2a, 0xa1, 0x98),
+            focus: Color::Rgb(0xb5, 0x89, 0x00),
+            highlight_bg: Color::Rgb(0x07, 0x36, 0x42),
+            highlight_fg: Color::Rgb(0x93, 0xa1, 0xa1),
+            pin: Color::Rgb(0xb5, 0x89, 0x00),
+            matched: Color::Rgb(0xcb, 0x4b, 0x16),
+            dim: Color::Rgb(0x58, 0x6e, 0x75),
+            mark: Color::Rgb(0xd3, 0x36, 0x82),
+            snippet: Color::Rgb(0x85, 0x99, 0x00),
+            kind: Color::Rgb(0x26, 0x8b, 0xd2),
+            success: Color::Rgb(0x85, 0x99, 0x00),
+            warning: Color::Rgb(0xcb, 0x4b, 0x16),
+            plain: false,
+        }
+    }
+
+    /// Every color reset to the terminal's own
+    fn plain() -> Self {
+        Self {
+            border: Color::Reset,
+            accent: Color::Reset,
+            focus: Color::Reset,
+            highlight_bg: Color::Reset,
+            highlight_fg: Color::Reset,
+            pin: Color::Reset,
+            matched: Color::Reset,
+            dim: Color::Reset,
+            mark: Color::Reset,
+            snippet: Color::Reset,
+            kind: Color::Reset,
+            success: Color::Reset,
+            warning: Color::Reset,
+            plain: true,
+        }
+    }
+
+    /// The theme for `config`, or no colors when `NO_COLOR` is set
+    pub fn load(config: &ThemeConfig) -> Result<Self> {
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        Self::resolve(config, no_color)
+    }
+
+    fn resolve(config: &ThemeConfig, no_color: bool) -> Result<Self> {
+        let mut theme = match config.name.as_str() {
+            "dark" => Self::dark(),
+            "light" => Self::light(),
+            "solarized" => Self::solarized(),
+            other => anyhow::bail!(
+                "unknown theme '{}' (expected one of: {})",
+                other,
+                THEME_NAMES.join(", ")
+            ),
+        };
+        let overrides = [
+            ("border", &config.border, &mut theme.border),
+            ("accent", &config.accent, &mut theme.accent),
+            ("focus", &config.focus, &mut theme.focus),
+            ("highlight_bg", &config.highlight_bg, &mut theme.highlight_bg),
+            ("highlight_fg", &config.highlight_fg, &mut theme.highlight_fg),
+            ("pin", &config.pin, &mut theme.pin),
+            ("match", &config.matched, &mut theme.matched),
+            ("dim", &config.dim, &mut theme.dim),
+            ("mark", &config.mark, &mut theme.mark),
+            ("snippet", &config.snippet, &mut theme.snippet),
+            ("kind", &config.kind, &mut theme.kind),
+            ("success", &config.success, &mut theme.success),
+            ("warning", &config.warning, &mut theme.warning),
+        ];
+        for (key, value, color) in overrides {
+            if let Some(value) = value {
+                *color = parse_color(value).with_context(|| format!("bad theme.{}", key))?;
+            }
+        }
+        // Overrides are still checked above so a bad one isn't hidden by NO_COLOR
+        Ok(if no_color { Self::plain() } else { theme })
+    }
+
+    /// Style of a pane's selected row, on `bg`
+    pub fn highlight(&self, bg: Color) -> Style {
+        let style = Style::default().add_modifier(Modifier::BOLD);
+        if self.plain {
+            style.add_modifier(Modifier::REVERSED)
+        } else {
+            style.bg(bg).fg(self.highlight_fg)
+        }
+    }
+}
+
+/// A color name ("cyan", "lightblue"), a 256-color index or "#rrggbb"
+fn parse_color(value: &str) -> Result<Color> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| anyhow::anyhow!("'{}' is not a color name, index or #rrggbb", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(name: &str) -> ThemeConfig {
+        ThemeConfig {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_builtin_themes_and_overrides() {
+        assert_eq!(Theme::resolve(&ThemeConfig::default(), false).unwrap(), Theme::dark());
+        let light = Theme::resolve(&config("light"), false).unwrap();
+        assert_eq!(light.border, Color::Black);
+        let solarized = Theme::resolve(&config("solarized"), false).unwrap();
+        assert_eq!(solarized.accent, Color::Rgb(0x2a, 0xa1, 0x98));
+
+        let mut custom = config("light");
+        custom.matched = Some("#ff8800".to_string());
+        custom.pin = Some("lightyellow".to_string());
+        custom.dim = Some("244".to_string());
+        let theme = Theme::resolve(&custom, false).unwrap();
+        assert_eq!(theme.matched, Color::Rgb(0xff, 0x88, 0x00));
+        assert_eq!(theme.pin, Color::LightYellow);
+        assert_eq!(theme.dim, Color::Indexed(244));
+        assert_eq!(theme.border, Color::Black, "Unset colors come from the named theme");
+
+        assert!(Theme::resolve(&config("neon"), false).is_err());
+        custom.border = Some("blurple".to_string());
+        let err = Theme::resolve(&custom, false).unwrap_err();
+        assert!(format!("{:#}", err).contains("theme.border"));
+    }
+
+    #[test]
+    fn test_no_color_drops_all_colors() {
+        let theme = Theme::resolve(&config("solarized"), true).unwrap();
+        assert!(theme.plain);
+        assert_eq!(theme.accent, Color::Reset);
+        let highlight = theme.highlight(theme.highlight_bg);
+        assert!(highlight.add_modifier.contains(Modifier::REVERSED));
+        assert_eq!(highlight.bg, None);
+
+        let mut bad = config("dark");
+        bad.warning = Some("nope".to_string());
+        assert!(Theme::resolve(&bad, true).is_err());
+    }
+}
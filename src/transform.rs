@@ -0,0 +1,952 @@
+use anyhow::{bail, Result};
+use chrono::Local;
+use std::fs;
+
+/// Case conversions only apply to identifier-like text of at most this length
+const MAX_IDENTIFIER_LEN: usize = 256;
+
+/// A transform offered in the picker's action menu and `clipstack get --transform`
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum Transform {
+    Calculate,
+    Trim,
+    OneLine,
+    Uppercase,
+    Lowercase,
+    CamelCase,
+    SnakeCase,
+    KebabCase,
+    UrlEncode,
+    UrlDecode,
+    Base64Encode,
+    Base64Decode,
+    HtmlEncode,
+    HtmlDecode,
+    JsonPretty,
+    JsonMinify,
+    HexToDec,
+    DecToHex,
+    SortLines,
+    UniqueLines,
+    ReverseLines,
+    ShuffleLines,
+    CountLines,
+}
+
+impl Transform {
+    /// Every transform, in menu order
+    pub const ALL: &'static [Transform] = &[
+        Transform::Calculate,
+        Transform::Trim,
+        Transform::OneLine,
+        Transform::Uppercase,
+        Transform::Lowercase,
+        Transform::CamelCase,
+        Transform::SnakeCase,
+        Transform::KebabCase,
+        Transform::UrlEncode,
+        Transform::UrlDecode,
+        Transform::Base64Encode,
+        Transform::Base64Decode,
+        Transform::HtmlEncode,
+        Transform::HtmlDecode,
+        Transform::JsonPretty,
+        Transform::JsonMinify,
+        Transform::HexToDec,
+        Transform::DecToHex,
+        Transform::SortLines,
+        Transform::UniqueLines,
+        Transform::ReverseLines,
+        Transform::ShuffleLines,
+        Transform::CountLines,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Transform::Calculate => "Calculate",
+            Transform::Trim => "Trim",
+            Transform::OneLine => "One line",
+            Transform::Uppercase => "UPPERCASE",
+            Transform::Lowercase => "lowercase",
+            Transform::CamelCase => "camelCase",
+            Transform::SnakeCase => "snake_case",
+            Transform::KebabCase => "kebab-case",
+            Transform::UrlEncode => "URL encode",
+            Transform::UrlDecode => "URL decode",
+            Transform::Base64Encode => "Base64 encode",
+            Transform::Base64Decode => "Base64 decode",
+            Transform::HtmlEncode => "HTML encode",
+            Transform::HtmlDecode => "HTML decode",
+            Transform::JsonPretty => "JSON pretty",
+            Transform::JsonMinify => "JSON minify",
+            Transform::HexToDec => "Hex → dec",
+            Transform::DecToHex => "Dec → hex",
+            Transform::SortLines => "Sort lines",
+            Transform::UniqueLines => "Unique lines",
+            Transform::ReverseLines => "Reverse lines",
+            Transform::ShuffleLines => "Shuffle lines",
+            Transform::CountLines => "Count lines",
+        }
+    }
+
+    /// Apply the transform, failing if it doesn't make sense for the input
+    /// or wouldn't change it
+    pub fn apply(self, input: &str) -> Result<String> {
+        let output = match self {
+            Transform::Calculate => return calculate(input).map(format_number),
+            Transform::Trim if input.trim().is_empty() => bail!("nothing but whitespace"),
+            Transform::Trim => input.trim().to_string(),
+            Transform::OneLine if !input.trim().contains('\n') => bail!("already one line"),
+            // Every run of whitespace, line breaks included, becomes one space
+            Transform::OneLine => input.split_whitespace().collect::<Vec<_>>().join(" "),
+            Transform::Uppercase => input.to_uppercase(),
+            Transform::Lowercase => input.to_lowercase(),
+            Transform::CamelCase => {
+                let words = split_words(input)?;
+                let mut out = words[0].to_lowercase();
+                for word in &words[1..] {
+                    out.push_str(&capitalize(word));
+                }
+                out
+            }
+            Transform::SnakeCase => split_words(input)?.join("_").to_lowercase(),
+            Transform::KebabCase => split_words(input)?.join("-").to_lowercase(),
+            Transform::UrlEncode => url_encode(input),
+            Transform::UrlDecode => url_decode(input)?,
+            Transform::Base64Encode => crate::util::encode_base64(input.as_bytes()),
+            Transform::Base64Decode => base64_decode(input)?,
+            Transform::HtmlEncode => html_encode(input),
+            Transform::HtmlDecode => html_decode(input),
+            Transform::JsonPretty => reformat_json(input, true)?,
+            Transform::JsonMinify => reformat_json(input, false)?,
+            Transform::HexToDec => hex_to_dec(input)?,
+            Transform::DecToHex => dec_to_hex(input)?,
+            Transform::SortLines => map_lines(input, |lines| lines.sort())?,
+            Transform::UniqueLines => map_lines(input, |lines| {
+                let mut seen = std::collections::HashSet::new();
+                lines.retain(|line| seen.insert(*line));
+            })?,
+            Transform::ReverseLines => map_lines(input, |lines| lines.reverse())?,
+            // A shuffle may legitimately land on the original order
+            Transform::ShuffleLines => return map_lines(input, |lines| shuffle(lines)),
+            Transform::CountLines => count_lines(input)?,
+        };
+        if output == input {
+            bail!("{} leaves the input unchanged", self.label());
+        }
+        Ok(output)
+    }
+}
+
+/// Transforms that succeed on the input, paired with their results; none for
+/// blank input, which every encoder would still accept
+pub fn applicable(input: &str) -> Vec<(Transform, String)> {
+    if input.trim().is_empty() {
+        return Vec::new();
+    }
+    Transform::ALL
+        .iter()
+        .filter_map(|&t| t.apply(input).ok().map(|out| (t, out)))
+        .collect()
+}
+
+/// Expand `{{...}}` placeholders in a snippet template.
+///
+/// Supported placeholders:
+/// - `{{date}}` / `{{date:%d.%m.%Y}}` - local date (chrono format, default `%Y-%m-%d`)
+/// - `{{time}}` / `{{time:%H:%M}}` - local time (default `%H:%M:%S`)
+/// - `{{hostname}}` - machine hostname
+/// - `{{uuid}}` - random v4 UUID
+/// - `{{env:NAME}}` or `{{NAME}}` (all caps) - environment variable, empty if unset
+///
+/// Unknown placeholders are left untouched so literal braces survive.
+pub fn expand_template(template: &str) -> String {
+    expand_with(template, |name| std::env::var(name).ok())
+}
+
+fn expand_with(template: &str, env: impl Fn(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+
+        let Some(end) = after.find("}}") else {
+            out.push_str(&rest[start..]);
+            return out;
+        };
+
+        let placeholder = &after[..end];
+        match expand_placeholder(placeholder.trim(), &env) {
+            Some(value) => out.push_str(&value),
+            None => out.push_str(&rest[start..start + 2 + end + 2]),
+        }
+        rest = &after[end + 2..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+fn expand_placeholder(placeholder: &str, env: &impl Fn(&str) -> Option<String>) -> Option<String> {
+    let (name, arg) = match placeholder.split_once(':') {
+        Some((name, arg)) => (name, Some(arg)),
+        None => (placeholder, None),
+    };
+
+    match name {
+        "date" => Some(format_now(arg.unwrap_or("%Y-%m-%d"))),
+        "time" => Some(format_now(arg.unwrap_or("%H:%M:%S"))),
+        "hostname" => Some(hostname()),
+        "uuid" => Some(uuid::Uuid::new_v4().to_string()),
+        "env" => arg.map(|var| env(var).unwrap_or_default()),
+        _ if arg.is_none() && is_env_name(name) => Some(env(name).unwrap_or_default()),
+        _ => None,
+    }
+}
+
+/// Format the current local time, rejecting invalid format strings instead of panicking
+fn format_now(format: &str) -> String {
+    use chrono::format::{Item, StrftimeItems};
+    use std::fmt::Write;
+
+    let items: Vec<Item> = StrftimeItems::new(format).collect();
+    if items.iter().any(|i| matches!(i, Item::Error)) {
+        return format!("{{{{invalid format: {}}}}}", format);
+    }
+
+    let mut out = String::new();
+    let _ = write!(out, "{}", Local::now().format_with_items(items.into_iter()));
+    out
+}
+
+fn hostname() -> String {
+    ["/proc/sys/kernel/hostname", "/etc/hostname"]
+        .iter()
+        .find_map(|p| fs::read_to_string(p).ok())
+        .map(|s| s.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// Bare placeholders are env vars only when they look like one (HOME, AWS_PROFILE)
+fn is_env_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+}
+
+/// Evaluate an arithmetic expression: `+ - * / % ^`, parentheses, unary minus.
+/// A lone number is rejected since there's nothing to calculate.
+pub fn calculate(input: &str) -> Result<f64> {
+    let mut parser = Calc {
+        chars: input.trim().chars().filter(|c| !c.is_whitespace()).collect(),
+        pos: 0,
+        saw_operator: false,
+        depth: 0,
+    };
+    if parser.chars.is_empty() {
+        bail!("empty expression");
+    }
+
+    let value = parser.expr()?;
+    if parser.pos != parser.chars.len() {
+        bail!("unexpected '{}'", parser.chars[parser.pos]);
+    }
+    if !parser.saw_operator {
+        bail!("not an expression");
+    }
+    if !value.is_finite() {
+        bail!("result is not a finite number");
+    }
+    Ok(value)
+}
+
+/// Format a result without float noise: integers plainly, others to 10 decimals
+fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        return format!("{}", value as i64);
+    }
+    let s = format!("{:.10}", value);
+    s.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// Deepest nesting of parentheses, signs and powers `calculate` accepts;
+/// far past any real expression, well before the stack runs out
+const MAX_DEPTH: usize = 256;
+
+/// Recursive-descent parser over the expression characters
+struct Calc {
+    chars: Vec<char>,
+    pos: usize,
+    saw_operator: bool,
+    depth: usize,
+}
+
+impl Calc {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            self.saw_operator |= c != '(' && c != ')';
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Parse with `parse` one level deeper, refusing to go past `MAX_DEPTH`
+    fn nested(&mut self, parse: fn(&mut Self) -> Result<f64>) -> Result<f64> {
+        if self.depth == MAX_DEPTH {
+            bail!("expression is nested too deeply");
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    // expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<f64> {
+        let mut value = self.term()?;
+        loop {
+            if self.eat('+') {
+                value += self.term()?;
+            } else if self.eat('-') {
+                value -= self.term()?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    // term := power (('*' | 'x' | '/' | '%') power)*
+    fn term(&mut self) -> Result<f64> {
+        let mut value = self.power()?;
+        loop {
+            if self.eat('*') || self.eat('x') || self.eat('×') {
+                value *= self.power()?;
+            } else if self.eat('/') {
+                let rhs = self.power()?;
+                if rhs == 0.0 {
+                    bail!("division by zero");
+                }
+                value /= rhs;
+            } else if self.eat('%') {
+                let rhs = self.power()?;
+                if rhs == 0.0 {
+                    bail!("division by zero");
+                }
+                value %= rhs;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    // power := unary ('^' power)?   (right-associative)
+    fn power(&mut self) -> Result<f64> {
+        let base = self.unary()?;
+        if self.eat('^') {
+            Ok(base.powf(self.nested(Self::power)?))
+        } else {
+            Ok(base)
+        }
+    }
+
+    // unary := '-' unary | '+' unary | atom
+    fn unary(&mut self) -> Result<f64> {
+        if self.eat('-') {
+            Ok(-self.nested(Self::unary)?)
+        } else if self.eat('+') {
+            self.nested(Self::unary)
+        } else {
+            self.atom()
+        }
+    }
+
+    // atom := number | '(' expr ')'
+    fn atom(&mut self) -> Result<f64> {
+        if self.eat('(') {
+            let value = self.nested(Self::expr)?;
+            if !self.eat(')') {
+                bail!("missing ')'");
+            }
+            return Ok(value);
+        }
+
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if c.is_ascii_digit() || c == '.' || c == '_') {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            match self.peek() {
+                Some(c) => bail!("unexpected '{}'", c),
+                None => bail!("unexpected end of expression"),
+            }
+        }
+
+        let number: String = self.chars[start..self.pos].iter().filter(|&&c| c != '_').collect();
+        number
+            .parse()
+            .map_err(|_| anyhow::anyhow!("invalid number '{}'", number))
+    }
+}
+
+/// Split identifier-like text into words at separators (`_`, `-`, `.`, spaces)
+/// and case boundaries (`parseHTTPResponse` -> parse, HTTP, Response)
+fn split_words(input: &str) -> Result<Vec<String>> {
+    let input = input.trim();
+    if input.len() > MAX_IDENTIFIER_LEN || input.contains('\n') {
+        bail!("not an identifier");
+    }
+    if let Some(c) = input
+        .chars()
+        .find(|c| !c.is_alphanumeric() && !matches!(c, '_' | '-' | '.' | ' '))
+    {
+        bail!("unexpected '{}' in identifier", c);
+    }
+
+    let mut words = Vec::new();
+    for part in input.split(['_', '-', '.', ' ']).filter(|p| !p.is_empty()) {
+        let chars: Vec<char> = part.chars().collect();
+        let mut start = 0;
+        for i in 1..chars.len() {
+            let (prev, cur) = (chars[i - 1], chars[i]);
+            let next_lower = chars.get(i + 1).is_some_and(|c| c.is_lowercase());
+            // aB, or the last capital of an acronym followed by lowercase (HTTPServer)
+            if (prev.is_lowercase() && cur.is_uppercase())
+                || (prev.is_uppercase() && cur.is_uppercase() && next_lower)
+            {
+                words.push(chars[start..i].iter().collect());
+                start = i;
+            }
+        }
+        words.push(chars[start..].iter().collect());
+    }
+
+    if words.is_empty() {
+        bail!("no words found");
+    }
+    Ok(words)
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect(),
+        None => String::new(),
+    }
+}
+
+/// Percent-encode everything except RFC 3986 unreserved characters
+fn url_encode(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for byte in input.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(byte as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+fn url_decode(input: &str) -> Result<String> {
+    if !input.contains('%') {
+        bail!("nothing to decode");
+    }
+
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = input
+                .get(i + 1..i + 3)
+                .and_then(|h| u8::from_str_radix(h, 16).ok());
+            let Some(byte) = hex else {
+                bail!("invalid escape at byte {}", i);
+            };
+            out.push(byte);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).map_err(|_| anyhow::anyhow!("decoded bytes are not valid UTF-8"))
+}
+
+/// Decoded base64 that reads as text; binary or control characters mean the
+/// input only happened to be valid base64
+fn base64_decode(input: &str) -> Result<String> {
+    let compact: String = input.split_whitespace().collect();
+    if compact.len() < 4 {
+        bail!("too short to be base64");
+    }
+    let bytes = crate::util::decode_base64(&compact).map_err(anyhow::Error::msg)?;
+    let text = String::from_utf8(bytes)
+        .map_err(|_| anyhow::anyhow!("decoded bytes are not valid UTF-8"))?;
+    if text.chars().any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t')) {
+        bail!("decoded bytes are not text");
+    }
+    Ok(text)
+}
+
+/// Re-space a JSON object or array without parsing it into values, so key
+/// order and number spelling survive: `pretty` puts each member on its own
+/// line with two-space indents, otherwise all whitespace between tokens goes
+fn reformat_json(input: &str, pretty: bool) -> Result<String> {
+    let input = input.trim();
+    if !(input.starts_with('{') || input.starts_with('['))
+        || serde_json::from_str::<serde::de::IgnoredAny>(input).is_err()
+    {
+        bail!("not a JSON object or array");
+    }
+    let newline = |out: &mut String, depth: usize| {
+        out.push('\n');
+        out.push_str(&"  ".repeat(depth));
+    };
+    let mut out = String::with_capacity(input.len());
+    let mut depth = 0;
+    let (mut in_string, mut escaped) = (false, false);
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_string = true;
+                out.push(c);
+            }
+            '{' | '[' => {
+                out.push(c);
+                depth += 1;
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                // Empty ones stay `{}` and `[]`
+                if pretty && !matches!(chars.peek(), Some('}' | ']')) {
+                    newline(&mut out, depth);
+                }
+            }
+            '}' | ']' => {
+                depth -= 1;
+                if pretty && !out.ends_with(['{', '[']) {
+                    newline(&mut out, depth);
+                }
+                out.push(c);
+            }
+            ',' => {
+                out.push(c);
+                if pretty {
+                    newline(&mut out, depth);
+                }
+            }
+            ':' => out.push_str(if pretty { ": " } else { ":" }),
+            c if c.is_whitespace() => {}
+            c => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+fn html_encode(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Decode named (`&amp;`, `&nbsp;`, ...) and numeric (`&#39;`, `&#x27;`) entities.
+/// Unknown entities are left as-is.
+fn html_decode(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let decoded = after.find(';').filter(|&end| end <= 10).and_then(|end| {
+            let entity = &after[..end];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{a0}'),
+                _ => entity
+                    .strip_prefix("#x")
+                    .or_else(|| entity.strip_prefix("#X"))
+                    .map(|hex| u32::from_str_radix(hex, 16))
+                    .or_else(|| entity.strip_prefix('#').map(|dec| dec.parse()))
+                    .and_then(|n| n.ok())
+                    .and_then(char::from_u32),
+            };
+            c.map(|c| (c, end))
+        });
+
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Hex to decimal. Needs a `0x` prefix or an a-f digit so plain numbers aren't reinterpreted.
+fn hex_to_dec(input: &str) -> Result<String> {
+    let input = input.trim();
+    let (digits, prefixed) = match input.strip_prefix("0x").or_else(|| input.strip_prefix("0X")) {
+        Some(digits) => (digits, true),
+        None => (input, false),
+    };
+    if !prefixed && !digits.chars().any(|c| c.is_ascii_alphabetic()) {
+        bail!("ambiguous hex number (prefix it with 0x)");
+    }
+    let value = u128::from_str_radix(digits, 16).map_err(|_| anyhow::anyhow!("not a hex number"))?;
+    Ok(value.to_string())
+}
+
+fn dec_to_hex(input: &str) -> Result<String> {
+    let input = input.trim();
+    if !input.chars().all(|c| c.is_ascii_digit()) {
+        bail!("not a decimal number");
+    }
+    let value: u128 = input.parse().map_err(|_| anyhow::anyhow!("not a decimal number"))?;
+    Ok(format!("0x{:x}", value))
+}
+
+/// How `clipstack split` cuts an entry into pieces
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum SplitBy {
+    Lines,
+    Words,
+    Commas,
+}
+
+/// Cut text into trimmed, non-empty pieces
+pub fn split(input: &str, by: SplitBy) -> Vec<&str> {
+    let pieces: Vec<&str> = match by {
+        SplitBy::Lines => input.lines().collect(),
+        SplitBy::Words => input.split_whitespace().collect(),
+        SplitBy::Commas => input.split(',').collect(),
+    };
+    pieces
+        .into_iter()
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+/// Split into lines, let `f` rearrange them, and rejoin keeping a trailing newline.
+/// Fails for single-line input since there's nothing to rearrange.
+fn map_lines(input: &str, f: impl FnOnce(&mut Vec<&str>)) -> Result<String> {
+    let mut lines: Vec<&str> = input.lines().collect();
+    if lines.len() < 2 {
+        bail!("needs more than one line");
+    }
+    f(&mut lines);
+
+    let mut out = lines.join("\n");
+    if input.ends_with('\n') {
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Fisher-Yates shuffle seeded from std's randomly keyed hasher
+fn shuffle(lines: &mut [&str]) {
+    use std::hash::{BuildHasher, Hasher};
+
+    let state = std::collections::hash_map::RandomState::new();
+    for i in (1..lines.len()).rev() {
+        let mut hasher = state.build_hasher();
+        hasher.write_usize(i);
+        let j = (hasher.finish() % (i as u64 + 1)) as usize;
+        lines.swap(i, j);
+    }
+}
+
+/// `sort | uniq -c | sort -rn`: distinct lines with occurrence counts, most frequent first
+fn count_lines(input: &str) -> Result<String> {
+    let lines: Vec<&str> = input.lines().collect();
+    if lines.len() < 2 {
+        bail!("needs more than one line");
+    }
+
+    // Each line's slot in `counts`, which stays in first-seen order
+    let mut slots: std::collections::HashMap<&str, usize> = std::collections::HashMap::new();
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+    for line in lines {
+        let slot = *slots.entry(line).or_insert_with(|| {
+            counts.push((line, 0));
+            counts.len() - 1
+        });
+        counts[slot].1 += 1;
+    }
+    // Stable sort keeps first-seen order among equal counts
+    counts.sort_by_key(|&(_, n)| std::cmp::Reverse(n));
+
+    let width = counts[0].1.to_string().len();
+    let mut out = String::new();
+    for (line, n) in counts {
+        out.push_str(&format!("{:>width$} {}\n", n, line));
+    }
+    if !input.ends_with('\n') {
+        out.pop();
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(name: &str) -> Option<String> {
+        match name {
+            "USER" => Some("alice".to_string()),
+            "AWS_PROFILE" => Some("prod".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_env_placeholders() {
+        assert_eq!(expand_with("hi {{USER}}", env), "hi alice");
+        assert_eq!(expand_with("--profile {{env:AWS_PROFILE}}", env), "--profile prod");
+        assert_eq!(expand_with("[{{MISSING}}]", env), "[]");
+    }
+
+    #[test]
+    fn test_unknown_placeholders_untouched() {
+        assert_eq!(expand_with("{{ foo }} {{lower}}", env), "{{ foo }} {{lower}}");
+        assert_eq!(expand_with("unclosed {{USER", env), "unclosed {{USER");
+        assert_eq!(expand_with("no placeholders", env), "no placeholders");
+    }
+
+    #[test]
+    fn test_date_placeholder() {
+        let today = Local::now().format("%Y-%m-%d").to_string();
+        assert_eq!(expand_with("{{date}}", env), today);
+
+        let year = Local::now().format("%Y").to_string();
+        assert_eq!(expand_with("(c) {{date:%Y}}", env), format!("(c) {}", year));
+    }
+
+    #[test]
+    fn test_invalid_date_format_does_not_panic() {
+        assert!(expand_with("{{date:%Q}}", env).contains("invalid format"));
+    }
+
+    #[test]
+    fn test_uuid_placeholder() {
+        let a = expand_with("{{uuid}}", env);
+        let b = expand_with("{{uuid}}", env);
+        assert_eq!(a.len(), 36);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_multibyte_text_around_placeholders() {
+        assert_eq!(expand_with("日本 {{USER}} 🎉", env), "日本 alice 🎉");
+    }
+
+    // ======== Calculator ========
+
+    #[test]
+    fn test_calculate_basic() {
+        assert_eq!(Transform::Calculate.apply("2*1024*1.07").unwrap(), "2191.36");
+        assert_eq!(Transform::Calculate.apply("1 + 2 * 3").unwrap(), "7");
+        assert_eq!(Transform::Calculate.apply("(1 + 2) * 3").unwrap(), "9");
+        assert_eq!(Transform::Calculate.apply("10 / 4").unwrap(), "2.5");
+        assert_eq!(Transform::Calculate.apply("2^10").unwrap(), "1024");
+        assert_eq!(Transform::Calculate.apply("2^3^2").unwrap(), "512");
+        assert_eq!(Transform::Calculate.apply("-3 + 5").unwrap(), "2");
+        assert_eq!(Transform::Calculate.apply("17 % 5").unwrap(), "2");
+        assert_eq!(Transform::Calculate.apply("1_000 x 3").unwrap(), "3000");
+        assert_eq!(Transform::Calculate.apply("0.1 + 0.2").unwrap(), "0.3");
+    }
+
+    #[test]
+    fn test_calculate_rejects_non_expressions() {
+        assert!(calculate("").is_err());
+        assert!(calculate("42").is_err(), "Lone number is not a calculation");
+        assert!(calculate("hello world").is_err());
+        assert!(calculate("1 +").is_err());
+        assert!(calculate("(1 + 2").is_err());
+        assert!(calculate("1 / 0").is_err());
+        assert!(calculate("2024-01-15T10:00").is_err());
+    }
+
+    #[test]
+    fn test_calculate_limits_nesting() {
+        assert_eq!(calculate(&format!("{}1{}+1", "(".repeat(100), ")".repeat(100))).unwrap(), 2.0);
+        for input in ["(".repeat(200_000), "-".repeat(300_000) + "1", "2^".repeat(100_000) + "1"] {
+            let err = calculate(&input).unwrap_err();
+            assert_eq!(err.to_string(), "expression is nested too deeply");
+        }
+        assert!(applicable(&"(".repeat(200_000)).iter().all(|(t, _)| *t != Transform::Calculate));
+    }
+
+    #[test]
+    fn test_applicable_only_lists_successful_transforms() {
+        assert_eq!(applicable("6*7")[0], (Transform::Calculate, "42".to_string()));
+        // Only encoding applies to everything
+        let actions = applicable("...");
+        assert_eq!(actions, [(Transform::Base64Encode, "Li4u".to_string())]);
+        assert!(applicable(" \n ").is_empty());
+    }
+
+    #[test]
+    fn test_whitespace_and_letter_case() {
+        assert_eq!(Transform::Trim.apply("  ssh prod \n").unwrap(), "ssh prod");
+        assert!(Transform::Trim.apply("tidy").is_err());
+        assert!(Transform::Trim.apply(" \n ").is_err());
+        assert_eq!(Transform::OneLine.apply("select *\n  from t\n").unwrap(), "select * from t");
+        assert!(Transform::OneLine.apply("one  line\n").is_err());
+        assert_eq!(Transform::Uppercase.apply("Straße").unwrap(), "STRASSE");
+        assert_eq!(Transform::Lowercase.apply("ÉCOLE").unwrap(), "école");
+        assert!(Transform::Lowercase.apply("lower 42").is_err());
+    }
+
+    #[test]
+    fn test_base64_encode_decode() {
+        assert_eq!(Transform::Base64Encode.apply("héllo").unwrap(), "aMOpbGxv");
+        assert_eq!(Transform::Base64Decode.apply("aMOpbGxv\n").unwrap(), "héllo");
+        assert_eq!(Transform::Base64Decode.apply("aGk=").unwrap(), "hi");
+        assert!(Transform::Base64Decode.apply("hi").is_err());
+        assert!(Transform::Base64Decode.apply("abcd").is_err(), "Decodes to binary");
+        assert!(Transform::Base64Decode.apply("not base64!").is_err());
+    }
+
+    #[test]
+    fn test_json_pretty_and_minify_keep_order() {
+        let compact = r#"{"z":1,"a":[1.50,"x, y: {\"q\"}"],"e":{},"l":[]}"#;
+        let pretty = Transform::JsonPretty.apply(compact).unwrap();
+        assert_eq!(
+            pretty,
+            "{\n  \"z\": 1,\n  \"a\": [\n    1.50,\n    \"x, y: {\\\"q\\\"}\"\n  ],\n  \
+             \"e\": {},\n  \"l\": []\n}"
+        );
+        assert_eq!(Transform::JsonMinify.apply(&pretty).unwrap(), compact);
+        assert!(Transform::JsonPretty.apply(&pretty).is_err(), "Already pretty");
+        assert!(Transform::JsonMinify.apply("{\"a\": oops}").is_err());
+        assert!(Transform::JsonPretty.apply("42").is_err());
+    }
+
+    // ======== Case and format conversions ========
+
+    #[test]
+    fn test_case_conversions() {
+        assert_eq!(Transform::SnakeCase.apply("parseHTTPResponse").unwrap(), "parse_http_response");
+        assert_eq!(Transform::KebabCase.apply("user_id").unwrap(), "user-id");
+        assert_eq!(Transform::CamelCase.apply("user-id").unwrap(), "userId");
+        assert_eq!(Transform::CamelCase.apply("Max Entries 2").unwrap(), "maxEntries2");
+        assert_eq!(Transform::SnakeCase.apply("MaxEntries").unwrap(), "max_entries");
+    }
+
+    #[test]
+    fn test_case_conversions_reject_prose_and_no_ops() {
+        assert!(Transform::SnakeCase.apply("Hello, world!").is_err());
+        assert!(Transform::SnakeCase.apply("already_snake").is_err());
+        assert!(Transform::CamelCase.apply("line one\nline two").is_err());
+    }
+
+    #[test]
+    fn test_url_encode_decode() {
+        assert_eq!(Transform::UrlEncode.apply("a b&c=ü").unwrap(), "a%20b%26c%3D%C3%BC");
+        assert_eq!(Transform::UrlDecode.apply("a%20b%26c%3D%C3%BC").unwrap(), "a b&c=ü");
+        assert!(Transform::UrlDecode.apply("100%").is_err());
+        assert!(Transform::UrlDecode.apply("%FF").is_err(), "Invalid UTF-8");
+        assert!(Transform::UrlEncode.apply("plain").is_err());
+    }
+
+    #[test]
+    fn test_html_encode_decode() {
+        assert_eq!(
+            Transform::HtmlEncode.apply("<a href=\"x\">Tom & Jerry's</a>").unwrap(),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&#39;s&lt;/a&gt;"
+        );
+        assert_eq!(
+            Transform::HtmlDecode.apply("&lt;b&gt; &#39;q&#x27; &amp;amp; &bogus;").unwrap(),
+            "<b> 'q' &amp; &bogus;"
+        );
+        assert!(Transform::HtmlDecode.apply("fish & chips").is_err());
+    }
+
+    #[test]
+    fn test_line_transforms() {
+        let list = "pear\napple\npear\nfig\n";
+        assert_eq!(Transform::SortLines.apply(list).unwrap(), "apple\nfig\npear\npear\n");
+        assert_eq!(Transform::UniqueLines.apply(list).unwrap(), "pear\napple\nfig\n");
+        assert_eq!(Transform::ReverseLines.apply(list).unwrap(), "fig\npear\napple\npear\n");
+        assert_eq!(Transform::CountLines.apply(list).unwrap(), "2 pear\n1 apple\n1 fig\n");
+
+        // Many distinct lines stay fast enough for the action menu
+        let many: Vec<String> = (0..60_000).map(|i| format!("line {}", i)).collect();
+        let counted = Transform::CountLines.apply(&many.join("\n")).unwrap();
+        assert_eq!(counted.lines().count(), 60_000);
+        assert!(counted.starts_with("1 line 0\n1 line 1\n"));
+
+        let shuffled = Transform::ShuffleLines.apply("a\nb\nc").unwrap();
+        let mut lines: Vec<&str> = shuffled.lines().collect();
+        lines.sort();
+        assert_eq!(lines, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_line_transforms_need_multiple_lines() {
+        assert!(Transform::SortLines.apply("single line\n").is_err());
+        assert!(Transform::CountLines.apply("single").is_err());
+        assert!(Transform::SortLines.apply("a\nb").is_err(), "Already sorted");
+        assert!(Transform::UniqueLines.apply("a\nb").is_err(), "Nothing to dedupe");
+    }
+
+    #[test]
+    fn test_split() {
+        let urls = "https://a.example\n\n  https://b.example  \r\n";
+        assert_eq!(split(urls, SplitBy::Lines), vec!["https://a.example", "https://b.example"]);
+        assert_eq!(split("id1  id2\tid3", SplitBy::Words), vec!["id1", "id2", "id3"]);
+        assert_eq!(split("a, b,,c ", SplitBy::Commas), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_hex_dec_conversion() {
+        assert_eq!(Transform::HexToDec.apply("0xff").unwrap(), "255");
+        assert_eq!(Transform::HexToDec.apply("deadBEEF").unwrap(), "3735928559");
+        assert!(Transform::HexToDec.apply("10").is_err(), "Ambiguous without 0x");
+        assert!(Transform::HexToDec.apply("0xzz").is_err());
+
+        assert_eq!(Transform::DecToHex.apply("255").unwrap(), "0xff");
+        assert!(Transform::DecToHex.apply("-5").is_err());
+        assert!(Transform::DecToHex.apply("12ab").is_err());
+    }
+}
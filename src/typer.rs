@@ -0,0 +1,109 @@
+use crate::config::TypeTool;
+use anyhow::{bail, Context, Result};
+use std::ffi::OsStr;
+use std::io::Write;
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+
+/// Seconds to wait before typing, for the picker's terminal to close and the
+/// app it was opened over to get focus back
+const TYPE_DELAY: &str = "0.3";
+
+/// Longest text typed; key by key, more would take minutes
+pub const MAX_TYPED: usize = 16 * 1024;
+
+/// The program that types for `tool`, looked up in `path` (a `$PATH` value):
+/// wtype for `auto` when it is installed, ydotool otherwise
+fn resolve(tool: TypeTool, path: &OsStr) -> Result<TypeTool> {
+    let installed = |name: &str| std::env::split_paths(path).any(|dir| dir.join(name).is_file());
+    let found = match tool {
+        TypeTool::Auto => [TypeTool::Wtype, TypeTool::Ydotool]
+            .into_iter()
+            .find(|tool| installed(tool.program())),
+        tool => Some(tool).filter(|tool| installed(tool.program())),
+    };
+    match found {
+        Some(tool) => Ok(tool),
+        None if tool == TypeTool::Auto => bail!("neither wtype nor ydotool is installed"),
+        None => bail!("{} is not installed", tool.program()),
+    }
+}
+
+/// Its arguments to type text read from stdin
+fn args(tool: TypeTool) -> &'static [&'static str] {
+    match tool {
+        TypeTool::Ydotool => &["type", "--file", "-"],
+        _ => &["-"],
+    }
+}
+
+/// Type `text` into the focused window shortly after clipstack exits, with
+/// wtype or ydotool, and return the program used. The text goes through a
+/// pipe rather than the command line, where other users could see it. The
+/// typist runs in a session of its own so it outlives the picker's terminal.
+pub fn type_later(tool: TypeTool, text: &str) -> Result<&'static str> {
+    if text.len() > MAX_TYPED {
+        bail!("{} bytes is too long to type (at most {})", text.len(), MAX_TYPED);
+    }
+    let tool = resolve(tool, &std::env::var_os("PATH").unwrap_or_default())?;
+    let program = tool.program();
+
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c")
+        .arg("sleep \"$0\" && exec \"$@\"")
+        .arg(TYPE_DELAY)
+        .arg(program)
+        .args(args(tool))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    // SAFETY: setsid is async-signal-safe and touches no memory of the parent
+    unsafe {
+        cmd.pre_exec(|| {
+            if libc::setsid() == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let mut child = cmd.spawn().with_context(|| format!("Failed to start {}", program))?;
+    // The pipe holds all of MAX_TYPED, so this returns before typing starts
+    let mut stdin = child.stdin.take().context("Failed to open a pipe to the typist")?;
+    stdin
+        .write_all(text.as_bytes())
+        .with_context(|| format!("Failed to hand the text to {}", program))?;
+    Ok(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn install(dir: &TempDir, name: &str) {
+        fs::write(dir.path().join(name), "#!/bin/sh\n").unwrap();
+    }
+
+    #[test]
+    fn test_resolve_prefers_wtype() {
+        let [both, only_ydotool, none] = [(); 3].map(|_| TempDir::new().unwrap());
+        install(&both, "wtype");
+        install(&both, "ydotool");
+        install(&only_ydotool, "ydotool");
+        let path = |dir: &TempDir| dir.path().as_os_str().to_owned();
+
+        assert_eq!(resolve(TypeTool::Auto, &path(&both)).unwrap(), TypeTool::Wtype);
+        assert_eq!(resolve(TypeTool::Ydotool, &path(&both)).unwrap(), TypeTool::Ydotool);
+        assert_eq!(resolve(TypeTool::Auto, &path(&only_ydotool)).unwrap(), TypeTool::Ydotool);
+        let err = resolve(TypeTool::Wtype, &path(&only_ydotool)).unwrap_err();
+        assert_eq!(err.to_string(), "wtype is not installed");
+        assert!(resolve(TypeTool::Auto, &path(&none)).is_err());
+    }
+
+    #[test]
+    fn test_long_text_is_not_typed() {
+        let err = type_later(TypeTool::Auto, &"x".repeat(MAX_TYPED + 1)).unwrap_err();
+        assert!(err.to_string().contains("too long to type"));
+    }
+}
@@ -0,0 +1,98 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::process::Command;
+
+/// Latest published release; only fetched when `check-update` is run
+const LATEST_RELEASE_URL: &str =
+    "https://api.github.com/repos/theirongolddev/clipstack/releases/latest";
+
+/// Give up on a slow network rather than hang the terminal
+const REQUEST_TIMEOUT_SECS: u32 = 10;
+
+/// Changelog lines shown for a newer release
+pub const MAX_HIGHLIGHTS: usize = 8;
+
+/// The parts of a GitHub release we use
+#[derive(Debug, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    pub html_url: String,
+    #[serde(default)]
+    pub body: Option<String>,
+}
+
+/// Fetch the latest release with curl. Nothing but the request itself is sent:
+/// no identifiers, no installed version.
+pub fn fetch_latest() -> Result<Release> {
+    let output = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location"])
+        .args(["--max-time", &REQUEST_TIMEOUT_SECS.to_string()])
+        .args(["--header", "Accept: application/vnd.github+json"])
+        .arg(LATEST_RELEASE_URL)
+        .output()
+        .context("Failed to run curl (is it installed?)")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Could not fetch the latest release: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    serde_json::from_slice(&output.stdout).context("Unexpected response from GitHub")
+}
+
+/// `1.2.3` from tags like `v1.2.3`, `1.2` or `1.2.3-rc1`; pre-release suffixes
+/// are ignored
+pub fn parse_version(tag: &str) -> Option<(u64, u64, u64)> {
+    let version = tag.trim().trim_start_matches('v');
+    let version = version.split(['-', '+']).next()?;
+    let mut parts = version.split('.').map(|p| p.parse::<u64>());
+    let major = parts.next()?.ok()?;
+    let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+    let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+    parts.next().is_none().then_some((major, minor, patch))
+}
+
+/// Whether `latest` is a newer version than `current`; None if either is unparseable
+pub fn is_newer(current: &str, latest: &str) -> Option<bool> {
+    Some(parse_version(latest)? > parse_version(current)?)
+}
+
+/// Bullet points from release notes, without their markers
+pub fn highlights(body: &str, limit: usize) -> Vec<String> {
+    body.lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            line.strip_prefix("- ").or_else(|| line.strip_prefix("* "))
+        })
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .take(limit)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(parse_version("v1.2.3"), Some((1, 2, 3)));
+        assert_eq!(parse_version("0.10"), Some((0, 10, 0)));
+        assert_eq!(parse_version("2.0.0-rc1"), Some((2, 0, 0)));
+        assert_eq!(parse_version("nightly"), None);
+        assert_eq!(parse_version("1.2.3.4"), None);
+
+        assert_eq!(is_newer("0.1.0", "v0.2.0"), Some(true));
+        assert_eq!(is_newer("0.10.0", "v0.9.9"), Some(false));
+        assert_eq!(is_newer("0.1.0", "v0.1.0"), Some(false));
+        assert_eq!(is_newer("0.1.0", "latest"), None);
+    }
+
+    #[test]
+    fn test_highlights() {
+        let body = "## What's new\r\n- Faster picker\r\n* Trash bin\n  - \nSee the docs.\n- Third";
+        assert_eq!(highlights(body, 10), ["Faster picker", "Trash bin", "Third"]);
+        assert_eq!(highlights(body, 1), ["Faster picker"]);
+        assert!(highlights("", 5).is_empty());
+    }
+}
@@ -0,0 +1,125 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+/// File in the storage dir the totals are kept in
+pub const USAGE_FILE_NAME: &str = "usage.json";
+
+/// Picker session totals, recorded when `picker.usage_stats` is on and shown by
+/// `clipstack stats --usage`. Nothing leaves the machine.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageStats {
+    pub sessions: u64,
+    /// Sessions that ended with an entry copied
+    pub selections: u64,
+    /// Sessions closed without copying anything
+    pub abandoned: u64,
+    /// Times search was started, by `/` or typing
+    pub searches: u64,
+    /// Sessions that searched at least once
+    pub searched_sessions: u64,
+    /// Picker open to copy, summed over selections, in milliseconds
+    pub select_ms_total: u64,
+    pub select_ms_max: u64,
+}
+
+impl UsageStats {
+    /// Add one finished picker session
+    pub fn record(&mut self, elapsed: Duration, searches: u64, selected: bool) {
+        self.sessions += 1;
+        self.searches += searches;
+        if searches > 0 {
+            self.searched_sessions += 1;
+        }
+        if selected {
+            let ms = elapsed.as_millis() as u64;
+            self.selections += 1;
+            self.select_ms_total += ms;
+            self.select_ms_max = self.select_ms_max.max(ms);
+        } else {
+            self.abandoned += 1;
+        }
+    }
+
+    /// Average time from opening the picker to copying an entry
+    pub fn select_avg(&self) -> Duration {
+        Duration::from_millis(self.select_ms_total.checked_div(self.selections).unwrap_or(0))
+    }
+}
+
+/// Totals so far (zero if nothing was recorded yet)
+pub fn read(path: &Path) -> UsageStats {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+/// Add a session to the totals in `path`
+pub fn record(path: &Path, elapsed: Duration, searches: u64, selected: bool) -> Result<()> {
+    let mut stats = read(path);
+    stats.record(elapsed, searches, selected);
+    let json = serde_json::to_string(&stats)?;
+    fs::write(path, json).with_context(|| format!("Failed to write {:?}", path))
+}
+
+/// Print the `clipstack stats --usage` section
+pub fn print(stats: &UsageStats, enabled: bool) {
+    if stats.sessions == 0 {
+        if enabled {
+            println!("Usage:       no picker sessions recorded yet");
+        } else {
+            println!("Usage:       not recorded (set usage_stats = true under [picker])");
+        }
+        return;
+    }
+
+    let percent = |n: u64| n as f64 * 100.0 / stats.sessions as f64;
+    println!("Usage:       {} picker sessions", stats.sessions);
+    println!("  Selected:  {} ({:.0}%)", stats.selections, percent(stats.selections));
+    println!("  Abandoned: {} ({:.0}%)", stats.abandoned, percent(stats.abandoned));
+    println!(
+        "  Searched:  {} sessions ({:.1} searches each)",
+        stats.searched_sessions,
+        stats.searches as f64 / stats.searched_sessions.max(1) as f64
+    );
+    if stats.selections > 0 {
+        println!(
+            "  To paste:  avg {:.1}s, max {:.1}s",
+            stats.select_avg().as_secs_f64(),
+            Duration::from_millis(stats.select_ms_max).as_secs_f64()
+        );
+    }
+    if !enabled {
+        println!("  (recording is off; set usage_stats = true under [picker])");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_record_sessions() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(USAGE_FILE_NAME);
+        assert_eq!(read(&path), UsageStats::default());
+
+        record(&path, Duration::from_millis(1200), 2, true).unwrap();
+        record(&path, Duration::from_millis(800), 0, true).unwrap();
+        record(&path, Duration::from_secs(5), 1, false).unwrap();
+
+        let stats = read(&path);
+        assert_eq!(stats.sessions, 3);
+        assert_eq!(stats.selections, 2);
+        assert_eq!(stats.abandoned, 1);
+        assert_eq!(stats.searches, 3);
+        assert_eq!(stats.searched_sessions, 2);
+        assert_eq!(stats.select_avg(), Duration::from_millis(1000));
+        assert_eq!(stats.select_ms_max, 1200);
+    }
+}
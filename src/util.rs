@@ -1,18 +1,91 @@
 use sha2::{Digest, Sha256};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::cell::OnceCell;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-/// Compute SHA256 hash of content and return as raw bytes
+/// Content hash algorithms; stored hashes are prefixed with the name (`blake3:<hex>`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    /// Legacy algorithm of entries written by older versions
+    Sha256,
+    /// Default for new entries, several times faster on large captures
+    Blake3,
+}
+
+impl HashAlgorithm {
+    pub const ALL: &'static [HashAlgorithm] = &[HashAlgorithm::Sha256, HashAlgorithm::Blake3];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Blake3 => "blake3",
+        }
+    }
+
+    /// Algorithm of a stored hash string (unprefixed hashes predate prefixes: sha256)
+    pub fn of(hash: &str) -> Option<Self> {
+        match hash.split_once(':') {
+            Some(("sha256", _)) | None => Some(HashAlgorithm::Sha256),
+            Some(("blake3", _)) => Some(HashAlgorithm::Blake3),
+            Some(_) => None,
+        }
+    }
+
+    /// Raw digest bytes
+    pub fn digest(self, content: &str) -> Vec<u8> {
+        match self {
+            HashAlgorithm::Sha256 => Sha256::digest(content.as_bytes()).to_vec(),
+            HashAlgorithm::Blake3 => blake3::hash(content.as_bytes()).as_bytes().to_vec(),
+        }
+    }
+
+    /// Prefixed hex digest as stored in the index
+    pub fn hash_string(self, content: &str) -> String {
+        let hex: String = self.digest(content).iter().map(|b| format!("{:02x}", b)).collect();
+        format!("{}:{}", self.name(), hex)
+    }
+}
+
+/// Compute the default (BLAKE3) hash of content as raw bytes, for change detection
 pub fn compute_hash(content: &str) -> Vec<u8> {
-    let mut hasher = Sha256::new();
-    hasher.update(content.as_bytes());
-    hasher.finalize().to_vec()
+    HashAlgorithm::Blake3.digest(content)
 }
 
-/// Compute SHA256 hash and return as formatted string (sha256:hex)
+/// Compute the default hash as a prefixed string (blake3:hex)
 pub fn compute_hash_string(content: &str) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(content.as_bytes());
-    format!("sha256:{:x}", hasher.finalize())
+    HashAlgorithm::Blake3.hash_string(content)
+}
+
+/// Checks one piece of content against stored hashes of any algorithm,
+/// hashing it at most once per algorithm actually encountered
+pub struct HashMatcher<'a> {
+    content: &'a str,
+    sha256: OnceCell<String>,
+    blake3: OnceCell<String>,
+}
+
+impl<'a> HashMatcher<'a> {
+    pub fn new(content: &'a str) -> Self {
+        Self {
+            content,
+            sha256: OnceCell::new(),
+            blake3: OnceCell::new(),
+        }
+    }
+
+    pub fn content(&self) -> &'a str {
+        self.content
+    }
+
+    pub fn matches(&self, stored: &str) -> bool {
+        let (algo, cell) = match HashAlgorithm::of(stored) {
+            Some(HashAlgorithm::Sha256) => (HashAlgorithm::Sha256, &self.sha256),
+            Some(HashAlgorithm::Blake3) => (HashAlgorithm::Blake3, &self.blake3),
+            None => return false,
+        };
+        let computed = cell.get_or_init(|| algo.hash_string(self.content));
+        // Unprefixed legacy hashes are bare sha256 hex
+        stored == computed || computed.strip_prefix("sha256:") == Some(stored)
+    }
 }
 
 /// Format bytes into human-readable size
@@ -26,6 +99,26 @@ pub fn format_size(bytes: usize) -> String {
     }
 }
 
+/// Parse a size like "50MB", "512KB", "1.5GB" or "4096" (bytes); units are
+/// powers of 1024 like `format_size`'s, case-insensitive
+pub fn parse_size(s: &str) -> Result<usize, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let value: f64 = num
+        .parse()
+        .map_err(|_| format!("invalid size '{}' (expected e.g. 512KB, 50MB, 1GB)", s))?;
+
+    let scale = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1.0,
+        "K" | "KB" => 1024.0,
+        "M" | "MB" => 1024.0 * 1024.0,
+        "G" | "GB" => 1024.0 * 1024.0 * 1024.0,
+        _ => return Err(format!("unknown size unit '{}' (use B, KB, MB or GB)", unit)),
+    };
+    Ok((value * scale) as usize)
+}
+
 /// Format timestamp as relative time (e.g., "5m ago", "2h ago")
 pub fn format_relative_time(timestamp: i64) -> String {
     let now = SystemTime::now()
@@ -42,10 +135,223 @@ pub fn format_relative_time(timestamp: i64) -> String {
     }
 }
 
+/// Current Unix time in milliseconds
+pub fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64
+}
+
+/// Parse a human duration like "250ms", "30s", "10m", "2h" or "1d" (bare numbers are seconds)
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let value: u64 = num
+        .parse()
+        .map_err(|_| format!("invalid duration '{}' (expected e.g. 30s, 10m, 2h, 1d)", s))?;
+
+    let secs = match unit {
+        "ms" => return Ok(Duration::from_millis(value)),
+        "" | "s" => Some(value),
+        "m" => value.checked_mul(60),
+        "h" => value.checked_mul(3600),
+        "d" => value.checked_mul(86400),
+        _ => return Err(format!("unknown duration unit '{}' (use ms, s, m, h or d)", unit)),
+    };
+    let secs = secs.ok_or_else(|| format!("duration '{}' is too long", s))?;
+    Ok(Duration::from_secs(secs))
+}
+
+/// A duration in milliseconds, capped at `i64::MAX` for adding to timestamps
+pub fn duration_millis(duration: Duration) -> i64 {
+    i64::try_from(duration.as_millis()).unwrap_or(i64::MAX)
+}
+
+/// Format a duration compactly (e.g., "250ms", "45s", "9m", "2h", "3d")
+pub fn format_duration(duration: Duration) -> String {
+    if duration < Duration::from_secs(1) {
+        return format!("{}ms", duration.as_millis());
+    }
+    let secs = duration.as_secs();
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Standard padded base64, for storing binary data where text is expected
+pub fn encode_base64(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Decode `encode_base64` output
+pub fn decode_base64(text: &str) -> Result<Vec<u8>, String> {
+    let text = text.trim_end_matches('=');
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let mut bits = 0u32;
+    let mut count = 0;
+    for c in text.bytes() {
+        let value = BASE64
+            .iter()
+            .position(|&b| b == c)
+            .ok_or_else(|| format!("invalid base64 character '{}'", c as char))?;
+        bits = bits << 6 | value as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            out.push((bits >> count) as u8);
+        }
+    }
+    Ok(out)
+}
+
+/// Whether data would print as garbage: not UTF-8, or holding a NUL or more
+/// than one control character (whitespace aside) in sixteen
+pub fn looks_binary(data: &[u8]) -> bool {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return true;
+    };
+    let mut chars = 0;
+    let mut controls = 0;
+    for c in text.chars() {
+        if c == '\0' {
+            return true;
+        }
+        chars += 1;
+        if c.is_control() && !c.is_whitespace() {
+            controls += 1;
+        }
+    }
+    controls * 16 > chars
+}
+
+/// Rows of offset, hex bytes and printable ASCII, like `xxd`: sixteen bytes a
+/// row, or eight when fewer than `width` columns fit that. Past `limit`
+/// bytes the rest are only counted.
+pub fn hex_dump(data: &[u8], width: usize, limit: usize) -> String {
+    let per_row = if width >= 78 { 16 } else { 8 };
+    let mut out = String::new();
+    for (row, chunk) in data[..data.len().min(limit)].chunks(per_row).enumerate() {
+        out.push_str(&format!("{:08x} ", row * per_row));
+        for i in 0..per_row {
+            if i % 8 == 0 {
+                out.push(' ');
+            }
+            match chunk.get(i) {
+                Some(byte) => out.push_str(&format!("{:02x} ", byte)),
+                None => out.push_str("   "),
+            }
+        }
+        out.push_str(" |");
+        out.extend(chunk.iter().map(|&b| match b {
+            0x20..=0x7e => b as char,
+            _ => '.',
+        }));
+        out.push_str("|\n");
+    }
+    if data.len() > limit {
+        out.push_str(&format!("⋯ {} more not shown\n", format_size(data.len() - limit)));
+    }
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_looks_binary() {
+        assert!(!looks_binary(b"plain text\twith tabs\r\n"));
+        assert!(!looks_binary("\x1b[31mred\x1b[0m and some more words".as_bytes()));
+        assert!(looks_binary(b"abc\0def"));
+        assert!(looks_binary(&[0xff, 0xfe, 0x41]));
+        assert!(looks_binary(b"\x01\x02\x03text"));
+    }
+
+    #[test]
+    fn test_hex_dump() {
+        let data: Vec<u8> = b"Hello\0world, and more".to_vec();
+        assert_eq!(
+            hex_dump(&data, 80, 1024),
+            "00000000  48 65 6c 6c 6f 00 77 6f  72 6c 64 2c 20 61 6e 64  |Hello.world, and|\n\
+             00000010  20 6d 6f 72 65                                    | more|\n"
+        );
+        assert_eq!(
+            hex_dump(&data[..10], 40, 1024),
+            "00000000  48 65 6c 6c 6f 00 77 6f  |Hello.wo|\n\
+             00000008  72 6c                    |rl|\n"
+        );
+        let big = vec![0u8; 5000];
+        let dump = hex_dump(&big, 80, 4096);
+        assert_eq!(dump.lines().count(), 256 + 1);
+        assert!(dump.ends_with("⋯ 904B more not shown\n"));
+    }
+
+    #[test]
+    fn test_base64_round_trip() {
+        assert_eq!(encode_base64(b""), "");
+        assert_eq!(encode_base64(b"f"), "Zg==");
+        assert_eq!(encode_base64(b"fo"), "Zm8=");
+        assert_eq!(encode_base64(b"foobar"), "Zm9vYmFy");
+        let binary: Vec<u8> = (0..=255).collect();
+        assert_eq!(decode_base64(&encode_base64(&binary)).unwrap(), binary);
+        assert_eq!(decode_base64("Zm8=").unwrap(), b"fo");
+        assert!(decode_base64("Zm*=").is_err());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size("512KB"), Ok(512 * 1024));
+        assert_eq!(parse_size("50mb"), Ok(50 * 1024 * 1024));
+        assert_eq!(parse_size("1.5G"), Ok(3 * 512 * 1024 * 1024));
+        assert_eq!(parse_size(&format_size(50 * 1024 * 1024)), Ok(50 * 1024 * 1024));
+        assert!(parse_size("lots").is_err());
+        assert!(parse_size("10TB").is_err());
+    }
+
+    #[test]
+    fn test_hash_strings_are_prefixed() {
+        let hash = compute_hash_string("hello");
+        assert!(hash.starts_with("blake3:"));
+        assert_eq!(hash.len(), "blake3:".len() + 64);
+        assert_eq!(HashAlgorithm::of(&hash), Some(HashAlgorithm::Blake3));
+        assert_eq!(
+            HashAlgorithm::Sha256.hash_string("hello"),
+            "sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+    }
+
+    #[test]
+    fn test_hash_matcher_accepts_legacy_sha256() {
+        let matcher = HashMatcher::new("hello");
+        let sha = HashAlgorithm::Sha256.hash_string("hello");
+
+        assert!(matcher.matches(&compute_hash_string("hello")));
+        assert!(matcher.matches(&sha));
+        assert!(matcher.matches(sha.strip_prefix("sha256:").unwrap()), "Unprefixed legacy hash");
+        assert!(!matcher.matches(&compute_hash_string("other")));
+        assert!(!matcher.matches("md5:5d41402abc4b2a76b9719d911017c592"));
+    }
+
     #[test]
     fn test_format_size() {
         assert_eq!(format_size(0), "0B");
@@ -78,4 +384,29 @@ mod tests {
         // 3 days ago
         assert_eq!(format_relative_time(now - 259_200_000), "3d ago");
     }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("10m").unwrap(), Duration::from_secs(600));
+        assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7200));
+        assert_eq!(parse_duration("1d").unwrap(), Duration::from_secs(86400));
+        assert_eq!(parse_duration("250ms").unwrap(), Duration::from_millis(250));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("10x").is_err());
+        assert!(parse_duration("m").is_err());
+        let err = parse_duration("999999999999999999d").unwrap_err();
+        assert!(err.contains("too long"));
+        assert_eq!(duration_millis(Duration::from_secs(u64::MAX)), i64::MAX);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_millis(250)), "250ms");
+        assert_eq!(format_duration(Duration::from_secs(45)), "45s");
+        assert_eq!(format_duration(Duration::from_secs(540)), "9m");
+        assert_eq!(format_duration(Duration::from_secs(7200)), "2h");
+        assert_eq!(format_duration(Duration::from_secs(259_200)), "3d");
+    }
 }